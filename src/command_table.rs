@@ -19,7 +19,7 @@ pub struct CommandTableEntry {
     /// Name of a command. This is used for command line entry.
     pub name: String,
     /// The underlying command that can be executed.
-    pub command: Rc<Command>,
+    pub command: Rc<dyn Command>,
 }
 
 /// Types of menu items.
@@ -61,9 +61,9 @@ impl CommandTable {
                commands: Vec<CommandTableEntry>)
                -> Rc<CommandTable> {
         Rc::new(CommandTable {
-            name: name,
-            inherit: inherit,
-            commands: commands,
+            name,
+            inherit,
+            commands,
             menu_definition: None,
         })
     }
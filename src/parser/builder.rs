@@ -17,9 +17,16 @@
 //!                                 .help("This is just a test parameter.")
 //!                                 .finalize())
 //!                  .finalize());
+//! tree.command(Command::new("help").wraps("".to_string()).finalize());
+//! let root = tree.freeze().unwrap();
 //! ```
 
-use std::rc::Rc;
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 use super::nodes::*;
 
 /// Indicate the type of parameter, so that the correct class and node
@@ -36,14 +43,30 @@ pub enum ParameterKind {
 
 /// Store a command tree while populating it. This can be used
 /// to construct a `RootNode` to be used with the `Parser`.
+///
+/// A `CommandTree` is the mutable stage of building a tree: once
+/// all of the commands have been added, `freeze` constructs the
+/// nodes and freezes them so that they can't be modified while
+/// they are in use. `finalize` constructs a tree which may still
+/// be modified, and may be called any number of times, each time
+/// creating an independent tree.
+#[derive(Default)]
 pub struct CommandTree {
     commands: Vec<Command>,
+    case_sensitivity: CaseSensitivity,
+    negations: Vec<String>,
+    frozen: bool,
 }
 
 impl CommandTree {
     /// Create a new `CommandTree`.
     pub fn new() -> Self {
-        CommandTree { commands: vec![] }
+        CommandTree {
+            commands: vec![],
+            case_sensitivity: CaseSensitivity::Sensitive,
+            negations: vec![],
+            frozen: false,
+        }
     }
 
     /// Add a `Command` to the `CommandTree`.
@@ -51,48 +74,293 @@ impl CommandTree {
         self.commands.push(command);
     }
 
-    fn build_parameter(self, parameter: Parameter) -> Rc<Node> {
+    /// Set how the names of all of the nodes in the tree are matched
+    /// against tokens. By default, they are `CaseSensitivity::Sensitive`.
+    pub fn case_sensitivity(&mut self, case_sensitivity: CaseSensitivity) {
+        self.case_sensitivity = case_sensitivity;
+    }
+
+    /// Add a `no` command, a `NegationNode`, beneath the command named
+    /// by `level`, or the root when it is `""`, so that the negatable
+    /// commands which follow it there can be negated.
+    ///
+    /// As with a wrapper around the root, the tree will not be freed.
+    pub fn negation(&mut self, level: &str) {
+        self.negations.push(level.to_string());
+    }
+
+    /// Check the commands in this tree for consistency.
+    ///
+    /// Every parameter named by `Parameter::requires` or
+    /// `Parameter::conflicts_with` must be another parameter of
+    /// the same command, and requirements may not form a cycle.
+    /// Only the last simple (positional) parameter of a command
+    /// may be repeatable.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        for command in &self.commands {
+            command.validate_requirements()?;
+            command.validate_conflicts()?;
+            command.validate_positional()?;
+            command.validate_timeout()?;
+        }
+        Ok(())
+    }
+
+    /// Find what is unusual in the commands of this tree, though
+    /// the tree can still be built, such as flags which are required
+    /// and so must always be given.
+    pub fn warnings(&self) -> Vec<BuildWarning> {
+        let mut warnings = vec![];
+        for command in &self.commands {
+            for parameter in &command.parameters {
+                if matches!(parameter.parameter_kind, ParameterKind::Flag) && parameter.required {
+                    warnings.push(BuildWarning::RequiredFlag {
+                        command: command.name.clone(),
+                        parameter: parameter.name.clone(),
+                    });
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Construct the tree of nodes described by this `CommandTree`,
+    /// after checking it with `validate`.
+    ///
+    /// Commands named by several words, like `"show interface"`, are
+    /// added beneath the commands named by the leading words. Those are
+    /// created without a handler when they haven't been described.
+    /// Wrapping commands are added last, so that they may wrap any
+    /// other command, given by its path, or the root, given by `""`.
+    /// `no` commands are added after them. Every node is given the
+    /// tree's `case_sensitivity`.
+    pub fn finalize(&self) -> Result<Arc<RootNode>, BuildError> {
+        self.validate()?;
+        let root = RootNode::new();
+        let mut commands = self.commands
+                               .iter()
+                               .filter(|c| c.wrapped_root.is_none())
+                               .collect::<Vec<_>>();
+        // Build shorter paths first so that described commands are
+        // found, rather than created, when longer paths are built.
+        commands.sort_by_key(|c| c.name.split_whitespace().count());
+        for command in commands {
+            let (parent, name) = self.parent_node(&root, &command.name);
+            if find_successor(&*parent, name).is_some() {
+                return Err(BuildError::DuplicateCommand { command: command.name.clone() });
+            }
+            let parameters = command.parameters
+                                    .iter()
+                                    .map(|p| self.build_parameter(p))
+                                    .collect();
+            let mut node = CommandNode::new(name,
+                                            command.priority,
+                                            command.hidden,
+                                            command.help_text.clone(),
+                                            command.boxed_handler(),
+                                            parameters);
+            if let Some(ref payload) = command.payload {
+                Arc::get_mut(&mut node).unwrap().set_payload(payload.clone());
+            }
+            if let Some(ref message) = command.deprecated {
+                Arc::get_mut(&mut node).unwrap().set_deprecated(message);
+            }
+            Arc::get_mut(&mut node).unwrap().set_negatable(command.negatable);
+            if let Some(timeout) = command.timeout {
+                Arc::get_mut(&mut node).unwrap().set_timeout(timeout);
+            }
+            parent.add_successor(node).expect("new nodes aren't frozen");
+        }
+        for command in &self.commands {
+            if let Some(ref wrapped_root) = command.wrapped_root {
+                let wrapped = match find_path(&root, wrapped_root) {
+                    Some(node) => node,
+                    None => {
+                        return Err(BuildError::UnknownWrappedCommand {
+                            command: command.name.clone(),
+                            wraps: wrapped_root.clone(),
+                        })
+                    }
+                };
+                let (parent, name) = self.parent_node(&root, &command.name);
+                parent.add_successor(WrapperNode::with_options(name,
+                                                               command.priority,
+                                                               command.hidden,
+                                                               command.help_text.clone(),
+                                                               command.boxed_handler(),
+                                                               wrapped))
+                      .expect("new nodes aren't frozen");
+            }
+        }
+        for level in &self.negations {
+            let unknown = || {
+                BuildError::UnknownWrappedCommand {
+                    command: "no".to_string(),
+                    wraps: level.clone(),
+                }
+            };
+            let node = find_path(&root, level).ok_or_else(unknown)?;
+            let negation = NegationNode::new("no", node.clone());
+            node.add_successor(negation).expect("new nodes aren't frozen");
+        }
+        Ok(root.with_case_sensitivity(self.case_sensitivity).expect("new nodes aren't frozen"))
+    }
+
+    /// Construct the tree of nodes described by this `CommandTree`
+    /// with `finalize`, then freeze it with `Node::freeze`.
+    ///
+    /// A `CommandTree` can only be frozen once. To create several
+    /// frozen trees from the same commands, use a function which
+    /// populates a new `CommandTree` each time.
+    pub fn freeze(&mut self) -> Result<Arc<RootNode>, BuildError> {
+        if self.frozen {
+            return Err(BuildError::AlreadyFrozen);
+        }
+        let root = self.finalize()?;
+        root.freeze();
+        self.frozen = true;
+        Ok(root)
+    }
+
+    /// Find the node beneath which the command named `path` belongs,
+    /// creating commands for any leading words that are missing, and
+    /// return it along with the last word of `path`.
+    fn parent_node<'a>(&self, root: &Arc<RootNode>, path: &'a str) -> (Arc<dyn Node>, &'a str) {
+        let mut words = path.split_whitespace().collect::<Vec<_>>();
+        let name = words.pop().unwrap_or("");
+        let mut parent: Arc<dyn Node> = root.clone();
+        for word in words {
+            parent = match find_successor(&*parent, word) {
+                Some(node) => node,
+                None => {
+                    let node = CommandNode::new(word, PRIORITY_DEFAULT, false, None, None, vec![]);
+                    parent.add_successor(node.clone()).expect("new nodes aren't frozen");
+                    node
+                }
+            };
+        }
+        (parent, name)
+    }
+
+    fn build_parameter(&self, parameter: &Parameter) -> Arc<dyn ParameterNode> {
         match parameter.parameter_kind {
-            ParameterKind::Flag => Rc::new(self.build_flag_parameter(parameter)),
-            ParameterKind::Named => Rc::new(self.build_named_parameter(parameter)),
-            ParameterKind::Simple => Rc::new(self.build_simple_parameter(parameter)),
-        }
-    }
-
-    fn build_flag_parameter(self, parameter: Parameter) -> FlagParameterNode {
-        FlagParameterNode::new(&*parameter.name,
-                               parameter.help_text,
-                               parameter.hidden,
-                               parameter.priority,
-                               vec![],
-                               parameter.repeatable,
-                               None,
-                               parameter.required)
-    }
-
-    fn build_named_parameter(self, parameter: Parameter) -> NamedParameterNode {
-        NamedParameterNode::new(&*parameter.name,
-                                parameter.help_text,
-                                parameter.hidden,
-                                parameter.priority,
-                                vec![],
-                                parameter.repeatable,
-                                None,
-                                parameter.required)
-    }
-
-    fn build_simple_parameter(self, parameter: Parameter) -> SimpleParameterNode {
-        SimpleParameterNode::new(&*parameter.name,
-                                 parameter.help_text,
-                                 parameter.hidden,
-                                 parameter.priority,
-                                 vec![],
-                                 parameter.repeatable,
-                                 None,
-                                 parameter.required)
+            ParameterKind::Flag => Arc::new(self.build_flag_parameter(parameter)),
+            ParameterKind::Named => Arc::new(self.build_named_parameter(parameter)),
+            ParameterKind::Simple => Arc::new(self.build_simple_parameter(parameter)),
+        }
+    }
+
+    fn build_flag_parameter(&self, parameter: &Parameter) -> FlagParameterNode {
+        let mut node = FlagParameterNode::with_options(&parameter.name,
+                                                       parameter.help_text.clone(),
+                                                       parameter.hidden,
+                                                       parameter.priority,
+                                                       vec![],
+                                                       parameter.repeatable,
+                                                       None,
+                                                       parameter.required);
+        for name in &parameter.requirements {
+            node.add_requirement(name).expect("new nodes aren't frozen");
+        }
+        for name in &parameter.conflicts {
+            node.add_conflict(name);
+        }
+        node.set_max_repetitions(parameter.max_repetitions);
+        node.set_deduplicate(parameter.deduplicate);
+        if let Some(min_repetitions) = parameter.min_repetitions {
+            node.set_min_repetitions(min_repetitions);
+        }
+        node
+    }
+
+    fn build_named_parameter(&self, parameter: &Parameter) -> NamedParameterNode {
+        let mut node = NamedParameterNode::with_options(&parameter.name,
+                                                        parameter.help_text.clone(),
+                                                        parameter.hidden,
+                                                        parameter.priority,
+                                                        vec![],
+                                                        parameter.repeatable,
+                                                        None,
+                                                        parameter.required);
+        for name in &parameter.requirements {
+            node.add_requirement(name).expect("new nodes aren't frozen");
+        }
+        for name in &parameter.conflicts {
+            node.add_conflict(name);
+        }
+        node.set_max_repetitions(parameter.max_repetitions);
+        node.set_deduplicate(parameter.deduplicate);
+        if let Some(min_repetitions) = parameter.min_repetitions {
+            node.set_min_repetitions(min_repetitions);
+        }
+        node
+    }
+
+    fn build_simple_parameter(&self, parameter: &Parameter) -> SimpleParameterNode {
+        let mut node = SimpleParameterNode::new(&parameter.name,
+                                                parameter.help_text.clone(),
+                                                parameter.hidden,
+                                                parameter.priority,
+                                                vec![],
+                                                parameter.repeatable,
+                                                None,
+                                                parameter.required);
+        for name in &parameter.requirements {
+            node.add_requirement(name).expect("new nodes aren't frozen");
+        }
+        for name in &parameter.conflicts {
+            node.add_conflict(name);
+        }
+        node.set_max_repetitions(parameter.max_repetitions);
+        node.set_deduplicate(parameter.deduplicate);
+        if let Some(min_repetitions) = parameter.min_repetitions {
+            node.set_min_repetitions(min_repetitions);
+        }
+        node
+    }
+}
+
+/// Find the successor of `node` with the given `name`.
+fn find_successor(node: &dyn Node, name: &str) -> Option<Arc<dyn Node>> {
+    node.successors().iter().find(|n| &*n.name() == name).cloned()
+}
+
+/// Find the node reached by following the words of `path` from
+/// `root`. The empty path refers to `root` itself.
+fn find_path(root: &Arc<RootNode>, path: &str) -> Option<Arc<dyn Node>> {
+    let mut node: Arc<dyn Node> = root.clone();
+    for word in path.split_whitespace() {
+        node = find_successor(&*node, word)?;
+    }
+    Some(node)
+}
+
+/// Follow requirements depth first from the last parameter in `path`,
+/// looking for one that leads back to the first. On success, `path`
+/// holds the cycle.
+fn find_requirement_cycle<'a>(requirements: &HashMap<&'a str, &'a Vec<String>>,
+                              path: &mut Vec<&'a str>)
+                              -> bool {
+    let last = path[path.len() - 1];
+    for next in requirements[last].iter() {
+        if next == path[0] {
+            path.push(next);
+            return true;
+        }
+        if !path.contains(&next.as_str()) {
+            path.push(next);
+            if find_requirement_cycle(requirements, path) {
+                return true;
+            }
+            path.pop();
+        }
     }
+    false
 }
 
+/// A handler shared by the nodes built from a `Command`.
+type SharedHandler = Arc<dyn Fn(&HandlerContext) -> HandlerResult + Send + Sync>;
+
 /// Description of a command to be added to the `CommandTree`.
 #[derive(Clone)]
 pub struct Command {
@@ -102,9 +370,93 @@ pub struct Command {
     help_text: Option<String>,
     parameters: Vec<Parameter>,
     wrapped_root: Option<String>,
+    handler: Option<SharedHandler>,
+    state_handler: bool,
+    payload: Option<Payload>,
+    deprecated: Option<String>,
+    negatable: bool,
+    timeout: Option<Duration>,
 }
 
 impl Command {
+    fn validate_requirements(&self) -> Result<(), BuildError> {
+        let requirements = self.parameters
+                               .iter()
+                               .map(|p| (p.name.as_str(), &p.requirements))
+                               .collect::<HashMap<_, _>>();
+        for parameter in &self.parameters {
+            for required in &parameter.requirements {
+                if !requirements.contains_key(required.as_str()) {
+                    return Err(BuildError::UnknownRequirement {
+                        command: self.name.clone(),
+                        parameter: parameter.name.clone(),
+                        requires: required.clone(),
+                    });
+                }
+            }
+        }
+        for parameter in &self.parameters {
+            let mut path = vec![parameter.name.as_str()];
+            if find_requirement_cycle(&requirements, &mut path) {
+                return Err(BuildError::CircularRequirement {
+                    command: self.name.clone(),
+                    parameters: path.iter().map(|p| p.to_string()).collect(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_conflicts(&self) -> Result<(), BuildError> {
+        for parameter in &self.parameters {
+            for conflict in &parameter.conflicts {
+                if conflict == &parameter.name {
+                    return Err(BuildError::SelfConflict {
+                        command: self.name.clone(),
+                        parameter: parameter.name.clone(),
+                    });
+                }
+                if !self.parameters.iter().any(|p| &p.name == conflict) {
+                    return Err(BuildError::UnknownConflict {
+                        command: self.name.clone(),
+                        parameter: parameter.name.clone(),
+                        conflicts_with: conflict.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_timeout(&self) -> Result<(), BuildError> {
+        if self.timeout.is_none() {
+            return Ok(());
+        }
+        if self.wrapped_root.is_some() {
+            return Err(BuildError::TimedWrapper { command: self.name.clone() });
+        }
+        if self.state_handler {
+            return Err(BuildError::TimedStateHandler { command: self.name.clone() });
+        }
+        Ok(())
+    }
+
+    fn validate_positional(&self) -> Result<(), BuildError> {
+        let positional = self.parameters
+                             .iter()
+                             .filter(|p| matches!(p.parameter_kind, ParameterKind::Simple))
+                             .collect::<Vec<_>>();
+        if let Some((_, init)) = positional.split_last() {
+            if let Some(parameter) = init.iter().find(|p| p.repeatable) {
+                return Err(BuildError::RepeatableNotLast {
+                    command: self.name.clone(),
+                    parameter: parameter.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Construct a default (blank) command with the given `name`.
     pub fn new(name: &str) -> Self {
         Command {
@@ -114,6 +466,12 @@ impl Command {
             help_text: None,
             parameters: vec![],
             wrapped_root: None,
+            handler: None,
+            state_handler: false,
+            payload: None,
+            deprecated: None,
+            negatable: false,
+            timeout: None,
         }
     }
 
@@ -143,6 +501,71 @@ impl Command {
         self
     }
 
+    /// Supply the handler to be executed for the command. This may
+    /// be a closure, which is shared by every tree built from this
+    /// command.
+    pub fn handler<F>(&mut self, handler: F) -> &mut Self
+        where F: Fn(&HandlerContext) -> HandlerResult + Send + Sync + 'static
+    {
+        self.handler = Some(Arc::new(handler));
+        self.state_handler = false;
+        self
+    }
+
+    /// Supply a handler which is given the state of the application,
+    /// as passed to `Parser::execute_with`. Executing the command
+    /// fails when no state of type `S` was given.
+    ///
+    /// As a handler with a `timeout` is run on a thread of its own,
+    /// without the state, such a command can't be built.
+    pub fn state_handler<S: Any>(&mut self,
+                                 handler: fn(&mut S, &HandlerContext) -> HandlerResult)
+                                 -> &mut Self {
+        self.handler(move |context| {
+            let mut state = context.state::<S>().ok_or("the application state is missing")?;
+            handler(&mut state, context)
+        });
+        self.state_handler = true;
+        self
+    }
+
+    /// Attach application data to the command, which can be found
+    /// with `CommandNode::payload`. Every tree built from this
+    /// command shares the same payload.
+    pub fn payload<T: Any + Send + Sync>(&mut self, payload: T) -> &mut Self {
+        self.payload = Some(Arc::new(payload));
+        self
+    }
+
+    /// Allow the command to be negated by a `no` command, as added
+    /// with `CommandTree::negation`.
+    pub fn negatable(&mut self, negatable: bool) -> &mut Self {
+        self.negatable = negatable;
+        self
+    }
+
+    /// Mark the command as deprecated, with a `message` saying what
+    /// to use instead, as with `CommandNode::set_deprecated`.
+    pub fn deprecated(&mut self, message: &str) -> &mut Self {
+        self.deprecated = Some(message.to_string());
+        self
+    }
+
+    /// Limit how long the handler may run, as with
+    /// `CommandNode::set_timeout`. Commands which wrap other commands,
+    /// or have a `state_handler`, can't be given a timeout.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The handler to give to the node built for the command.
+    fn boxed_handler(&self) -> Option<Handler> {
+        self.handler.clone().map(|h| {
+            Box::new(move |context: &HandlerContext| h(context)) as Handler
+        })
+    }
+
     /// Create a `WrapperNode` instead of a `CommandNode`. The
     /// `wrapped_root` signifies the path to the command that should
     /// be wrapped by this command.
@@ -166,9 +589,14 @@ pub struct Parameter {
     priority: i32,
     name: String,
     repeatable: bool,
+    max_repetitions: Option<usize>,
+    min_repetitions: Option<usize>,
+    deduplicate: bool,
     aliases: Vec<String>,
     help_text: Option<String>,
     required: bool,
+    requirements: Vec<String>,
+    conflicts: Vec<String>,
     parameter_kind: ParameterKind,
 }
 
@@ -180,9 +608,14 @@ impl Parameter {
             priority: PRIORITY_PARAMETER,
             name: name.to_string(),
             repeatable: false,
+            max_repetitions: None,
+            min_repetitions: None,
+            deduplicate: false,
             aliases: vec![],
             help_text: None,
             required: false,
+            requirements: vec![],
+            conflicts: vec![],
             parameter_kind: ParameterKind::Simple,
         }
     }
@@ -209,6 +642,29 @@ impl Parameter {
         self
     }
 
+    /// Limit the number of times that a repeatable parameter may be
+    /// given. A limit of 0 disables the parameter.
+    pub fn max_repetitions(&mut self, max_repetitions: usize) -> &mut Self {
+        self.max_repetitions = Some(max_repetitions);
+        self
+    }
+
+    /// Require a repeatable parameter to be given at least this many
+    /// times. By default, this is 1 for required parameters and 0
+    /// for optional ones.
+    pub fn min_repetitions(&mut self, min_repetitions: usize) -> &mut Self {
+        self.min_repetitions = Some(min_repetitions);
+        self
+    }
+
+    /// Keep each value given for a repeatable parameter only once,
+    /// in the order in which they were first given. This suits
+    /// list-style settings where repeating a value changes nothing.
+    pub fn deduplicate(&mut self, deduplicate: bool) -> &mut Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
     /// Add an alias that this parameter can use.
     pub fn alias(&mut self, alias: &str) -> &mut Self {
         self.aliases.push(alias.to_string());
@@ -227,6 +683,23 @@ impl Parameter {
         self
     }
 
+    /// Declare that this parameter may only be given together with
+    /// the parameter named `name`. Requirements are transitive: if
+    /// `name` itself requires other parameters, those must also be
+    /// given.
+    pub fn requires(&mut self, name: &str) -> &mut Self {
+        self.requirements.push(name.to_string());
+        self
+    }
+
+    /// Declare that this parameter may not be given together with
+    /// the parameter named `name`. Once one of them has been given,
+    /// the other is no longer offered during completion.
+    pub fn conflicts_with(&mut self, name: &str) -> &mut Self {
+        self.conflicts.push(name.to_string());
+        self
+    }
+
     /// Set which type of `ParameterNode` is supposed to be created
     /// to represent this parameter.
     pub fn kind(&mut self, kind: ParameterKind) -> &mut Self {
@@ -241,3 +714,500 @@ impl Parameter {
         self.clone()
     }
 }
+
+/// Errors that validating a `CommandTree` can raise.
+#[derive(Clone,Debug)]
+pub enum BuildError {
+    /// A parameter requires a parameter that the command doesn't have.
+    UnknownRequirement {
+        /// The name of the command.
+        command: String,
+        /// The parameter declaring the requirement.
+        parameter: String,
+        /// The name of the missing parameter.
+        requires: String,
+    },
+    /// Requirements between parameters form a cycle.
+    CircularRequirement {
+        /// The name of the command.
+        command: String,
+        /// The parameters forming the cycle, starting and ending
+        /// with the same parameter.
+        parameters: Vec<String>,
+    },
+    /// A parameter conflicts with a parameter that the command doesn't have.
+    UnknownConflict {
+        /// The name of the command.
+        command: String,
+        /// The parameter declaring the conflict.
+        parameter: String,
+        /// The name of the missing parameter.
+        conflicts_with: String,
+    },
+    /// A parameter is declared to conflict with itself.
+    SelfConflict {
+        /// The name of the command.
+        command: String,
+        /// The parameter declaring the conflict.
+        parameter: String,
+    },
+    /// More than one command has the same name.
+    DuplicateCommand {
+        /// The name of the command.
+        command: String,
+    },
+    /// A wrapping command wraps a command which doesn't exist.
+    UnknownWrappedCommand {
+        /// The name of the wrapping command.
+        command: String,
+        /// The path of the command to be wrapped.
+        wraps: String,
+    },
+    /// A repeatable simple parameter is followed by another
+    /// simple parameter, which could then never be given.
+    RepeatableNotLast {
+        /// The name of the command.
+        command: String,
+        /// The repeatable parameter.
+        parameter: String,
+    },
+    /// A wrapping command was given a timeout, which only the
+    /// handlers of `CommandNode`s support.
+    TimedWrapper {
+        /// The name of the wrapping command.
+        command: String,
+    },
+    /// A command with a `state_handler` was given a timeout, though
+    /// the state can't be given to a handler on another thread.
+    TimedStateHandler {
+        /// The name of the command.
+        command: String,
+    },
+    /// `CommandTree::freeze` was called more than once.
+    AlreadyFrozen,
+}
+
+impl Error for BuildError {}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BuildError::UnknownRequirement { ref command, ref parameter, ref requires } => {
+                write!(f,
+                       "command '{}': parameter '{}' requires unknown parameter '{}'",
+                       command,
+                       parameter,
+                       requires)
+            }
+            BuildError::CircularRequirement { ref command, ref parameters } => {
+                write!(f,
+                       "command '{}': circular parameter requirements: {}",
+                       command,
+                       parameters.join(" -> "))
+            }
+            BuildError::UnknownConflict { ref command, ref parameter, ref conflicts_with } => {
+                write!(f,
+                       "command '{}': parameter '{}' conflicts with unknown parameter '{}'",
+                       command,
+                       parameter,
+                       conflicts_with)
+            }
+            BuildError::SelfConflict { ref command, ref parameter } => {
+                write!(f,
+                       "command '{}': parameter '{}' conflicts with itself",
+                       command,
+                       parameter)
+            }
+            BuildError::DuplicateCommand { ref command } => {
+                write!(f, "command '{}' is defined more than once", command)
+            }
+            BuildError::UnknownWrappedCommand { ref command, ref wraps } => {
+                write!(f, "command '{}' wraps unknown command '{}'", command, wraps)
+            }
+            BuildError::RepeatableNotLast { ref command, ref parameter } => {
+                write!(f,
+                       "command '{}': repeatable parameter '{}' must be the last simple parameter",
+                       command,
+                       parameter)
+            }
+            BuildError::TimedWrapper { ref command } => {
+                write!(f, "wrapping command '{}' can't have a timeout", command)
+            }
+            BuildError::TimedStateHandler { ref command } => {
+                write!(f,
+                       "command '{}' can't have a timeout, as its handler needs the state",
+                       command)
+            }
+            BuildError::AlreadyFrozen => "this command tree has already been frozen".fmt(f),
+        }
+    }
+}
+
+/// Problems found by `CommandTree::warnings`, which don't prevent
+/// the tree from being built.
+#[derive(Clone,Debug,PartialEq)]
+pub enum BuildWarning {
+    /// A flag is required, so that it can't be left out and its
+    /// value is always `true`.
+    RequiredFlag {
+        /// The name of the command.
+        command: String,
+        /// The name of the flag.
+        parameter: String,
+    },
+}
+
+impl fmt::Display for BuildWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BuildWarning::RequiredFlag { ref command, ref parameter } => {
+                write!(f, "command '{}': flag '{}' is required", command, parameter)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::{Parser, VerifyError};
+
+    #[test]
+    fn unknown_requirement() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("connect")
+                         .parameter(Parameter::new("key").requires("cert").finalize())
+                         .finalize());
+        match tree.validate() {
+            Err(BuildError::UnknownRequirement { parameter, requires, .. }) => {
+                assert_eq!(parameter, "key");
+                assert_eq!(requires, "cert");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn chained_requirements() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("connect")
+                         .parameter(Parameter::new("key").requires("cert").finalize())
+                         .parameter(Parameter::new("cert").requires("ca").finalize())
+                         .parameter(Parameter::new("ca").finalize())
+                         .finalize());
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn circular_requirements() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("connect")
+                         .parameter(Parameter::new("a").requires("b").finalize())
+                         .parameter(Parameter::new("b").requires("c").finalize())
+                         .parameter(Parameter::new("c").requires("a").finalize())
+                         .finalize());
+        match tree.validate() {
+            Err(e @ BuildError::CircularRequirement { .. }) => {
+                assert_eq!(e.to_string(),
+                           "command 'connect': circular parameter requirements: a -> b -> c -> a");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn self_conflict() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("output")
+                         .parameter(Parameter::new("json").conflicts_with("json").finalize())
+                         .finalize());
+        match tree.validate() {
+            Err(BuildError::SelfConflict { command, parameter }) => {
+                assert_eq!(command, "output");
+                assert_eq!(parameter, "json");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn unknown_conflict() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("output")
+                         .parameter(Parameter::new("json").conflicts_with("quiet").finalize())
+                         .finalize());
+        match tree.validate() {
+            Err(BuildError::UnknownConflict { .. }) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn repeatable_positional_must_be_last() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("copy")
+                         .parameter(Parameter::new("src").repeatable(true).finalize())
+                         .parameter(Parameter::new("dst").finalize())
+                         .finalize());
+        match tree.validate() {
+            Err(BuildError::RepeatableNotLast { parameter, .. }) => assert_eq!(parameter, "src"),
+            _ => panic!(),
+        }
+
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("copy")
+                         .parameter(Parameter::new("dst").finalize())
+                         .parameter(Parameter::new("force").kind(ParameterKind::Flag).finalize())
+                         .parameter(Parameter::new("src").repeatable(true).finalize())
+                         .finalize());
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn timeouts_need_plain_handlers() {
+        fn status(_: &mut u32, _: &HandlerContext) -> HandlerResult {
+            Ok(None)
+        }
+
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("status")
+                         .state_handler(status)
+                         .timeout(Duration::from_millis(100))
+                         .finalize());
+        match tree.validate() {
+            Err(error @ BuildError::TimedStateHandler { .. }) => {
+                assert_eq!(error.to_string(),
+                           "command 'status' can't have a timeout, as its handler needs the state")
+            }
+            _ => panic!(),
+        }
+
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("help")
+                         .wraps("".to_string())
+                         .timeout(Duration::from_millis(100))
+                         .finalize());
+        match tree.validate() {
+            Err(BuildError::TimedWrapper { command }) => assert_eq!(command, "help"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn nested_commands() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show interface").finalize());
+        tree.command(Command::new("show").help("Show things.").finalize());
+        tree.command(Command::new("show route").finalize());
+        let root = tree.finalize().unwrap();
+
+        let successors = root.successors();
+        assert_eq!(successors.len(), 1);
+        assert_eq!(&*successors[0].name(), "show");
+        assert_eq!(successors[0].help_text().as_deref(), Some("Show things."));
+        let commands = successors[0].successors();
+        let names = commands.iter().map(|n| n.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["interface", "route"]);
+    }
+
+    #[test]
+    fn duplicate_command() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").finalize());
+        tree.command(Command::new("show").finalize());
+        match tree.finalize() {
+            Err(BuildError::DuplicateCommand { command }) => assert_eq!(command, "show"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn wrapped_command() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show interface").finalize());
+        tree.command(Command::new("show route").finalize());
+        tree.command(Command::new("explain").wraps("show".to_string()).finalize());
+        let root = tree.finalize().unwrap();
+
+        let explain = root.successors()[1].clone();
+        assert_eq!(&*explain.name(), "explain");
+        let names = explain.successors().iter().map(|n| n.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["interface", "route"]);
+    }
+
+    #[test]
+    fn unknown_wrapped_command() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("explain").wraps("show".to_string()).finalize());
+        match tree.finalize() {
+            Err(BuildError::UnknownWrappedCommand { wraps, .. }) => assert_eq!(wraps, "show"),
+            _ => panic!(),
+        }
+    }
+
+    fn template() -> CommandTree {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show interface")
+                         .parameter(Parameter::new("name").finalize())
+                         .finalize());
+        tree.command(Command::new("explain").wraps("show".to_string()).finalize());
+        tree
+    }
+
+    #[test]
+    fn freeze_tree() {
+        let mut tree = template();
+        let root = tree.freeze().unwrap();
+        assert!(root.frozen());
+        let show = root.successors()[0].clone();
+        let interface = show.successors()[0].clone();
+        assert!(show.frozen());
+        assert!(interface.frozen());
+        assert!(interface.as_command().unwrap().parameters()[0].frozen());
+        assert!(root.successors()[1].frozen());
+
+        match tree.freeze() {
+            Err(BuildError::AlreadyFrozen) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn frozen_tree_cannot_be_modified() {
+        let root = template().freeze().unwrap();
+        let show = root.successors()[0].clone();
+        let route = CommandNode::new("route", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let error = show.add_successor(route).unwrap_err();
+        assert_eq!(error, FrozenError { node: "show".to_string() });
+        assert_eq!(error.to_string(), "node 'show' is frozen and can't be modified");
+        assert_eq!(show.successors().len(), 1);
+        assert!(show.set_metadata("audit", "read").is_err());
+        assert!(root.clone().with_case_sensitivity(CaseSensitivity::Insensitive).is_err());
+    }
+
+    #[test]
+    fn trees_from_template() {
+        let first = template().freeze().unwrap();
+        let second = template().freeze().unwrap();
+        assert!(*first.successors()[0] != *second.successors()[0]);
+
+        // Unfrozen trees may still be extended.
+        let third = template().finalize().unwrap();
+        assert!(!third.frozen());
+        let reload = CommandNode::new("reload", PRIORITY_DEFAULT, false, None, None, vec![]);
+        third.add_successor(reload).unwrap();
+        assert_eq!(third.successors().len(), 3);
+        assert_eq!(first.successors().len(), 2);
+    }
+
+    struct Descriptor {
+        telemetry_name: String,
+    }
+
+    #[test]
+    fn command_payload() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show route")
+                         .payload(Descriptor { telemetry_name: "show.route".to_string() })
+                         .finalize());
+        let root = tree.finalize().unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("show route").is_ok());
+        let route = parser.nodes.last().unwrap().as_command().unwrap();
+        assert_eq!(route.payload::<Descriptor>().unwrap().telemetry_name, "show.route");
+        // Commands created for the leading words have no payload.
+        assert!(parser.nodes[0].as_command().unwrap().payload::<Descriptor>().is_none());
+    }
+
+    #[test]
+    fn deprecated_command() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("sh").deprecated("use 'show' instead").finalize());
+        tree.command(Command::new("show").finalize());
+        let root = tree.finalize().unwrap();
+
+        let deprecated = root.successors()
+                             .iter()
+                             .map(|n| n.as_command().unwrap().deprecated().map(|m| m.to_string()))
+                             .collect::<Vec<_>>();
+        assert_eq!(deprecated, vec![Some("use 'show' instead".to_string()), None]);
+    }
+
+    #[test]
+    fn case_insensitive_tree() {
+        let mut tree = CommandTree::new();
+        tree.case_sensitivity(CaseSensitivity::Insensitive);
+        tree.command(Command::new("show route")
+                         .parameter(Parameter::new("verbose")
+                                        .kind(ParameterKind::Flag)
+                                        .finalize())
+                         .finalize());
+        let root = tree.freeze().unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("Show ROUTE Verbose").is_ok());
+        assert_eq!(parser.nodes.len(), 3);
+
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("SHOW").is_err());
+    }
+
+    fn flags_command() -> Command {
+        Command::new("show")
+            .parameter(Parameter::new("verbose").kind(ParameterKind::Flag).finalize())
+            .parameter(Parameter::new("brief").kind(ParameterKind::Flag).finalize())
+            .handler(|_| Ok(None))
+            .finalize()
+    }
+
+    #[test]
+    fn flags_in_any_order() {
+        let mut tree = CommandTree::new();
+        tree.command(flags_command());
+        assert!(tree.warnings().is_empty());
+        let root = tree.finalize().unwrap();
+        for line in &["show verbose brief", "show brief verbose"] {
+            let mut parser = Parser::new(root.clone());
+            assert!(parser.parse(line).is_ok());
+            assert!(parser.verify().is_ok());
+            let values = parser.values();
+            assert_eq!(values.get_as::<bool>("verbose"), Some(true));
+            assert_eq!(values.get_as::<bool>("brief"), Some(true));
+            assert!(parser.complete(None).is_empty());
+        }
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("show brief").is_ok());
+        assert_eq!(parser.values().get_as::<bool>("verbose"), None);
+        assert!(parser.parse("brief").is_err());
+    }
+
+    #[test]
+    fn required_flag_warning() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("reload")
+                         .parameter(Parameter::new("force")
+                                        .kind(ParameterKind::Flag)
+                                        .required(true)
+                                        .finalize())
+                         .handler(|_| Ok(None))
+                         .finalize());
+        let warnings = tree.warnings();
+        assert_eq!(warnings,
+                   vec![BuildWarning::RequiredFlag {
+                            command: "reload".to_string(),
+                            parameter: "force".to_string(),
+                        }]);
+        assert_eq!(warnings[0].to_string(), "command 'reload': flag 'force' is required");
+
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("reload").is_ok());
+        match &parser.verify().unwrap_err()[..] {
+            [VerifyError::MissingParameter { name, .. }] => assert_eq!(name, "force"),
+            _ => panic!(),
+        }
+    }
+}
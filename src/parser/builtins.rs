@@ -0,0 +1,189 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Built-in Commands
+//!
+//! Commands which most command line interfaces will want to
+//! provide, ready to be installed into a command tree. Installing
+//! them fails if that tree has been frozen.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use parser::help::HelpFormatter;
+use parser::modes::ModeChange;
+use parser::nodes::*;
+
+/// Install a `help` command as a successor of `root`.
+///
+/// The command is a `WrapperNode` around `root`, so it completes
+/// and matches the same commands as `root` does. When executed,
+/// its output is the help for the command that follows it, or for
+/// `root` when it is given alone.
+///
+/// As the command wraps the root of its own tree, the tree
+/// will not be freed.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::builtins::install_help_command;
+/// use commands::parser::nodes::*;
+/// use commands::parser::Parser;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("show",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// let tree: Arc<dyn Node> = root.clone();
+/// install_help_command(&tree).unwrap();
+///
+/// let mut parser = Parser::new(root);
+/// parser.parse("help show").unwrap();
+/// assert!(parser.execute().unwrap().unwrap().contains("show"));
+/// ```
+pub fn install_help_command(root: &Arc<dyn Node>) -> Result<(), FrozenError> {
+    root.add_successor(WrapperNode::with_options("help",
+                                                 PRIORITY_DEFAULT,
+                                                 false,
+                                                 Some("Show help for a command.".to_string()),
+                                                 Some(Box::new(help_handler)),
+                                                 root.clone()))
+}
+
+fn help_handler(context: &HandlerContext) -> HandlerResult {
+    Ok(Some(HelpFormatter::new().format(context.node())))
+}
+
+/// Install an `exit` command, and `quit` as a hidden alias for
+/// it, as successors of `root`.
+///
+/// Executing either command sets `repl_handle`, which the loop
+/// reading command lines should check to know when to stop.
+/// The help text defaults to `"Exit."`.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use commands::parser::builtins::install_exit_command;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// let tree: Arc<dyn Node> = root.clone();
+/// let done = Arc::new(AtomicBool::new(false));
+/// install_exit_command(&tree, done.clone(), None).unwrap();
+/// assert!(!done.load(Ordering::SeqCst));
+/// ```
+pub fn install_exit_command(root: &Arc<dyn Node>,
+                            repl_handle: Arc<AtomicBool>,
+                            help_text: Option<String>)
+                            -> Result<(), FrozenError> {
+    let help_text = help_text.or_else(|| Some("Exit.".to_string()));
+    for &(name, hidden) in &[("exit", false), ("quit", true)] {
+        let handle = repl_handle.clone();
+        root.add_successor(CommandNode::new(name,
+                                            PRIORITY_DEFAULT,
+                                            hidden,
+                                            help_text.clone(),
+                                            Some(Box::new(move |_: &HandlerContext| {
+                                                handle.store(true, Ordering::SeqCst);
+                                                Ok(None)
+                                            })),
+                                            vec![]))?;
+    }
+    Ok(())
+}
+
+/// Install an `exit` command, which leaves the current mode, and
+/// an `end` command, which leaves every mode, as successors of
+/// `root`, the root of a mode. They leave it with a `ModeChange`,
+/// so that `Parser::execute_lines` follows them.
+///
+/// These differ from `install_exit_command`, which ends the loop
+/// reading command lines, and should be installed in the trees
+/// of modes rather than in the tree that the `Parser` starts with.
+pub fn install_mode_commands(root: &Arc<dyn Node>) -> Result<(), FrozenError> {
+    let commands = [("exit", "Leave the current mode.", ModeChange::Exit),
+                    ("end", "Leave every mode.", ModeChange::End)];
+    for (name, help_text, mode_change) in commands {
+        let mut command = CommandNode::new(name,
+                                           PRIORITY_DEFAULT,
+                                           false,
+                                           Some(help_text.to_string()),
+                                           Some(Box::new(|_: &HandlerContext| Ok(None))),
+                                           vec![]);
+        Arc::get_mut(&mut command).unwrap().set_mode_change(mode_change);
+        root.add_successor(command)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use super::*;
+    use parser::Parser;
+
+    #[test]
+    fn help_command() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(CommandNode::new("route",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+        root.add_successor(show).unwrap();
+        let tree: Arc<dyn Node> = root.clone();
+        install_help_command(&tree).unwrap();
+
+        let names = root.successors().iter().map(|n| n.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["show", "help"]);
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("help show").is_ok());
+        assert!(parser.verify().is_ok());
+        assert!(parser.execute().is_ok());
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("help").is_ok());
+        assert!(parser.verify().is_ok());
+        assert!(parser.execute().is_ok());
+    }
+
+    fn exit_tree(help_text: Option<String>) -> (Arc<RootNode>, Arc<AtomicBool>) {
+        let root = RootNode::new();
+        let tree: Arc<dyn Node> = root.clone();
+        let done = Arc::new(AtomicBool::new(false));
+        install_exit_command(&tree, done.clone(), help_text).unwrap();
+        (root, done)
+    }
+
+    #[test]
+    fn exit_command() {
+        for line in &["exit", "quit"] {
+            let (root, done) = exit_tree(None);
+            let mut parser = Parser::new(root);
+            assert!(parser.parse(line).is_ok());
+            assert!(parser.verify().is_ok());
+            assert!(!done.load(Ordering::SeqCst));
+            assert!(parser.execute().is_ok());
+            assert!(done.load(Ordering::SeqCst));
+        }
+    }
+
+    #[test]
+    fn quit_is_hidden() {
+        let (root, _) = exit_tree(Some("Leave.".to_string()));
+        let parser = Parser::new(root);
+        let completions = parser.complete(None);
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].help_symbol, "exit");
+        assert_eq!(completions[0].help_text, Some("Leave.".to_string()));
+    }
+}
@@ -6,16 +6,72 @@
 
 //! # Completion
 //!
+//! The results of completing a node, as returned by `Node::complete`
+//! and `Parser::complete`.
 
-use parser::nodes::*;
+use std::ops::Range;
+use parser::nodes::{CaseSensitivity, Node};
 use tokenizer::Token;
 use util::longest_common_prefix;
 
+/// What a `Completion` is for, so that a menu can tell commands
+/// from the parameters which may follow them.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum CompletionKind {
+    /// A command, or a wrapper of commands like `help`.
+    Command,
+    /// Any other node which is given by its name, like a
+    /// `KeywordNode`.
+    #[default]
+    Keyword,
+    /// A flag, given by its name alone.
+    Flag,
+    /// The name of a named parameter, which is followed by its value.
+    NamedParameter,
+    /// The value of a parameter.
+    Value,
+}
+
+impl CompletionKind {
+    /// The kind of the completion of `node`.
+    pub fn of(node: &dyn Node) -> Self {
+        if node.as_command().is_some() || node.as_wrapper().is_some() {
+            CompletionKind::Command
+        } else if node.as_parameter_name().is_some() {
+            CompletionKind::NamedParameter
+        } else {
+            match node.as_parameter() {
+                Some(parameter) if parameter.takes_value() => CompletionKind::Value,
+                Some(_) => CompletionKind::Flag,
+                None => CompletionKind::Keyword,
+            }
+        }
+    }
+}
+
+/// How the token being completed was matched by a `Completion`, as
+/// `Parser::set_case_insensitive_completion` and
+/// `Parser::set_fuzzy_completion` allow looser matches. These are
+/// ordered from the strictest to the loosest.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq,PartialOrd,Ord)]
+pub enum CompletionMatching {
+    /// The node matched the token, as when parsing it.
+    #[default]
+    Prefix,
+    /// The token is a prefix of the name of the node when case is
+    /// ignored, as `SH` is of `show`.
+    CaseInsensitive,
+    /// The characters of the token are found in order within the
+    /// name of the node, as those of `ifc` are in `interface-config`.
+    Fuzzy,
+}
+
 /// Represents a single option returned by `complete`.
 ///
 /// An option may be `complete`, which means that it represents
 /// a syntactically complete parameter value which can be used
 /// as-is, whereas incomplete options are not valid values.
+#[derive(Clone,Debug)]
 pub struct CompletionOption {
     /// String for this option.
     pub option_string: String,
@@ -27,8 +83,8 @@ impl CompletionOption {
     /// CompletionOption constructor
     pub fn new(option_string: String, complete: bool) -> CompletionOption {
         CompletionOption {
-            option_string: option_string,
-            complete: complete,
+            option_string,
+            complete,
         }
     }
 }
@@ -42,6 +98,11 @@ impl CompletionOption {
 ///
 /// The lifetime parameter `'t` refers to the lifetime of the
 /// body of text which generated the `Token`.
+///
+/// For a menu, the `options` are what may be inserted, while the
+/// `help_symbol`, like `<count>`, and the `help_text` describe the
+/// node.
+#[derive(Clone,Debug)]
 pub struct Completion<'t> {
     /// Value placeholder for help.
     pub help_symbol: String,
@@ -54,6 +115,16 @@ pub struct Completion<'t> {
     pub exhaustive: bool,
     /// The actual completion options.
     pub options: Vec<CompletionOption>,
+    /// What the node is. This is set by `Parser::complete`, and is
+    /// `CompletionKind::Keyword` for a `Completion` from `new`.
+    pub kind: CompletionKind,
+    /// Could the command be executed once the node has been given,
+    /// without anything more? This is set by `Parser::complete`.
+    pub terminal: bool,
+    /// How the token was matched. This is set by `Parser::complete`,
+    /// and is `CompletionMatching::Prefix` for a `Completion` from
+    /// `new`.
+    pub matching: CompletionMatching,
 }
 
 impl<'t> Completion<'t> {
@@ -91,9 +162,10 @@ impl<'t> Completion<'t> {
             }
         }
         // Add longest common prefix as an incomplete options, but
-        // filter it against the existing options and the token.
+        // filter it against the existing options and the token. There
+        // is nothing to add when the options have no common prefix.
         let lcp = longest_common_prefix(all_options).to_string();
-        if !complete_options.contains(&lcp) && !other_options.contains(&lcp) {
+        if !lcp.is_empty() && !complete_options.contains(&lcp) && !other_options.contains(&lcp) {
             match token {
                 Some(t) => {
                     if lcp != t.text {
@@ -109,68 +181,153 @@ impl<'t> Completion<'t> {
                                           .collect::<Vec<_>>();
         options.extend(other_options.into_iter().map(|o| CompletionOption::new(o, false)));
         Completion {
-            help_symbol: help_symbol,
-            help_text: help_text,
-            token: token,
-            exhaustive: exhaustive,
-            options: options,
+            help_symbol,
+            help_text,
+            token,
+            exhaustive,
+            options,
+            kind: CompletionKind::default(),
+            terminal: false,
+            matching: CompletionMatching::default(),
         }
     }
 }
 
-/// Trait for nodes that support completion.
-pub trait Complete<'t> {
-    /// Given a node and an optional token, provide the completion options.
-    fn complete(&self, token: Option<Token<'t>>) -> Completion<'t>;
+/// Whether the completions found by `Parser::complete_at` finish
+/// the word at the cursor or suggest the word after it.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum CompletionMode {
+    /// The cursor is within or at the end of a word, as in
+    /// `show int`, and the completions are those of the word.
+    CurrentWord,
+    /// The cursor is after whitespace, as in `show interfaces `,
+    /// and the completions are what can follow.
+    NextWord,
 }
 
-impl<'t> Complete<'t> for Node {
-    /// By default, completion should complete for the name of the given
-    /// node.
-    ///
-    /// This is the expected behavior for `CommandNode` as well as
-    /// `ParameterNameNode`.
-    fn complete(&self, token: Option<Token<'t>>) -> Completion<'t> {
-        Completion::new(self.help_symbol().clone(),
-                        self.help_text().clone(),
-                        token,
-                        true,
-                        vec![self.name()],
-                        vec![])
-    }
+/// The completions for the token at a cursor within a line, as
+/// found by `Parser::complete_at`.
+#[derive(Clone,Debug)]
+pub struct CompletionResult<'t> {
+    /// The completions, as from `Parser::complete`.
+    pub completions: Vec<Completion<'t>>,
+    /// Whether the word at the cursor was completed.
+    pub mode: CompletionMode,
+    /// The loosest way in which any of the `completions` matched
+    /// the word at the cursor.
+    pub matching: CompletionMatching,
+    /// The bytes of the line which a chosen option should replace:
+    /// the whole of the token at the cursor, including any of it
+    /// after the cursor. This is empty, at the cursor, when the
+    /// cursor isn't within or at the end of a token.
+    pub replace: Range<usize>,
 }
 
-impl<'t> Complete<'t> for RootNode {
-    /// A `RootNode` can not be completed.
-    fn complete(&self, _token: Option<Token<'t>>) -> Completion<'t> {
-        panic!("BUG: Can not complete a root node.");
-    }
+/// What to insert for the token being completed, as found by
+/// `common_prefix`.
+#[derive(Clone,Debug,PartialEq)]
+pub struct CommonPrefix {
+    /// The text to add after what has been typed.
+    pub text: String,
+    /// Is there only one option, so that a space may be added
+    /// after the text, as it is complete?
+    pub unique: bool,
 }
 
-impl<'t> Complete<'t> for ParameterNode {
-    /// By default a `ParameterNode` completes only to itself.
-    ///
-    /// Implementations of `ParameterNode` may wish to override this
-    /// so that they can provide custom completion for their valid
-    /// values.
-    fn complete(&self, token: Option<Token<'t>>) -> Completion<'t> {
-        Completion::new(self.help_symbol().clone(),
-                        self.help_text().clone(),
-                        token,
-                        true,
-                        vec![],
-                        vec![])
+/// Find what can be inserted after `typed`, the text of the token
+/// being completed, for `completions`, such as those from
+/// `Parser::complete`, as when tab is pressed at a prompt.
+///
+/// This is the longest prefix which the complete options starting
+/// with `typed` have in common, beyond `typed` itself. With
+/// `CaseSensitivity::Insensitive`, options which only differ in
+/// case are the same, and the prefix has the case of the first
+/// option. `None` is returned when there is nothing to insert.
+///
+/// When there is a single option, and no completion allows values
+/// other than its options, the prefix is `unique`, even when it
+/// is empty.
+pub fn common_prefix(completions: &[Completion],
+                     typed: &str,
+                     case_sensitivity: CaseSensitivity)
+                     -> Option<CommonPrefix> {
+    let mut candidates: Vec<&str> = vec![];
+    for option in completions.iter().flat_map(|c| &c.options).filter(|o| o.complete) {
+        let text = option.option_string.as_str();
+        if case_sensitivity.starts_with(text, typed) &&
+           !candidates.iter().any(|c| case_sensitivity.equals(c, text)) {
+            candidates.push(text);
+        }
+    }
+    let first = *candidates.first()?;
+    let same = |a: char, b: char| case_sensitivity.equals(&a.to_string(), &b.to_string());
+    let len = candidates[1..].iter().fold(first.chars().count(), |len, c| {
+        len.min(first.chars().zip(c.chars()).take_while(|&(a, b)| same(a, b)).count())
+    });
+    let text = first.chars().take(len).skip(typed.chars().count()).collect::<String>();
+    let unique = candidates.len() == 1 && completions.iter().all(|c| c.exhaustive);
+    if text.is_empty() && !unique {
+        return None;
     }
+    Some(CommonPrefix { text, unique })
 }
 
-impl<'t> Complete<'t> for FlagParameterNode {
-    /// Flag parameters complete to the name of their node.
-    fn complete(&self, token: Option<Token<'t>>) -> Completion<'t> {
-        Completion::new(self.help_symbol().clone(),
-                        self.help_text().clone(),
-                        token,
-                        true,
-                        vec![self.name()],
-                        vec![])
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn completions(names: &[&str]) -> Vec<Completion<'static>> {
+        names.iter()
+             .map(|n| Completion::new(n.to_string(), None, None, true, vec![n], vec![]))
+             .collect()
+    }
+
+    fn prefix(text: &str, unique: bool) -> Option<CommonPrefix> {
+        Some(CommonPrefix {
+            text: text.to_string(),
+            unique,
+        })
+    }
+
+    #[test]
+    fn no_options() {
+        let sensitive = CaseSensitivity::Sensitive;
+        assert_eq!(common_prefix(&[], "", sensitive), None);
+        assert_eq!(common_prefix(&completions(&["show"]), "x", sensitive), None);
+    }
+
+    #[test]
+    fn one_option() {
+        let completions = completions(&["show"]);
+        assert_eq!(common_prefix(&completions, "s", CaseSensitivity::Sensitive),
+                   prefix("how", true));
+        assert_eq!(common_prefix(&completions, "show", CaseSensitivity::Sensitive),
+                   prefix("", true));
+
+        // A value other than the option may be given.
+        let mut completions = completions;
+        completions[0].exhaustive = false;
+        assert_eq!(common_prefix(&completions, "s", CaseSensitivity::Sensitive),
+                   prefix("how", false));
+    }
+
+    #[test]
+    fn many_options() {
+        let completions = completions(&["show", "shutdown", "reload"]);
+        assert_eq!(common_prefix(&completions, "", CaseSensitivity::Sensitive), None);
+        assert_eq!(common_prefix(&completions, "s", CaseSensitivity::Sensitive),
+                   prefix("h", false));
+        assert_eq!(common_prefix(&completions, "sh", CaseSensitivity::Sensitive), None);
+    }
+
+    #[test]
+    fn options_differing_in_case() {
+        let completions = completions(&["Show", "SHOW", "shutdown"]);
+        assert_eq!(common_prefix(&completions, "s", CaseSensitivity::Sensitive),
+                   prefix("hutdown", true));
+        assert_eq!(common_prefix(&completions, "s", CaseSensitivity::Insensitive),
+                   prefix("h", false));
+        assert_eq!(common_prefix(&completions, "SHO", CaseSensitivity::Insensitive),
+                   prefix("w", true));
     }
 }
@@ -0,0 +1,154 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Constraints
+//!
+//! Constraints across several parameters of a command: at most
+//! one of a `MutuallyExclusiveGroup` may be given, at least one
+//! of a `RequiredGroup` must be, and a parameter may depend on
+//! another with `add_dependency`.
+//!
+//! Constraints are registered with the `CommandNode` whose
+//! parameters they constrain and are enforced by the `Parser`.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use commands::parser::constraints::*;
+//! use commands::parser::nodes::*;
+//!
+//! let verbose: Arc<dyn ParameterNode> =
+//!     Arc::new(FlagParameterNode::with_options("verbose",
+//!                                              None,
+//!                                              false,
+//!                                              PRIORITY_PARAMETER,
+//!                                              vec![],
+//!                                              false,
+//!                                              None,
+//!                                              false));
+//! let log = CommandNode::new("log", PRIORITY_DEFAULT, false, None, None, vec![verbose.clone()]);
+//! let verbosity = MutuallyExclusiveGroup::new("verbosity");
+//! add_to_group(&verbosity, verbose);
+//! log.add_exclusive_group(verbosity).unwrap();
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use parser::nodes::{FrozenError, ParameterNode};
+
+/// A group of parameters of which at most one may be given.
+///
+/// Giving a parameter more than once, when it is repeatable,
+/// still counts as giving only one member of the group.
+pub struct MutuallyExclusiveGroup {
+    name: String,
+    members: RwLock<Vec<Arc<dyn ParameterNode>>>,
+}
+
+impl MutuallyExclusiveGroup {
+    /// Construct a new, empty, `MutuallyExclusiveGroup`.
+    pub fn new(name: &str) -> Arc<Self> {
+        Arc::new(MutuallyExclusiveGroup {
+            name: name.to_string(),
+            members: RwLock::new(vec![]),
+        })
+    }
+
+    /// The name of this group, used when reporting errors.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The parameters in this group.
+    pub fn members(&self) -> Vec<Arc<dyn ParameterNode>> {
+        self.members.read().unwrap().clone()
+    }
+
+    /// Is the parameter named `name` a member of this group?
+    pub fn contains(&self, name: &str) -> bool {
+        self.members.read().unwrap().iter().any(|m| &*m.name() == name)
+    }
+}
+
+impl fmt::Debug for MutuallyExclusiveGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let members = self.members
+                          .read()
+                          .unwrap()
+                          .iter()
+                          .map(|m| m.name())
+                          .collect::<Vec<_>>();
+        f.debug_struct("MutuallyExclusiveGroup")
+         .field("name", &self.name)
+         .field("members", &members)
+         .finish()
+    }
+}
+
+/// Add the parameter `node` to `group`.
+pub fn add_to_group(group: &MutuallyExclusiveGroup, node: Arc<dyn ParameterNode>) {
+    group.members.write().unwrap().push(node);
+}
+
+/// A group of parameters of which at least one must be given.
+pub struct RequiredGroup {
+    name: String,
+    members: RwLock<Vec<Arc<dyn ParameterNode>>>,
+}
+
+impl RequiredGroup {
+    /// Construct a new, empty, `RequiredGroup`.
+    pub fn new(name: &str) -> Arc<Self> {
+        Arc::new(RequiredGroup {
+            name: name.to_string(),
+            members: RwLock::new(vec![]),
+        })
+    }
+
+    /// The name of this group, used when reporting errors.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The parameters in this group.
+    pub fn members(&self) -> Vec<Arc<dyn ParameterNode>> {
+        self.members.read().unwrap().clone()
+    }
+
+    /// Is the parameter named `name` a member of this group?
+    pub fn contains(&self, name: &str) -> bool {
+        self.members.read().unwrap().iter().any(|m| &*m.name() == name)
+    }
+}
+
+impl fmt::Debug for RequiredGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let members = self.members
+                          .read()
+                          .unwrap()
+                          .iter()
+                          .map(|m| m.name())
+                          .collect::<Vec<_>>();
+        f.debug_struct("RequiredGroup")
+         .field("name", &self.name)
+         .field("members", &members)
+         .finish()
+    }
+}
+
+/// Add the parameter `node` to the required `group`.
+pub fn add_to_required_group(group: &RequiredGroup, node: Arc<dyn ParameterNode>) {
+    group.members.write().unwrap().push(node);
+}
+
+/// Require `requires` to be given whenever `dependent` is.
+///
+/// This is the same as `ParameterNode::add_requirement`, but
+/// can be used once the parameters have been constructed.
+pub fn add_dependency(dependent: &Arc<dyn ParameterNode>,
+                      requires: &Arc<dyn ParameterNode>)
+                      -> Result<(), FrozenError> {
+    dependent.add_requirement(&requires.name())
+}
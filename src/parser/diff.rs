@@ -0,0 +1,202 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Tree Differences
+//!
+//! Compare two command trees, as when a configuration has been
+//! reloaded, to find which nodes were added, removed or modified.
+//! Nodes are matched up by their names, level by level.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use commands::parser::diff::{diff, TreeChange};
+//! use commands::parser::nodes::*;
+//!
+//! let old = RootNode::new();
+//! old.add_successor(CommandNode::new("show",
+//!                                    PRIORITY_DEFAULT,
+//!                                    false,
+//!                                    None,
+//!                                    None,
+//!                                    vec![])).unwrap();
+//! let new = RootNode::new();
+//! new.add_successor(CommandNode::new("show",
+//!                                    PRIORITY_DEFAULT,
+//!                                    false,
+//!                                    None,
+//!                                    None,
+//!                                    vec![])).unwrap();
+//! new.add_successor(CommandNode::new("reload",
+//!                                    PRIORITY_DEFAULT,
+//!                                    false,
+//!                                    None,
+//!                                    None,
+//!                                    vec![])).unwrap();
+//! let (old, new): (Arc<dyn Node>, Arc<dyn Node>) = (old, new);
+//!
+//! let changes = diff(&old, &new);
+//! assert_eq!(changes.changes(), &[TreeChange::Added("reload".parse().unwrap())]);
+//! assert!(diff(&old, &old).is_empty());
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+use parser::nodes::{bfs, resolve, Node, NodePath};
+
+/// A property of a node which can differ between two trees.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum NodeField {
+    /// The help symbol.
+    HelpSymbol,
+    /// The help text.
+    HelpText,
+    /// Whether the node is hidden.
+    Hidden,
+    /// The priority.
+    Priority,
+}
+
+impl fmt::Display for NodeField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            NodeField::HelpSymbol => "help symbol".fmt(f),
+            NodeField::HelpText => "help text".fmt(f),
+            NodeField::Hidden => "hidden".fmt(f),
+            NodeField::Priority => "priority".fmt(f),
+        }
+    }
+}
+
+/// A difference between two trees.
+#[derive(Clone,Debug,PartialEq)]
+pub enum TreeChange {
+    /// The node at the path is only in the new tree.
+    Added(NodePath),
+    /// The node at the path is only in the old tree.
+    Removed(NodePath),
+    /// The node at the path is in both trees, but `field` differs.
+    Modified(NodePath, NodeField),
+}
+
+impl fmt::Display for TreeChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            TreeChange::Added(ref path) => write!(f, "added {}", path),
+            TreeChange::Removed(ref path) => write!(f, "removed {}", path),
+            TreeChange::Modified(ref path, field) => write!(f, "modified {} of {}", field, path),
+        }
+    }
+}
+
+/// The differences between two trees, as found by `diff`.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct TreeDiff {
+    changes: Vec<TreeChange>,
+}
+
+impl TreeDiff {
+    /// The changes, in the order in which the trees were walked.
+    pub fn changes(&self) -> &[TreeChange] {
+        &self.changes
+    }
+
+    /// Are the trees the same?
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Find the differences between the trees beneath `old` and `new`.
+///
+/// The roots themselves are only compared by their successors.
+/// Beneath a node which was added or removed, nothing more is
+/// listed. The nodes of `old` are walked with `bfs` first, listing
+/// those which were removed or modified, then those of `new`,
+/// listing those which were added.
+pub fn diff(old: &Arc<dyn Node>, new: &Arc<dyn Node>) -> TreeDiff {
+    let mut diff = TreeDiff::default();
+    for (path, old_node) in bfs(old.clone()).with_paths().skip(1) {
+        match resolve(new, &path) {
+            Some(new_node) => diff_node(&old_node, &new_node, &path, &mut diff),
+            None if resolve(new, &parent(&path)).is_some() => {
+                diff.changes.push(TreeChange::Removed(path))
+            }
+            None => {}
+        }
+    }
+    for (path, _) in bfs(new.clone()).with_paths().skip(1) {
+        if resolve(old, &path).is_none() && resolve(old, &parent(&path)).is_some() {
+            diff.changes.push(TreeChange::Added(path));
+        }
+    }
+    diff
+}
+
+/// The path of the parent of the node at `path`.
+fn parent(path: &NodePath) -> NodePath {
+    NodePath(path.0[..path.0.len() - 1].to_vec())
+}
+
+fn diff_node(old: &Arc<dyn Node>, new: &Arc<dyn Node>, path: &NodePath, diff: &mut TreeDiff) {
+    let fields = [(NodeField::HelpSymbol, old.help_symbol() != new.help_symbol()),
+                  (NodeField::HelpText, old.help_text() != new.help_text()),
+                  (NodeField::Hidden, old.hidden() != new.hidden()),
+                  (NodeField::Priority, old.priority() != new.priority())];
+    for &(field, _) in fields.iter().filter(|&&(_, differs)| differs) {
+        diff.changes.push(TreeChange::Modified(path.clone(), field));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::fixtures::{path, show_tree as tree};
+    use parser::nodes::*;
+
+    #[test]
+    fn same_trees() {
+        let old = tree(&[("route", PRIORITY_DEFAULT)]);
+        let new = tree(&[("route", PRIORITY_DEFAULT)]);
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn added_node() {
+        let old = tree(&[("route", PRIORITY_DEFAULT)]);
+        let new = tree(&[("route", PRIORITY_DEFAULT), ("version", PRIORITY_DEFAULT)]);
+        assert_eq!(diff(&old, &new).changes(), &[TreeChange::Added(path("show.version"))]);
+    }
+
+    #[test]
+    fn removed_node() {
+        let old = tree(&[("route", PRIORITY_DEFAULT), ("version", PRIORITY_DEFAULT)]);
+        let new = tree(&[("version", PRIORITY_DEFAULT)]);
+        let changes = diff(&old, &new);
+        assert_eq!(changes.changes(), &[TreeChange::Removed(path("show.route"))]);
+        assert_eq!(changes.changes()[0].to_string(), "removed show.route");
+    }
+
+    #[test]
+    fn priority_change() {
+        let old = tree(&[("route", PRIORITY_DEFAULT)]);
+        let new = tree(&[("route", PRIORITY_DEFAULT + 1)]);
+        let changes = diff(&old, &new);
+        assert_eq!(changes.changes(),
+                   &[TreeChange::Modified(path("show.route"), NodeField::Priority)]);
+        assert_eq!(changes.changes()[0].to_string(), "modified priority of show.route");
+    }
+
+    #[test]
+    fn wrapped_trees_are_compared() {
+        let old = tree(&[]);
+        old.add_successor(WrapperNode::new("help", None, old.clone())).unwrap();
+        let new = tree(&[("route", PRIORITY_DEFAULT)]);
+        new.add_successor(WrapperNode::new("help", None, new.clone())).unwrap();
+        assert!(diff(&old, &old).is_empty());
+        // The commands beneath the wrapper are only compared once.
+        assert_eq!(diff(&old, &new).changes(), &[TreeChange::Added(path("show.route"))]);
+    }
+}
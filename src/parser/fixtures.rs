@@ -0,0 +1,28 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Trees shared by the tests of the modules which find nodes by
+//! their paths.
+
+use std::sync::Arc;
+use parser::nodes::*;
+
+/// A tree with a `show` command, beneath which is a command for each
+/// of `commands`, given by its name and priority.
+pub fn show_tree(commands: &[(&str, i32)]) -> Arc<dyn Node> {
+    let root = RootNode::new();
+    let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+    for &(name, priority) in commands {
+        show.add_successor(CommandNode::new(name, priority, false, None, None, vec![])).unwrap();
+    }
+    root.add_successor(show).unwrap();
+    root
+}
+
+/// Parse `path`, which must be valid, as a `NodePath`.
+pub fn path(path: &str) -> NodePath {
+    path.parse().unwrap()
+}
@@ -0,0 +1,130 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Help
+//!
+//! Render help text for a node in the command tree.
+//!
+//! ```
+//! use commands::parser::help::HelpFormatter;
+//! use commands::parser::nodes::*;
+//!
+//! let root = RootNode::new();
+//! root.add_successor(CommandNode::new("show",
+//!                                     PRIORITY_DEFAULT,
+//!                                     false,
+//!                                     Some("Show things.".to_string()),
+//!                                     None,
+//!                                     vec![])).unwrap();
+//! assert_eq!(HelpFormatter::new().format(&*root), "  show  Show things.\n");
+//! ```
+
+use std::sync::Arc;
+use parser::nodes::*;
+
+/// Formats the help for a node and the nodes which may follow it.
+pub struct HelpFormatter {
+    indent: usize,
+}
+
+impl Default for HelpFormatter {
+    fn default() -> Self {
+        HelpFormatter::new()
+    }
+}
+
+impl HelpFormatter {
+    /// Construct a `HelpFormatter` which indents entries by 2 spaces.
+    pub fn new() -> Self {
+        HelpFormatter { indent: 2 }
+    }
+
+    /// Format the help for `node`.
+    ///
+    /// This begins with the help symbol and help text of the node,
+    /// unless it is the root, followed by an entry for each of the
+    /// nodes which may follow it. For a command, these include its
+    /// positional parameters. Hidden nodes are left out.
+    pub fn format(&self, node: &dyn Node) -> String {
+        let mut help = String::new();
+        if !node.help_symbol().is_empty() {
+            help.push_str(&node.help_symbol());
+            if let Some(text) = node.help_text() {
+                help.push_str(" - ");
+                help.push_str(&text);
+            }
+            help.push('\n');
+        }
+        let mut entries = node.successors()
+                              .iter()
+                              .filter(|n| !n.hidden())
+                              .cloned()
+                              .collect::<Vec<_>>();
+        if let Some(command) = node.as_command() {
+            entries.extend(command.parameters()
+                                  .into_iter()
+                                  .filter(|p| p.positional() && !p.hidden())
+                                  .map(|p| -> Arc<dyn Node> { p }));
+        }
+        let width = entries.iter().map(|n| n.help_symbol().len()).max().unwrap_or(0);
+        for entry in entries {
+            let line = match entry.help_text() {
+                Some(text) => {
+                    format!("{:indent$}{:width$}  {}",
+                            "",
+                            entry.help_symbol(),
+                            text,
+                            indent = self.indent,
+                            width = width)
+                }
+                None => format!("{:indent$}{}", "", entry.help_symbol(), indent = self.indent),
+            };
+            help.push_str(&line);
+            help.push('\n');
+        }
+        help
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use super::*;
+
+    fn command(name: &str, help_text: Option<&str>, hidden: bool) -> Arc<CommandNode> {
+        CommandNode::new(name,
+                         PRIORITY_DEFAULT,
+                         hidden,
+                         help_text.map(|t| t.to_string()),
+                         None,
+                         vec![])
+    }
+
+    #[test]
+    fn format_command() {
+        let show = command("show", Some("Show things."), false);
+        show.add_successor(command("interface", Some("Show interfaces."), false)).unwrap();
+        show.add_successor(command("route", None, false)).unwrap();
+        show.add_successor(command("secrets", Some("Hidden."), true)).unwrap();
+        assert_eq!(HelpFormatter::new().format(&*show),
+                   "show - Show things.\n  interface  Show interfaces.\n  route\n");
+    }
+
+    #[test]
+    fn format_positional_parameters() {
+        let parameter = Arc::new(SimpleParameterNode::new("host",
+                                                          Some("Host to ping.".to_string()),
+                                                          false,
+                                                          PRIORITY_PARAMETER,
+                                                          vec![],
+                                                          false,
+                                                          None,
+                                                          true));
+        let ping = command("ping", None, false);
+        ping.add_parameter(parameter).unwrap();
+        assert_eq!(HelpFormatter::new().format(&*ping), "ping\n  <host>  Host to ping.\n");
+    }
+}
@@ -9,8 +9,9 @@
 //! The `commands::parser` module provides:
 //!
 //! * Functionality for building a tree of commands based on their names.
-//! * Parsing tokenized input to select a command. Input tokenization can
-//!   be performed by the `commands::tokenizer` module.
+//! * Parsing input to select a command. A whole command line can be
+//!   given to `Parser::parse`, which tokenizes it with the
+//!   `commands::tokenizer` module.
 //! * Validating parameters.
 //! * Performing completion on commands and parameters.
 //!
@@ -24,18 +25,50 @@
 //!   tree. This parser is intended to be short-lived and to just live
 //!   for the duration of parsing and evaluating a single command line
 //!   input.
+//!
+//! Nodes are `Send` and `Sync`, so a finished tree can be shared
+//! between threads as an `Arc<RootNode>`, with each thread parsing
+//! with a `Parser` of its own. All of the state of a parse is held
+//! by the `Parser` rather than by the nodes.
 
 pub mod nodes;
 pub mod completion;
 pub mod builder;
+pub mod permissions;
+pub mod help;
+pub mod builtins;
+pub mod constraints;
+pub mod values;
+pub mod patch;
+pub mod diff;
+pub mod registry;
+pub mod modes;
+pub mod observers;
+#[cfg(test)]
+mod fixtures;
 
+use std::any::Any;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
-use std::rc::Rc;
+use std::mem;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use parser::nodes::*;
-use tokenizer::{Token, TokenType};
-use parser::completion::{Complete, Completion};
+use tokenizer::{tokenize, SourceLocation, SourceOffset, Token, TokenType, TokenizerError};
+use util::{caret_diagnostic, subsequence_span, suggestions};
+use parser::completion::{Completion, CompletionKind, CompletionMatching, CompletionMode,
+                         CompletionResult};
+use parser::constraints::MutuallyExclusiveGroup;
+use parser::modes::{Mode, ModeChange, ModeStack};
+use parser::observers::Observer;
+use parser::permissions::PermissionChecker;
+use parser::values::{ParamValue, ParameterValues};
 
 /// Command parser
 ///
@@ -55,288 +88,5520 @@ use parser::completion::{Complete, Completion};
 /// The parser is constructed as a `mut`able object as most of
 /// the methods on it will modify its state.
 pub struct Parser<'p> {
-    current_node: Rc<Node>,
+    root: Arc<dyn Node>,
+    current_node: Arc<dyn Node>,
     /// The nodes which have been accepted during `parse` or `advance`.
-    pub nodes: Vec<Rc<Node>>,
+    pub nodes: Vec<Arc<dyn Node>>,
     /// The tokens which have been accepted during `parse` or `advance`.
     pub tokens: Vec<Token<'p>>,
-    commands: Vec<Rc<CommandNode>>,
-    parameters: HashMap<String, String>,
+    commands: Vec<Arc<dyn Node>>,
+    parameters: HashMap<String, Vec<String>>,
+    /// The node to continue from once the value of a named
+    /// parameter has been given.
+    resume_node: Option<Arc<dyn Node>>,
+    /// The state before each accepted token, for `undo`.
+    history: Vec<ParseStep>,
+    warnings: Vec<ParseWarning>,
+    ignore_unknown_parameters: bool,
+    strict: bool,
+    trailing_tokens: TrailingTokens,
+    /// The tokens left over after a complete command, in
+    /// `TrailingTokens::Permissive` mode.
+    leftover_tokens: Vec<Token<'p>>,
+    max_tokens: Option<usize>,
+    backtrack_depth: usize,
+    /// Whether tokens are being parsed again while backtracking,
+    /// which doesn't backtrack any further.
+    backtracking: bool,
+    case_insensitive_completion: bool,
+    fuzzy_completion: bool,
+    /// The command to accept for a line whose first token matches
+    /// nothing, from `RootNode::fallback`.
+    fallback: Option<Arc<dyn Node>>,
+    use_fallback: bool,
+    modes: ModeStack,
+    permission_checker: Option<Box<dyn PermissionChecker>>,
+    user_role: String,
+    observers: Vec<Box<dyn Observer>>,
+    quiet: bool,
 }
 
 impl<'p> Parser<'p> {
     /// Construct a parser with a root node.
-    pub fn new(initial_node: Rc<RootNode>) -> Parser<'p> {
+    ///
+    /// This is cheap, as the tree is shared rather than copied, so a
+    /// new parser may be made for each command line. A parser can
+    /// also be `reset` to parse another line, keeping its settings,
+    /// like the permission checker, which is easier in a loop.
+    pub fn new(initial_node: Arc<RootNode>) -> Parser<'p> {
+        let fallback = initial_node.fallback().map(|c| c as Arc<dyn Node>);
         Parser {
+            root: initial_node.clone(),
             current_node: initial_node,
             nodes: vec![],
             tokens: vec![],
             commands: vec![],
             parameters: HashMap::new(),
+            resume_node: None,
+            history: vec![],
+            warnings: vec![],
+            ignore_unknown_parameters: false,
+            strict: false,
+            trailing_tokens: TrailingTokens::Strict,
+            leftover_tokens: vec![],
+            max_tokens: None,
+            backtrack_depth: 0,
+            backtracking: false,
+            case_insensitive_completion: false,
+            fuzzy_completion: false,
+            fallback,
+            use_fallback: true,
+            modes: ModeStack::new(),
+            permission_checker: None,
+            user_role: String::new(),
+            observers: vec![],
+            quiet: false,
+        }
+    }
+
+    /// Restrict the nodes which can be completed and parsed to
+    /// those that `checker` permits for a user with `user_role`.
+    pub fn set_permission_checker(&mut self,
+                                  checker: Box<dyn PermissionChecker>,
+                                  user_role: &str) {
+        self.permission_checker = Some(checker);
+        self.user_role = user_role.to_string();
+    }
+
+    /// Tell `observer` about what the parser does from now on, after
+    /// the observers which were added before it.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Tell each of the observers about an event, unless the parser
+    /// is running `quietly`.
+    fn notify<F: Fn(&dyn Observer)>(&self, event: F) {
+        if self.quiet {
+            return;
+        }
+        for observer in &self.observers {
+            // The default panic hook has reported the panic already.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| event(&**observer)));
+        }
+    }
+
+    /// Run `f` without telling the observers about anything, as
+    /// for completing and checking, which don't act on the line.
+    fn quietly<T, F: FnOnce(&mut Self) -> T>(&mut self, f: F) -> T {
+        let quiet = mem::replace(&mut self.quiet, true);
+        let result = f(self);
+        self.quiet = quiet;
+        result
+    }
+
+    /// Skip tokens after a command which match nothing, rather than
+    /// failing with `ParseError::NoMatches`, raising a
+    /// `ParseWarning::IgnoredUnknownParameter` for each of them.
+    ///
+    /// A token is only unknown when the command has no positional
+    /// parameter left to give it to. While one of them hasn't been
+    /// given, the token is bound to it as usual, without a warning,
+    /// and only the tokens after the last positional are skipped.
+    ///
+    /// Skipped tokens aren't accepted, so `undo` steps over them.
+    pub fn set_ignore_unknown_parameters(&mut self, ignore: bool) {
+        self.ignore_unknown_parameters = ignore;
+    }
+
+    /// In strict mode, anything which would raise a `ParseWarning`
+    /// fails with `ParseError::StrictModeViolation` instead, for
+    /// applications which don't tolerate deprecated or ambiguous usage.
+    pub fn set_strict_mode(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// How tokens which match nothing once the command is complete
+    /// are handled. By default, they are `TrailingTokens::Strict`.
+    pub fn set_trailing_tokens(&mut self, trailing_tokens: TrailingTokens) {
+        self.trailing_tokens = trailing_tokens;
+    }
+
+    /// The tokens which were left over after a complete command, in
+    /// `TrailingTokens::Permissive` mode, in the order given.
+    pub fn leftover_tokens(&self) -> &[Token<'p>] {
+        &self.leftover_tokens
+    }
+
+    /// Accept no more than `max_tokens` tokens for a command, failing
+    /// with `ParseError::TooManyTokens` for any after those, as
+    /// applications may want to reject overly long command lines.
+    ///
+    /// Tokens left over in `TrailingTokens::Permissive` mode count
+    /// towards the limit, while those skipped as unknown parameters
+    /// don't, as they aren't accepted.
+    pub fn set_max_tokens(&mut self, max_tokens: usize) {
+        self.max_tokens = Some(max_tokens);
+    }
+
+    /// Backtrack when a token can't be accepted, trying the other
+    /// nodes which any of the last `depth` accepted tokens matched,
+    /// as when `show log` is a command but `log` could also have
+    /// been the value of `show <name>`. A `depth` of 0, the default,
+    /// turns backtracking off.
+    ///
+    /// The tokens after the one which is reinterpreted are parsed
+    /// again, and the first interpretation, from the most recent
+    /// token back, which accepts all of them is kept. When several
+    /// nodes of the same token do, the token is a
+    /// `ParseError::AmbiguousMatch`. The depth bounds the number of
+    /// tokens which are parsed again, each of which doesn't backtrack
+    /// itself.
+    pub fn set_backtracking(&mut self, depth: usize) {
+        self.backtrack_depth = depth;
+    }
+
+    /// Also complete the nodes whose names the token is a prefix of
+    /// when case is ignored, so that `SH` completes `show`, even for
+    /// nodes which are `CaseSensitivity::Sensitive`. This only affects
+    /// completion: the chosen option must still be entered as the
+    /// node matches it. Such completions are
+    /// `CompletionMatching::CaseInsensitive`.
+    pub fn set_case_insensitive_completion(&mut self, case_insensitive: bool) {
+        self.case_insensitive_completion = case_insensitive;
+    }
+
+    /// When nothing matches the token as a prefix, complete the nodes
+    /// whose names hold the characters of the token in order, so that
+    /// `ifc` completes `interface-config`. These are
+    /// `CompletionMatching::Fuzzy`, ordered from the tightest match,
+    /// as measured by `util::subsequence_span`, then by priority.
+    pub fn set_fuzzy_completion(&mut self, fuzzy: bool) {
+        self.fuzzy_completion = fuzzy;
+    }
+
+    /// Whether a line whose first token matches nothing is given to
+    /// the fallback command of the root, as set with
+    /// `RootNode::set_fallback`. This is on by default, and can be
+    /// turned off for scripts which must only use real commands.
+    pub fn set_use_fallback(&mut self, use_fallback: bool) {
+        self.use_fallback = use_fallback;
+    }
+
+    /// Is the user permitted to use `node`? Everything is
+    /// permitted when there is no `PermissionChecker`.
+    fn permitted(&self, node: &dyn Node) -> bool {
+        match self.permission_checker {
+            Some(ref checker) => checker.is_permitted(node, &self.user_role),
+            None => true,
         }
     }
 
     /// Given an optional token, get the possible valid completions
     /// for the current parser state.
     ///
-    /// Possible completions are successors of the current node which
-    /// are not `hidden`, are `acceptable`, and which match the token,
-    /// if one has been provided.
+    /// Possible completions are successors of the current node, and
+    /// the positional parameter that the next value would be given
+    /// to, which are not `hidden` or disabled, are `available` and
+    /// `acceptable`, and which `Node::completes` the token, if one
+    /// has been provided. Parameters which conflict with a parameter
+    /// that has already been given, or share an exclusive group with
+    /// one, are not offered, nor are nodes that the user isn't
+    /// permitted to use.
+    ///
+    /// Deprecated commands are still offered, with `[DEPRECATED]`
+    /// added to their help text.
+    ///
+    /// With `set_case_insensitive_completion` and
+    /// `set_fuzzy_completion`, nodes which the token matches more
+    /// loosely are offered too, with the `matching` of each
+    /// `Completion` telling how.
     ///
-    /// Nodes may customize the `Complete` trait to customize the
+    /// Nodes may override `Node::complete` to customize the
     /// `Completion` and `CompletionOption`s which are generated
     /// for that node.
     ///
     /// Each valid successor node will have one `Completion` in the
-    /// result vector. Each `Completion` will have one or more
-    /// `CompletionOption` for each valid way that the value may be
-    /// entered.
-    pub fn complete(&self, token: Option<Token<'p>>) -> Vec<Completion> {
+    /// result vector, ordered from the highest priority to the lowest,
+    /// so that the nodes which `advance` prefers come first. Each
+    /// `Completion` will have one or more `CompletionOption` for each
+    /// valid way that the value may be entered.
+    pub fn complete(&self, token: Option<Token<'p>>) -> Vec<Completion<'_>> {
+        let mut candidates = self.current_node.successors().to_vec();
+        let positional = self.positional_parameters();
+        if let Some(parameter) = self.next_positional(&positional) {
+            candidates.push(parameter);
+        }
+        // Offer the nodes which would win a tie first.
+        candidates.sort_by_key(|n| Reverse(n.priority()));
+        candidates.retain(|n| self.completable(&**n));
+        let t = match token {
+            Some(t) => t,
+            None => return candidates.iter().map(|n| self.complete_node(&**n, None)).collect(),
+        };
+        let insensitive = CaseSensitivity::Insensitive;
+        let mut completions = vec![];
+        for n in &candidates {
+            if n.completes(t.text) {
+                completions.push(self.complete_node(&**n, token));
+            } else if self.case_insensitive_completion &&
+                      insensitive.starts_with(&n.name(), t.text) {
+                completions.push(self.loosely_complete(&**n,
+                                                       t,
+                                                       CompletionMatching::CaseInsensitive,
+                                                       |o| insensitive.starts_with(o, t.text)));
+            }
+        }
+        if completions.is_empty() && self.fuzzy_completion {
+            let mut fuzzy = candidates.iter()
+                                      .filter_map(|n| {
+                                          subsequence_span(t.text, &n.name()).map(|span| (span, n))
+                                      })
+                                      .collect::<Vec<_>>();
+            // The sort is stable, so ties are still by priority.
+            fuzzy.sort_by_key(|&(span, _)| span);
+            completions = fuzzy.into_iter()
+                               .map(|(_, n)| {
+                                   self.loosely_complete(&**n,
+                                                         t,
+                                                         CompletionMatching::Fuzzy,
+                                                         |o| subsequence_span(t.text, o).is_some())
+                               })
+                               .collect();
+        }
+        completions
+    }
+
+    /// Could `node` be offered as a completion? To be one, it should
+    /// not be hidden or disabled, it should be available, permitted
+    /// and acceptable, and it should not conflict or share an
+    /// exclusive group with a parameter which has already been given.
+    fn completable(&self, node: &dyn Node) -> bool {
+        !node.hidden() && enabled(node) && node.available() && self.permitted(node) &&
+        self.acceptable(node) && self.negation_allows(node) &&
+        !self.conflicts_with_given(&node.name()) &&
+        self.exclusive_with_given(&node.name()).is_none()
+    }
+
+    /// Get the completion for `node`, which `token` only matches as
+    /// `matching` allows, keeping the options which `keep`.
+    fn loosely_complete<'t, F>(&self,
+                               node: &dyn Node,
+                               token: Token<'t>,
+                               matching: CompletionMatching,
+                               keep: F)
+                               -> Completion<'t>
+        where F: Fn(&str) -> bool
+    {
+        let mut completion = self.complete_node(node, None);
+        completion.options.retain(|o| keep(&o.option_string));
+        completion.token = Some(token);
+        completion.matching = matching;
+        completion
+    }
+
+    /// Get the completion for `node`, with its `kind` and whether it
+    /// is `terminal`, adding `[DEPRECATED]` to its help text when it
+    /// is a deprecated command.
+    fn complete_node<'t>(&self, node: &dyn Node, token: Option<Token<'t>>) -> Completion<'t> {
+        let mut completion = node.complete(token);
+        if node.as_command().and_then(|c| c.deprecated()).is_some() {
+            completion.help_text = Some(match completion.help_text {
+                Some(text) => format!("{} [DEPRECATED]", text),
+                None => "[DEPRECATED]".to_string(),
+            });
+        }
+        completion.kind = CompletionKind::of(node);
+        completion.terminal = self.terminal(node);
+        completion
+    }
+
+    /// Could the command be executed once `node` has been accepted,
+    /// as far as the parameters which are still required go?
+    fn terminal(&self, node: &dyn Node) -> bool {
+        if node.as_wrapper().is_some() {
+            return true;
+        }
+        if let Some(command) = node.as_command() {
+            return command.handler().is_some() && command.required_param_count() == 0 &&
+                   command.required_groups().is_empty();
+        }
+        if node.as_parameter_name().is_some() || self.command().is_none() {
+            return false;
+        }
+        self.remaining_required().iter().all(|p| {
+            let found = self.parameters.get(&*p.name()).map_or(0, |v| v.len());
+            p.name() == node.name() && found + 1 >= p.min_repetitions()
+        })
+    }
+
+    /// Get a completion for each successor of the current node which
+    /// matches `token`, without breaking ties between them.
+    ///
+    /// When `advance` reports an `AmbiguousMatch`, these are the nodes
+    /// which the user may have meant, including those that lost on
+    /// priority. The same nodes are left out as by `complete`, such as
+    /// those which are hidden or conflict with a given parameter.
+    pub fn ambiguous_completions(&self, token: &str) -> Vec<Completion<'_>> {
         self.current_node
             .successors()
-            .into_iter()
-            .filter(|n| {
-                // To be a possible completion, the node should not be
-                // hidden, it should be acceptable, and if there's a token,
-                // it should be a valid match for the node.
-                !n.hidden() && n.acceptable(self) &&
-                if let Some(t) = token {
-                    n.matches(self, t)
-                } else {
-                    true
-                }
-            })
-            .map(|n| n.complete(token))
-            .collect::<Vec<_>>()
+            .iter()
+            .filter(|n| self.completable(&***n) && n.matches(token))
+            .map(|n| self.complete_node(&**n, None))
+            .collect()
     }
 
-    /// Parse a vector of tokens, advancing through the
-    /// node hierarchy.
+    /// Complete the token at byte `cursor` within `line`, as when tab
+    /// is pressed with the cursor in the middle of a line.
+    ///
+    /// The parser is `reset` and the tokens before the one at the
+    /// cursor are parsed, without telling the observers. The token is then completed from the part
+    /// of it before the cursor: what follows the cursor is ignored,
+    /// except that the `replace` range of the result covers the whole
+    /// token. Within double quotes, the token is completed from after
+    /// the opening quote, and without a closing quote it runs to the
+    /// end of the line. After whitespace, everything is completed and
+    /// the range is empty.
+    ///
+    /// The parser is left where the tokens before the cursor took it.
+    /// The `mode` of the result tells these apart: `CurrentWord`
+    /// when a word was completed, and `NextWord` after whitespace.
     ///
     /// ```
-    /// use commands::parser::nodes::RootNode;
+    /// use commands::parser::nodes::*;
     /// use commands::parser::Parser;
-    /// use commands::tokenizer::tokenize;
     ///
     /// let root = RootNode::new();
+    /// root.add_successor(CommandNode::new("show",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     None,
+    ///                                     vec![])).unwrap();
     /// let mut parser = Parser::new(root);
     ///
-    /// if let Ok(tokens) = tokenize("show interface") {
-    ///     parser.parse(tokens);
+    /// let result = parser.complete_at("shxx", 2).unwrap();
+    /// assert_eq!(result.completions[0].help_symbol, "show");
+    /// assert_eq!(result.replace, 0..4);
+    /// ```
+    pub fn complete_at(&mut self,
+                       line: &'p str,
+                       cursor: usize)
+                       -> Result<CompletionResult<'_>, ParseError<'p>> {
+        let mut cursor = cursor.min(line.len());
+        while !line.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        let head = &line[..cursor];
+        let start = match tokenize(head) {
+            Ok(tokens) => {
+                match tokens.last() {
+                    Some(last) if last.token_type == TokenType::Word => cursor - last.text.len(),
+                    _ => cursor,
+                }
+            }
+            Err(TokenizerError::UnclosedDoubleQuoteAtEndOfInput) => opening_quote(head),
+            Err(error) => return Err(ParseError::Tokenize(error)),
+        };
+        self.reset();
+        self.quietly(|parser| parser.parse(&line[..start]))?;
+        let quoted = line[start..].starts_with('"');
+        let end = if start == cursor && !quoted { cursor } else { token_end(line, start, cursor) };
+        // The quotes around a token aren't part of what it matches.
+        let mut prefix = start..cursor;
+        if quoted {
+            prefix.start += 1;
+            if prefix.end > prefix.start && line[..prefix.end].ends_with('"') {
+                prefix.end -= 1;
+            }
+        }
+        let (mode, token) = if start == cursor {
+            (CompletionMode::NextWord, None)
+        } else {
+            let offset = |byte: usize| {
+                let char = line[..byte].chars().count();
+                SourceOffset::new(char, 0, char)
+            };
+            let location = SourceLocation::new(offset(prefix.start), offset(prefix.end));
+            let token = Token::new(&line[prefix], TokenType::Word, location);
+            (CompletionMode::CurrentWord, Some(token))
+        };
+        let completions = self.complete(token);
+        let matching = completions.iter().map(|c| c.matching).max().unwrap_or_default();
+        Ok(CompletionResult {
+            completions,
+            mode,
+            matching,
+            replace: start..end,
+        })
+    }
+
+    /// Complete the end of `line`, as typed so far: the last word of
+    /// it, or after trailing whitespace, the word which can come
+    /// next, as `complete_at` does with the cursor at the end.
+    pub fn complete_line(&mut self, line: &'p str) -> Result<CompletionResult<'_>, ParseError<'p>> {
+        self.complete_at(line, line.len())
+    }
+
+    /// Parse a whole command line, advancing through the node
+    /// hierarchy for each of its words.
+    ///
+    /// The line is split up by the tokenizer, so quoting and escaping
+    /// work as they do there. Parsing stops at the first token which
+    /// can't be accepted. The error carries that token, and its
+    /// `location` gives its position within the line. A line which is
+    /// empty or only whitespace is accepted without selecting a
+    /// command.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    /// use commands::parser::{ParseError, Parser};
+    ///
+    /// let root = RootNode::new();
+    /// root.add_successor(CommandNode::new("show",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     None,
+    ///                                     vec![])).unwrap();
+    ///
+    /// let mut parser = Parser::new(root.clone());
+    /// assert!(parser.parse("show").is_ok());
+    ///
+    /// let mut parser = Parser::new(root);
+    /// match parser.parse("show interface") {
+    ///     Err(ParseError::UnexpectedToken { token, index }) => {
+    ///         assert_eq!(index, 1);
+    ///         assert_eq!(token.location.start.char, 5);
+    ///     }
+    ///     _ => panic!(),
     /// }
     /// ```
-    pub fn parse(&mut self, tokens: Vec<Token<'p>>) -> Result<(), ParseError<'p>> {
+    pub fn parse(&mut self, line: &'p str) -> Result<(), ParseError<'p>> {
+        let tokens = tokenize(line).map_err(|error| {
+                                        let error = ParseError::Tokenize(error);
+                                        self.notify(|o| o.on_parse_error(&error));
+                                        error
+                                    })?;
+        self.parse_tokens(tokens)
+    }
+
+    /// Parse a vector of tokens, such as those from `tokenize`,
+    /// advancing through the node hierarchy.
+    pub fn parse_tokens(&mut self, tokens: Vec<Token<'p>>) -> Result<(), ParseError<'p>> {
         for token in tokens {
             match token.token_type {
                 TokenType::Invalid => unreachable!(),
                 TokenType::Whitespace => {}
-                TokenType::Word => try!(self.advance(token)),
+                TokenType::Word => self.advance(token)?,
             }
         }
         Ok(())
     }
 
     /// Parse a single token, advancing through the node hierarchy.
+    ///
+    /// The token is matched against the successors of the current
+    /// node, including hidden ones. When several match, a node named
+    /// exactly by the token wins, and then the node with the highest
+    /// priority. A token which matches no successor is bound to the
+    /// next positional parameter of the accepted command.
     pub fn advance(&mut self, token: Token<'p>) -> Result<(), ParseError<'p>> {
-        // We clone the current node so that it doesn't stay borrowed
-        // and break things when we try to modify it below.
-        let cn = self.current_node.clone();
-        let matches = cn.successors()
-                        .into_iter()
-                        .filter(|n| n.acceptable(self) && n.matches(self, token))
-                        .collect::<Vec<_>>();
-        match matches.len() {
-            1 => {
-                let matching_node = matches[0];
-                matching_node.accept(self, token);
-                self.current_node = matching_node.clone();
-                self.nodes.push(matching_node.clone());
-                self.tokens.push(token);
-                Ok(())
+        if self.observers.is_empty() {
+            return self.advance_one(token);
+        }
+        let nodes = self.nodes.clone();
+        let result = self.advance_one(token);
+        match result {
+            Ok(()) => {
+                // Backtracking may have accepted other nodes for the
+                // tokens before this one.
+                let unchanged = nodes.iter()
+                                     .zip(&self.nodes)
+                                     .take_while(|&(a, b)| a == b)
+                                     .count();
+                for (node, token) in self.nodes.iter().zip(&self.tokens).skip(unchanged) {
+                    self.notify(|o| o.on_accept(&**node, token));
+                }
             }
-            0 => Err(ParseError::NoMatches(token)),
-            _ => Err(ParseError::AmbiguousMatch(token)),
+            Err(ref error) => self.notify(|o| o.on_parse_error(error)),
         }
+        result
     }
 
-    /// Execute the command that has been accepted by the parser.
-    ///
-    /// * XXX: This should be returning a Result probably.
-    pub fn execute(&self) {
-        if !self.commands.is_empty() {
-            unimplemented!();
-            // self.commands[0].execute(self)
+    /// `advance`, without telling the observers.
+    fn advance_one(&mut self, token: Token<'p>) -> Result<(), ParseError<'p>> {
+        let given = self.tokens.len() + self.leftover_tokens.len();
+        if let Some(max) = self.max_tokens.filter(|&max| given >= max) {
+            return Err(ParseError::TooManyTokens {
+                token,
+                index: given,
+                max,
+            });
+        }
+        if !self.leftover_tokens.is_empty() {
+            return self.leave_over(token);
+        }
+        let mut step = self.step();
+        let accepted = self.tokens.len();
+        match self.advance_token(token, &mut step) {
+            Err(ref error) if self.trailing(error) => return self.leave_over(token),
+            Err(error) => {
+                if self.dead_end(&error) {
+                    if let Some(result) = self.backtrack(token) {
+                        return result;
+                    }
+                }
+                return Err(error);
+            }
+            Ok(()) => {}
+        }
+        if self.tokens.len() > accepted {
+            self.history.push(step);
         }
+        Ok(())
     }
 
-    /// Verify that the parser is in a valid state with
-    /// respect to having accepted a command and all
-    /// required parameters.
-    pub fn verify(&self) -> Result<(), VerifyError> {
-        if self.commands.is_empty() {
-            return Err(VerifyError::NoCommandAccepted);
-        } else {
-            for expected in self.commands[0].parameters() {
-                if expected.required() && !self.parameters.contains_key(expected.name()) {
-                    return Err(VerifyError::MissingParameter(expected.name().clone()));
+    /// The state before the next token, to push onto the `history`
+    /// once it has been accepted.
+    fn step(&self) -> ParseStep {
+        ParseStep {
+            current_node: self.current_node.clone(),
+            resume_node: self.resume_node.clone(),
+            commands: self.commands.len(),
+            warnings: self.warnings.len(),
+            binding: None,
+            alternatives: vec![],
+        }
+    }
+
+    /// Can a token which raised `error` be accepted by backtracking?
+    /// Only when it doesn't fit after what was accepted before.
+    fn dead_end(&self, error: &ParseError<'p>) -> bool {
+        let unmatched = matches!(*error,
+                                 ParseError::NoMatches { .. } |
+                                 ParseError::UnexpectedToken { .. } |
+                                 ParseError::TooManyArguments { .. } |
+                                 ParseError::InvalidParameterValue { .. });
+        self.backtrack_depth > 0 && !self.backtracking && unmatched
+    }
+
+    /// Try to accept `token` by accepting another node for one of
+    /// the last `backtrack_depth` tokens, as `set_backtracking`
+    /// describes. Returns `None`, leaving the parser as it was, when
+    /// no interpretation accepts every token.
+    fn backtrack(&mut self, token: Token<'p>) -> Option<Result<(), ParseError<'p>>> {
+        let saved = self.snapshot();
+        let mut tokens = self.tokens.clone();
+        tokens.push(token);
+        let oldest = self.history.len().saturating_sub(self.backtrack_depth);
+        self.backtracking = true;
+        let mut result = None;
+        for index in (oldest..self.history.len()).rev() {
+            let mut accepted = vec![];
+            for alternative in saved.history[index].alternatives.clone() {
+                self.restore(&saved);
+                while self.history.len() > index {
+                    self.undo();
+                }
+                if self.reinterpret(&alternative, &tokens[index..]).is_ok() {
+                    accepted.push((alternative, self.snapshot()));
                 }
             }
+            result = match accepted.len() {
+                0 => continue,
+                1 => {
+                    self.restore(&accepted[0].1);
+                    Some(Ok(()))
+                }
+                _ => {
+                    Some(Err(ParseError::AmbiguousMatch {
+                        token: tokens[index],
+                        index,
+                        candidates: accepted.iter().map(|a| a.0.name().to_string()).collect(),
+                    }))
+                }
+            };
+            break;
+        }
+        if !matches!(result, Some(Ok(()))) {
+            self.restore(&saved);
+        }
+        self.backtracking = false;
+        result
+    }
+
+    /// Accept `node` for the first of `tokens`, and then parse the
+    /// rest of them.
+    fn reinterpret(&mut self,
+                   node: &Arc<dyn Node>,
+                   tokens: &[Token<'p>])
+                   -> Result<(), ParseError<'p>> {
+        let mut step = self.step();
+        let current_node = self.current_node.clone();
+        self.accept(&current_node, node, tokens[0], vec![], &mut step)?;
+        self.history.push(step);
+        for &token in &tokens[1..] {
+            self.advance_one(token)?;
         }
         Ok(())
     }
-}
 
-/// Errors that calling `parse` on the `Parser` can raise.
-#[derive(Clone,Debug)]
-pub enum ParseError<'t> {
-    /// The parser is in an invalid state.
-    InvalidState,
-    /// There were no matches for the token.
-    NoMatches(Token<'t>),
-    /// There was more than 1 possible match for the token.
-    AmbiguousMatch(Token<'t>), // XXX: One day, add: Vec<&'p Rc<Node>>),
-}
+    /// Can the token which raised `error` be left over? It can in
+    /// permissive mode, when it doesn't fit after a command which
+    /// is complete.
+    fn trailing(&self, error: &ParseError<'p>) -> bool {
+        let unmatched = matches!(*error,
+                                 ParseError::NoMatches { .. } |
+                                 ParseError::UnexpectedToken { .. } |
+                                 ParseError::TooManyArguments { .. });
+        let permissive = self.trailing_tokens == TrailingTokens::Permissive;
+        permissive && unmatched && self.verify_parameters().is_ok()
+    }
 
-impl<'t> Error for ParseError<'t> {
-    fn description(&self) -> &str {
-        match *self {
-            ParseError::InvalidState => "Invalid state.",
-            ParseError::NoMatches(_) => "No match.",
-            ParseError::AmbiguousMatch(_) => "Ambiguous match.",
+    /// Add `token` to the `leftover_tokens`, as are all of the tokens
+    /// after the first one.
+    fn leave_over(&mut self, token: Token<'p>) -> Result<(), ParseError<'p>> {
+        self.warn(ParseWarning::IgnoredTrailingToken { token: token.text.to_string() })?;
+        self.leftover_tokens.push(token);
+        Ok(())
+    }
+
+    /// The warnings raised while parsing, such as for deprecated
+    /// commands, in the order in which they were raised.
+    ///
+    /// Unlike a `ParseError`, a warning doesn't stop the command
+    /// from being parsed or executed.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Raise `warning`, or fail with `ParseError::StrictModeViolation`
+    /// in strict mode.
+    fn warn(&mut self, warning: ParseWarning) -> Result<(), ParseError<'p>> {
+        if self.strict {
+            return Err(ParseError::StrictModeViolation(warning));
         }
+        self.warnings.push(warning);
+        Ok(())
     }
-}
 
-impl<'t> fmt::Display for ParseError<'t> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        self.description().fmt(f)
+    /// Take the warnings raised so far, leaving none behind, as when
+    /// they have been shown to the user.
+    pub fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        for step in &mut self.history {
+            step.warnings = 0;
+        }
+        mem::take(&mut self.warnings)
     }
-}
 
-/// Errors that calling `verify` on the `Parser` can raise.
-#[derive(Clone,Debug)]
-pub enum VerifyError {
-    /// No command has been accepted by the parser.
-    NoCommandAccepted,
-    /// A required parameter is missing.
-    MissingParameter(String),
-}
+    /// Step back over the most recently accepted token, restoring
+    /// the state from before it was parsed.
+    ///
+    /// Returns `false` when no token has been accepted.
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(step) => {
+                self.current_node = step.current_node;
+                self.resume_node = step.resume_node;
+                self.commands.truncate(step.commands);
+                self.warnings.truncate(step.warnings);
+                match step.binding {
+                    Some((name, Some(values))) => {
+                        self.parameters.insert(name, values);
+                    }
+                    Some((name, None)) => {
+                        self.parameters.remove(&name);
+                    }
+                    None => {}
+                }
+                self.nodes.pop();
+                self.tokens.pop();
+                true
+            }
+            None => false,
+        }
+    }
 
-impl Error for VerifyError {
-    fn description(&self) -> &str {
-        match *self {
-            VerifyError::NoCommandAccepted => "No command has been accepted by the parser.",
-            VerifyError::MissingParameter(_) => "A required parameter is missing.",
+    /// Discard everything which has been parsed, returning to the
+    /// root, so that the parser can be used for another command line.
+    /// Within a mode, this is the root of the mode's tree, or the node
+    /// given to `reset_to_node`, which is the accepted command again.
+    ///
+    /// The tree, the modes and the permission checker are kept.
+    pub fn reset(&mut self) {
+        let root = self.active_root();
+        self.reset_to(root);
+    }
+
+    /// `reset`, parsing from `root` rather than the active root.
+    fn reset_to(&mut self, root: Arc<dyn Node>) {
+        self.current_node = root;
+        self.nodes.clear();
+        self.tokens.clear();
+        self.commands.clear();
+        self.parameters.clear();
+        self.resume_node = None;
+        self.history.clear();
+        self.warnings.clear();
+        self.leftover_tokens.clear();
+        if !self.current_node.is_root() {
+            let node = self.current_node.clone();
+            self.record_command(&node);
         }
     }
-}
 
-impl fmt::Display for VerifyError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        self.description().fmt(f)
+    /// Save what has been parsed, so that the parser can go back to
+    /// it with `restore`, as when a line editor parses the line as it
+    /// is typed and a character is deleted.
+    pub fn snapshot(&self) -> ParserSnapshot<'p> {
+        ParserSnapshot {
+            current_node: self.current_node.clone(),
+            nodes: self.nodes.clone(),
+            tokens: self.tokens.clone(),
+            commands: self.commands.clone(),
+            parameters: self.parameters.clone(),
+            resume_node: self.resume_node.clone(),
+            history: self.history.clone(),
+            warnings: self.warnings.clone(),
+            leftover_tokens: self.leftover_tokens.clone(),
+        }
     }
-}
 
-/// Can this node be accepted in the current parser state?
-pub trait Acceptable {
-    /// Can this node be accepted in the current parser state?
-    fn acceptable(&self, parser: &Parser) -> bool;
-}
+    /// Go back to what had been parsed when `snapshot` was taken.
+    ///
+    /// The settings of the parser, like its permission checker, and
+    /// its modes aren't part of a snapshot and are kept.
+    pub fn restore(&mut self, snapshot: &ParserSnapshot<'p>) {
+        let snapshot = snapshot.clone();
+        self.current_node = snapshot.current_node;
+        self.nodes = snapshot.nodes;
+        self.tokens = snapshot.tokens;
+        self.commands = snapshot.commands;
+        self.parameters = snapshot.parameters;
+        self.resume_node = snapshot.resume_node;
+        self.history = snapshot.history;
+        self.warnings = snapshot.warnings;
+        self.leftover_tokens = snapshot.leftover_tokens;
+    }
 
-impl Acceptable for Rc<Node> {
-    /// By default, a node can be accepted when it hasn't been seen yet.
-    fn acceptable(&self, parser: &Parser) -> bool {
-        !parser.nodes.contains(self)
+    /// Move the parser to `node`, without consuming any input, as
+    /// when entering a configuration mode for part of the tree.
+    ///
+    /// Anything which was parsed before is discarded. When `node`
+    /// is a command, it becomes the accepted command, so that its
+    /// parameters can be given. The node must be in the tree of
+    /// this parser, or `ParseError::NodeNotInTree` is returned.
+    ///
+    /// The node is entered as a mode named after it, on the `modes`,
+    /// so that the parser keeps returning to it when it is `reset`.
+    /// When the innermost mode was entered with `reset_to_node` too,
+    /// it is left first, so the node must be in the tree of the mode
+    /// outside it. Leaving and entering modes runs the `on_exit` hook
+    /// of the command of the old mode, and then the `on_enter` hook
+    /// of `node`, given what was parsed before. Moving to the same
+    /// node again, as before each line of a mode, runs neither.
+    pub fn reset_to_node(&mut self, node: Arc<dyn Node>) -> Result<(), ParseError<'p>> {
+        if ancestors(&self.tree_root(), &*node).is_none() {
+            return Err(ParseError::NodeNotInTree(node.name().to_string()));
+        }
+        let in_node = self.modes.current().filter(|m| !m.root().is_root());
+        if in_node.as_ref().map(|m| m.root()) != Some(&node) {
+            if in_node.is_some() {
+                self.modes.pop();
+            }
+            let values = self.values();
+            let context = HandlerContext::new(&*node, &self.nodes, &values);
+            let mode = Mode::new(&node.name(), node.clone()).with_command(node.clone(), &context);
+            self.modes.enter(mode);
+        }
+        self.reset();
+        Ok(())
     }
-}
 
-impl Acceptable for RepeatableNode {
-    /// A repeatable node can be accepted.
-    fn acceptable(&self, _parser: &Parser) -> bool {
-        if self.repeatable() {
-            return true;
+    /// The modes which have been entered, with `reset_to_node`, from
+    /// handlers with `HandlerContext::enter_mode`, or directly with
+    /// `ModeStack::push`.
+    pub fn modes(&self) -> &ModeStack {
+        &self.modes
+    }
+
+    /// The name of the innermost mode, for a prompt, or `None` before
+    /// any mode has been entered.
+    pub fn mode_name(&self) -> Option<String> {
+        self.modes.current().map(|m| m.name().to_string())
+    }
+
+    /// The root which lines are parsed from: that of the innermost
+    /// mode, or that of the tree when no mode has been entered.
+    fn active_root(&self) -> Arc<dyn Node> {
+        match self.modes.current() {
+            Some(mode) => mode.root().clone(),
+            None => self.root.clone(),
         }
-        unimplemented!()
-        // This should check nodes.contains, but then go on to check
-        // for a repeat marker and whether or not that's been seen.
     }
-}
 
-/// Define whether or not a node matches a token.
-pub trait Matches {
-    /// Does this node match the specified `token`?
-    fn matches(&self, parser: &Parser, token: Token) -> bool;
-}
+    /// The root of the tree of the innermost mode, which the nodes
+    /// given to `reset_to_node` are in: that of the innermost mode
+    /// with a tree of its own, or that of the tree.
+    fn tree_root(&self) -> Arc<dyn Node> {
+        let modes = self.modes.modes();
+        match modes.iter().rev().find(|m| m.root().is_root()) {
+            Some(mode) => mode.root().clone(),
+            None => self.root.clone(),
+        }
+    }
 
-impl Matches for Node {
-    /// By default, a node matches a `token` when the name of the
-    /// node starts with the `token`.
-    fn matches(&self, _parser: &Parser, token: Token) -> bool {
-        self.name().starts_with(token.text)
+    /// Leave the innermost mode, however it was entered, running the
+    /// `on_exit` hook of the command which entered it, and `reset` to
+    /// the root of the mode outside it.
+    pub fn exit_mode(&mut self) {
+        self.modes.pop();
+        self.reset();
+    }
+
+    /// Accept `token`, adding the other nodes which it matched to
+    /// the alternatives of `step`.
+    fn advance_token(&mut self,
+                     token: Token<'p>,
+                     step: &mut ParseStep)
+                     -> Result<(), ParseError<'p>> {
+        // We clone the current node so that it doesn't stay borrowed
+        // and break things when we try to modify it below.
+        let cn = self.current_node.clone();
+        let matches = cn.successors()
+                        .iter()
+                        .filter(|n| self.acceptable(&***n) && n.matches(token.text))
+                        .cloned()
+                        .collect::<Vec<_>>();
+        if matches.is_empty() {
+            if let Some(fallback) = self.fallback.clone().filter(|_| self.falls_back(&*cn)) {
+                return self.advance_fallback(fallback, token, step);
+            }
+            if let Some(error) = self.duplicate_parameter(&*cn, token) {
+                return Err(error);
+            }
+            if let Some(name) = cn.as_parameter_name() {
+                return Err(self.invalid_value(&**name.parameter(), token));
+            }
+            return self.advance_positional(token, step);
+        }
+        if matches.iter().all(|n| !enabled(&**n)) {
+            return Err(ParseError::DisabledCommand {
+                command: matches[0].name().to_string(),
+                token,
+                index: self.tokens.len(),
+            });
+        }
+        let matches = matches.into_iter()
+                             .filter(|n| enabled(&**n) && n.available())
+                             .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(ParseError::CommandUnavailable {
+                token,
+                index: self.tokens.len(),
+            });
+        }
+        let matches = matches.into_iter()
+                             .filter(|n| self.permitted(&**n))
+                             .collect::<Vec<_>>();
+        let reachable = matches.clone();
+        // A named node beats a positional command, which would match
+        // any token.
+        let matches = if matches.iter().all(|n| n.as_positional_command().is_some()) {
+            matches
+        } else {
+            matches.into_iter().filter(|n| n.as_positional_command().is_none()).collect()
+        };
+        let (matches, not_negatable): (Vec<_>, Vec<_>) =
+            matches.into_iter().partition(|n| self.negation_allows(&**n));
+        if matches.is_empty() {
+            if let Some(command) = not_negatable.first() {
+                return Err(ParseError::NotNegatable {
+                    command: command.name().to_string(),
+                    token,
+                    index: self.tokens.len(),
+                });
+            }
+        }
+        let candidates = matches.iter().map(|n| n.name().to_string()).collect::<Vec<_>>();
+        let matches = break_ties(token.text, matches);
+        match matches.len() {
+            1 => {
+                step.alternatives.extend(reachable.into_iter()
+                                                  .filter(|n| n != &matches[0] &&
+                                                              self.negation_allows(&**n)));
+                self.accept(&cn, &matches[0], token, candidates, step)
+            }
+            0 => {
+                Err(ParseError::PermissionDenied {
+                    token,
+                    index: self.tokens.len(),
+                })
+            }
+            _ => {
+                Err(ParseError::AmbiguousMatch {
+                    token,
+                    index: self.tokens.len(),
+                    candidates: matches.iter().map(|n| n.name().to_string()).collect(),
+                })
+            }
+        }
     }
-}
 
-impl Matches for ParameterNode {
-    /// Parameters can match any token by default.
-    fn matches(&self, _parser: &Parser, _token: Token) -> bool {
-        true
+    /// Accept `matching_node`, a successor of `cn`, for `token`.
+    /// When it was chosen among several `candidates`, a warning is
+    /// raised.
+    fn accept(&mut self,
+              cn: &Arc<dyn Node>,
+              matching_node: &Arc<dyn Node>,
+              token: Token<'p>,
+              candidates: Vec<String>,
+              step: &mut ParseStep)
+              -> Result<(), ParseError<'p>> {
+        self.check_exclusive(&**matching_node, token)?;
+        let sensitivity = matching_node.case_sensitivity();
+        if candidates.len() > 1 && !sensitivity.equals(&matching_node.name(), token.text) {
+            self.warn(ParseWarning::AmbiguousButResolved {
+                token: token.text.to_string(),
+                chosen: matching_node.name().to_string(),
+                candidates,
+            })?;
+        }
+        if let Some(message) = matching_node.as_command().and_then(|c| c.deprecated()) {
+            self.warn(ParseWarning::DeprecatedCommand {
+                command: matching_node.name().to_string(),
+                message: message.to_string(),
+            })?;
+        }
+        self.record_command(matching_node);
+        self.bind(&**matching_node, token.text, step);
+        // Parameters don't become the current node, so that they
+        // can be followed by anything that could follow the node
+        // which they came after, including themselves.
+        if matching_node.as_parameter().is_none() {
+            if matching_node.as_parameter_name().is_some() {
+                self.resume_node = Some(cn.clone());
+            }
+            self.current_node = match matching_node.as_subparser() {
+                // Continue from the root of the other tree.
+                Some(subparser) => subparser.sub_root().clone(),
+                None => matching_node.clone(),
+            };
+        } else if let Some(node) = self.resume_node.take() {
+            // The value of a named parameter has been given, so
+            // go back to the node which its name followed.
+            self.current_node = node;
+        }
+        self.nodes.push(matching_node.clone());
+        self.tokens.push(token);
+        Ok(())
     }
-}
 
-impl Matches for FlagParameterNode {
-    /// A flag parameter is just looking for the token matching
-    /// its name.
-    fn matches(&self, _parser: &Parser, token: Token) -> bool {
-        self.name().starts_with(token.text)
+    /// Let `node` record the value that `token` gives it, keeping
+    /// what had been recorded under its name in `step`, for `undo`.
+    fn bind(&mut self, node: &dyn Node, token: &str, step: &mut ParseStep) {
+        step.binding = Some((node.name().to_string(), self.parameters.get(&*node.name()).cloned()));
+        node.accept(token, &mut self.parameters);
+    }
+
+    /// Should a token which matched no successor of `node` go to
+    /// the fallback command? Only the first token of a line does.
+    fn falls_back(&self, node: &dyn Node) -> bool {
+        self.use_fallback && self.tokens.is_empty() && *node == *self.root
+    }
+
+    /// Accept the `fallback` command, giving it `token` as the value
+    /// of its first positional parameter.
+    fn advance_fallback(&mut self,
+                        fallback: Arc<dyn Node>,
+                        token: Token<'p>,
+                        step: &mut ParseStep)
+                        -> Result<(), ParseError<'p>> {
+        let (current_node, commands) = (self.current_node.clone(), self.commands.len());
+        self.record_command(&fallback);
+        self.current_node = fallback;
+        let result = self.advance_positional(token, step);
+        if result.is_err() {
+            self.current_node = current_node;
+            self.commands.truncate(commands);
+        }
+        result
     }
-}
 
-/// Define the behavior of a node when it is accepted by the parser.
-pub trait Accept {
-    /// Accept this node with the given `token` as data.
+    /// Bind a token that matched no successor to the next positional
+    /// parameter of the accepted command.
     ///
-    /// This is where parameters are stored, commands added.
-    fn accept<'p>(&self, parser: &mut Parser<'p>, token: Token);
-}
+    /// Required positional parameters are filled in declaration order
+    /// before any optional ones are considered. A repeatable positional
+    /// parameter, which must be the last one, takes all remaining tokens.
+    fn advance_positional(&mut self,
+                          token: Token<'p>,
+                          step: &mut ParseStep)
+                          -> Result<(), ParseError<'p>> {
+        let positional = self.positional_parameters();
+        let next = self.next_positional(&positional);
+        if next.is_none() {
+            if let Some(command) = self.command().filter(|_| self.ignore_unknown_parameters) {
+                return self.warn(ParseWarning::IgnoredUnknownParameter {
+                    command: command.name().to_string(),
+                    token: token.text.to_string(),
+                });
+            }
+        }
+        if positional.is_empty() {
+            return Err(self.no_matches(token));
+        }
+        match next {
+            Some(parameter) => {
+                if !parameter.matches(token.text) {
+                    return Err(self.invalid_value(&*parameter, token));
+                }
+                self.check_exclusive(&*parameter, token)?;
+                self.bind(&*parameter, token.text, step);
+                self.nodes.push(parameter);
+                self.tokens.push(token);
+                Ok(())
+            }
+            None => {
+                Err(ParseError::TooManyArguments {
+                    token,
+                    index: self.tokens.len(),
+                    expected: positional.iter().map(|p| p.name().to_string()).collect(),
+                })
+            }
+        }
+    }
 
-impl Accept for Node {
-    /// By default, nothing needs to happen for `accept`.
-    fn accept<'p>(&self, _parser: &mut Parser<'p>, _token: Token) {}
-}
+    /// The error for a `token` which nothing matched: `NoMatches`,
+    /// listing what could have been given instead, or `UnexpectedToken`
+    /// when nothing more can be given.
+    ///
+    /// Hidden nodes aren't listed, nor suggested for a mistyped token.
+    fn no_matches(&self, token: Token<'p>) -> ParseError<'p> {
+        let index = self.tokens.len();
+        let completions = self.complete(None);
+        if completions.is_empty() {
+            return ParseError::UnexpectedToken { token, index };
+        }
+        let names = completions.iter()
+                               .flat_map(|c| &c.options)
+                               .filter(|o| o.complete)
+                               .map(|o| o.option_string.as_str());
+        let suggestions = suggestions(token.text, names).into_iter().map(String::from).collect();
+        ParseError::NoMatches {
+            token,
+            index,
+            acceptable: completions.into_iter().map(|c| c.help_symbol).collect(),
+            suggestions,
+        }
+    }
+
+    /// The error for a `token` which matches a successor of `node`
+    /// that is a parameter, or the name of one, which isn't repeatable
+    /// and has already been given.
+    fn duplicate_parameter(&self, node: &dyn Node, token: Token<'p>) -> Option<ParseError<'p>> {
+        node.successors().iter().filter(|n| n.matches(token.text)).find_map(|n| {
+            let parameter: &dyn ParameterNode = match (n.as_parameter_name(), n.as_parameter()) {
+                (Some(name), _) => &**name.parameter(),
+                (None, Some(parameter)) => parameter,
+                (None, None) => return None,
+            };
+            if parameter.repeatable() || !self.parameters.contains_key(&*parameter.name()) {
+                return None;
+            }
+            Some(ParseError::DuplicateParameter {
+                parameter: parameter.name().to_string(),
+                token,
+                index: self.tokens.len(),
+            })
+        })
+    }
 
-impl Accept for Rc<CommandNode> {
-    /// Record this command.
-    fn accept<'p>(&self, parser: &mut Parser<'p>, _token: Token) {
-        if let Some(_) = self.handler() {
-            parser.commands.push(self.clone())
+    /// The error for a `token` which `parameter` doesn't accept as
+    /// its value.
+    fn invalid_value(&self, parameter: &dyn ParameterNode, token: Token<'p>) -> ParseError<'p> {
+        ParseError::InvalidParameterValue {
+            parameter: parameter.name().to_string(),
+            index: self.tokens.len(),
+            reason: format!("expected {}", parameter.help_symbol()).into_boxed_str(),
+            token,
         }
     }
-}
 
-impl Accept for ParameterNode {
-    /// Record this parameter value.
-    fn accept<'p>(&self, parser: &mut Parser<'p>, token: Token) {
-        if self.repeatable() {
-            unimplemented!();
-        } else {
-            parser.parameters.insert(self.name().clone(), token.text.to_string());
+    /// The positional parameters of the accepted command.
+    fn positional_parameters(&self) -> Vec<Arc<dyn ParameterNode>> {
+        match self.command() {
+            Some(command) => command.parameters().into_iter().filter(|p| p.positional()).collect(),
+            None => vec![],
         }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::nodes::*;
-    use super::*;
+    /// The parameter among `positional` which the next positional
+    /// value will be bound to, if any.
+    fn next_positional(&self,
+                       positional: &[Arc<dyn ParameterNode>])
+                       -> Option<Arc<dyn ParameterNode>> {
+        let unbound = |p: &&Arc<dyn ParameterNode>| self.acceptable(&***p);
+        positional.iter()
+                  .filter(|p| p.required())
+                  .find(&unbound)
+                  .or_else(|| positional.iter().filter(|p| !p.required()).find(&unbound))
+                  .cloned()
+    }
 
-    #[test]
-    #[should_panic]
-    fn verify_signals_no_command() {
-        let root = RootNode::new();
-        let parser = Parser::new(root);
-        match parser.verify() {
-            Err(VerifyError::NoCommandAccepted) => panic!(),
-            _ => {}
+    /// Record `node` as the command to be executed when it is a
+    /// command or wrapper with a handler.
+    fn record_command(&mut self, node: &Arc<dyn Node>) {
+        let has_handler = match (node.as_command(), node.as_wrapper()) {
+            (Some(command), _) => command.handler().is_some(),
+            (_, Some(wrapper)) => wrapper.handler().is_some(),
+            _ => false,
+        };
+        if has_handler {
+            self.commands.push(node.clone());
+        }
+    }
+
+    /// Can `node` be accepted in the current parser state?
+    ///
+    /// A repeatable node can be accepted until it reaches its
+    /// `max_repetitions` or its repeat marker has been seen. Other
+    /// nodes can be accepted once, unless they are disabled by a
+    /// limit of 0.
+    fn acceptable(&self, node: &dyn Node) -> bool {
+        let repeatable = match node.as_repeatable() {
+            Some(repeatable) => repeatable,
+            None => return self.repetitions(node) == 0,
+        };
+        if let Some(ref marker) = *repeatable.repeat_marker() {
+            if self.nodes.contains(marker) {
+                return false;
+            }
+        }
+        let max_repetitions = match (repeatable.repeatable(), repeatable.max_repetitions()) {
+            (true, max_repetitions) => max_repetitions,
+            (false, Some(0)) => Some(0),
+            (false, _) => Some(1),
+        };
+        match max_repetitions {
+            Some(max_repetitions) => repeatable.current_count(&self.nodes) < max_repetitions,
+            None => true,
+        }
+    }
+
+    /// The number of times that `node` has been accepted.
+    fn repetitions(&self, node: &dyn Node) -> usize {
+        self.nodes.iter().filter(|n| &***n == node).count()
+    }
+
+    /// The nodes which have been accepted, in order, including
+    /// hidden ones and the nodes of parameters.
+    pub fn accepted_nodes(&self) -> &[Arc<dyn Node>] {
+        &self.nodes
+    }
+
+    /// The line which has been accepted, with the full names of the
+    /// nodes and the values of the parameters, joined by spaces, as
+    /// for logging the commands which are executed.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use commands::parser::nodes::*;
+    /// use commands::parser::Parser;
+    ///
+    /// let name = SimpleParameterNode::new("name",
+    ///                                     None,
+    ///                                     false,
+    ///                                     PRIORITY_PARAMETER,
+    ///                                     vec![],
+    ///                                     false,
+    ///                                     None,
+    ///                                     true);
+    /// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+    /// show.add_successor(CommandNode::new("interfaces",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     Some(Box::new(|_: &HandlerContext| Ok(None))),
+    ///                                     vec![Arc::new(name)])).unwrap();
+    /// let root = RootNode::new();
+    /// root.add_successor(show).unwrap();
+    ///
+    /// let mut parser = Parser::new(root);
+    /// parser.parse("sh int eth0").unwrap();
+    /// assert_eq!(parser.command_path(), "show interfaces eth0");
+    /// ```
+    pub fn command_path(&self) -> String {
+        let words = self.nodes.iter().zip(&self.tokens).map(|(node, token)| {
+            let value = match node.as_parameter() {
+                Some(parameter) => parameter.takes_value(),
+                None => node.as_positional_command().is_some(),
+            };
+            if value { token.text.to_string() } else { node.name().to_string() }
+        });
+        words.collect::<Vec<_>>().join(" ")
+    }
+
+    /// Has a `NegationNode` been accepted, as in `no shutdown`?
+    pub fn negated(&self) -> bool {
+        self.nodes.iter().any(|n| n.as_negation().is_some())
+    }
+
+    /// Only commands which are `negatable`, or which lead to one,
+    /// may follow a `NegationNode`.
+    fn negation_allows(&self, node: &dyn Node) -> bool {
+        !self.negated() || node.as_command().is_none_or(negatable)
+    }
+
+    /// The command which has been accepted, if any. For nested
+    /// commands, like `show interface`, this is the last of them.
+    fn command(&self) -> Option<&CommandNode> {
+        self.commands.last().and_then(|c| c.as_command())
+    }
+
+    /// The wrapper which has been accepted in place of a command, if any.
+    fn wrapper(&self) -> Option<&WrapperNode> {
+        self.commands.first().and_then(|c| c.as_wrapper())
+    }
+
+    /// The values given for the parameters so far, by name.
+    ///
+    /// Flags of the accepted command are `Bool(true)` and repeatable
+    /// parameters are a `List` of their values. Any other parameter
+    /// is the `Str` that was given for it.
+    pub fn values(&self) -> ParameterValues {
+        let declared = self.command().map_or(vec![], |c| c.parameters());
+        let mut values = ParameterValues::new();
+        for (name, given) in &self.parameters {
+            let value = match declared.iter().find(|p| *p.name() == **name) {
+                Some(p) if !p.takes_value() => ParamValue::Bool(true),
+                Some(p) if p.repeatable() => ParamValue::List(strings(given)),
+                _ if given.len() > 1 => ParamValue::List(strings(given)),
+                _ => ParamValue::Str(given.last().cloned().unwrap_or_default()),
+            };
+            values.insert(name, value);
+        }
+        values
+    }
+
+    /// Execute the command that has been accepted by the parser.
+    ///
+    /// The parser is checked with `verify` first, so that handlers
+    /// can rely on their parameters being complete and consistent.
+    /// The handler of the last command which was accepted is run, and
+    /// the wall time that it takes is given to the command's
+    /// `post_hook`, if it has one, even when the handler times out.
+    /// Handlers are given a
+    /// `HandlerContext` with the accepted nodes and the `values` of
+    /// the parameters. The output of the handler is returned, and a
+    /// handler which fails gives `ExecuteError::Handler`, so that
+    /// failures of the command are told apart from parse errors, and
+    /// one which runs past the `timeout` of its command gives
+    /// `ExecuteError::Timeout`, or `ExecuteError::Panicked` when it
+    /// panics on its thread.
+    ///
+    /// When a `WrapperNode` was accepted first, its handler is run
+    /// instead and given the last node which was accepted after it,
+    /// or the node that it wraps if there is none. Otherwise, once the
+    /// handler has succeeded, the `ModeChange` of the command is made,
+    /// if it has one.
+    ///
+    /// The observers are told before the handler is run and once it
+    /// has returned.
+    pub fn execute(&self) -> Result<Option<String>, ExecuteError> {
+        self.execute_with(&mut ())
+    }
+
+    /// Run the handler of the accepted command on a thread of its own,
+    /// returning its result unless it takes longer than `timeout` or
+    /// panics.
+    fn execute_timed(&self,
+                     values: ParameterValues,
+                     timeout: Duration)
+                     -> Result<HandlerResult, ExecuteError> {
+        let command = self.commands.last().unwrap().clone();
+        let path = self.path_to(command.as_command().unwrap());
+        let nodes = self.nodes.clone();
+        let negated = self.negated();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let handler = command.as_command().and_then(|c| c.handler()).unwrap();
+            let context = HandlerContext::new(&*command, &nodes, &values).with_negation(negated);
+            // The parser has stopped waiting when this fails.
+            let _ = sender.send(handler(&context));
+        });
+        receiver.recv_timeout(timeout).map_err(|error| {
+            match error {
+                RecvTimeoutError::Timeout => {
+                    ExecuteError::Timeout {
+                        command: path,
+                        timeout,
+                    }
+                }
+                // The handler dropped the sender as it unwound.
+                RecvTimeoutError::Disconnected => ExecuteError::Panicked { command: path },
+            }
+        })
+    }
+
+    /// Execute the accepted command as with `execute`, giving its
+    /// handler access to `state`, the state of the application.
+    ///
+    /// Handlers get the state with `HandlerContext::state`, or are
+    /// given it directly when they are added with
+    /// `Command::state_handler`. This lets handlers be plain functions,
+    /// as in a tree built from a static table.
+    pub fn execute_with<S: Any>(&self, state: &mut S) -> Result<Option<String>, ExecuteError> {
+        self.executable()?;
+        let values = self.values();
+        let command = match (self.wrapper(), self.command()) {
+            (Some(wrapper), _) => wrapper.name().to_string(),
+            (None, Some(command)) => self.path_to(command),
+            (None, None) => return Ok(None),
+        };
+        self.notify(|o| o.on_execute(&command, &values));
+        let start = Instant::now();
+        let result = self.run_handler(values.clone(), state);
+        let elapsed = start.elapsed();
+        if result.is_ok() {
+            self.change_mode(&values);
+        }
+        self.notify(|o| o.on_result(&command, &result, elapsed));
+        result
+    }
+
+    /// The `ModeChange` of the accepted command, which executing it
+    /// makes, unless a wrapper was accepted to run in its place.
+    fn mode_change(&self) -> Option<&ModeChange> {
+        match self.wrapper() {
+            Some(_) => None,
+            None => self.command().and_then(|c| c.mode_change()),
+        }
+    }
+
+    /// Make the `mode_change` of the command which has been executed,
+    /// given the `values` of its parameters.
+    fn change_mode(&self, values: &ParameterValues) {
+        match self.mode_change() {
+            Some(ModeChange::Enter { name, root }) => {
+                let command = self.commands.last().unwrap().clone();
+                let context = HandlerContext::new(&*command, &self.nodes, values);
+                let mode = Mode::new(name, root.clone()).with_command(command.clone(), &context);
+                self.modes.enter(mode);
+            }
+            Some(ModeChange::Exit) => {
+                self.modes.pop();
+            }
+            Some(ModeChange::End) => {
+                self.modes.clear();
+            }
+            None => {}
+        }
+    }
+
+    /// Run the handler of the accepted command, or of the wrapper
+    /// which was accepted first, for `execute_with`.
+    fn run_handler<S: Any>(&self,
+                           values: ParameterValues,
+                           state: &mut S)
+                           -> Result<Option<String>, ExecuteError> {
+        if let Some(wrapper) = self.wrapper() {
+            let no_handler = || ExecuteError::NoHandler(wrapper.name().to_string());
+            let handler = wrapper.handler().ok_or_else(no_handler)?;
+            let target: &dyn Node = match self.nodes.last() {
+                Some(node) if node != &self.commands[0] => &**node,
+                _ => &**wrapper.root(),
+            };
+            let context = HandlerContext::new(target, &self.nodes, &values)
+                              .with_state(state)
+                              .with_negation(self.negated())
+                              .with_modes(&self.modes);
+            handler(&context).map_err(|error| {
+                ExecuteError::Handler {
+                    command: wrapper.name().to_string(),
+                    error,
+                }
+            })
+        } else if let Some(command) = self.command() {
+            let no_handler = || ExecuteError::NoHandler(command.name().to_string());
+            let handler = command.handler().ok_or_else(no_handler)?;
+            let start = Instant::now();
+            let result = match command.timeout() {
+                Some(timeout) => self.execute_timed(values, timeout),
+                None => {
+                    let context = HandlerContext::new(command, &self.nodes, &values)
+                                      .with_state(state)
+                                      .with_negation(self.negated())
+                                      .with_modes(&self.modes);
+                    Ok(handler(&context))
+                }
+            };
+            if let Some(post_hook) = command.post_hook() {
+                post_hook(&command.name(), start.elapsed());
+            }
+            result?.map_err(|error| {
+                ExecuteError::Handler {
+                    command: self.path_to(command),
+                    error,
+                }
+            })
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Could the accepted command be executed? This is everything that
+    /// `execute_with` checks before it runs a handler.
+    fn executable(&self) -> Result<(), ExecuteError> {
+        if self.commands.is_empty() {
+            let accepted = self.nodes
+                               .iter()
+                               .rev()
+                               .find(|n| n.as_command().is_some() || n.as_wrapper().is_some());
+            return Err(match accepted {
+                Some(node) => ExecuteError::NoHandler(node.name().to_string()),
+                None => ExecuteError::NoCommandAccepted,
+            });
+        }
+        if let Some(disabled) = self.commands.iter().find(|c| !enabled(&***c)) {
+            return Err(ExecuteError::DisabledCommand(disabled.name().to_string()));
+        }
+        self.verify()?;
+        let unhandled = match (self.wrapper(), self.command()) {
+            (Some(wrapper), _) if wrapper.handler().is_none() => Some(wrapper.name()),
+            (None, Some(command)) if command.handler().is_none() => Some(command.name()),
+            _ => None,
+        };
+        match unhandled {
+            Some(name) => Err(ExecuteError::NoHandler(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that `line` could be executed, without running any
+    /// handler: it is parsed from the root of the current mode, as
+    /// after `reset`, and what `execute` would check before running
+    /// the handler is checked. The errors are those that parsing and
+    /// executing the line would give. The accepted command is left in
+    /// the parser.
+    ///
+    /// As no handler runs, nothing that a handler does is seen by
+    /// the lines checked after it, like a mode that it enters. Only
+    /// `execute_lines` follows the `ModeChange`s of commands. The tree
+    /// is still walked, so the preconditions of commands, and the
+    /// `successors`, `matches` and `complete` of nodes which compute
+    /// them, are still called while checking. The observers aren't
+    /// told about anything.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    /// use commands::parser::Parser;
+    ///
+    /// let root = RootNode::new();
+    /// root.add_successor(CommandNode::new("show",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     None,
+    ///                                     vec![])).unwrap();
+    ///
+    /// let mut parser = Parser::new(root);
+    /// assert!(parser.check("show").is_err());
+    /// assert!(parser.check("reload").is_err());
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn check(&mut self, line: &'p str) -> Result<(), ScriptError<'p>> {
+        let root = self.active_root();
+        self.check_from(root, line)
+    }
+
+    /// `check`, parsing `line` from `root`.
+    #[allow(clippy::result_large_err)]
+    fn check_from(&mut self, root: Arc<dyn Node>, line: &'p str) -> Result<(), ScriptError<'p>> {
+        self.reset_to(root);
+        self.quietly(|parser| {
+            parser.parse(line).map_err(ScriptError::Parse)?;
+            parser.executable().map_err(ScriptError::Execute)
+        })
+    }
+
+    /// Check each of `lines`, as with `check`, and only when all of
+    /// them pass, execute them in turn, as for a configuration file
+    /// which shouldn't be applied in part. Blank lines are skipped.
+    ///
+    /// Each line is checked in the mode that the lines before it
+    /// would leave the parser in, when their commands have a
+    /// `ModeChange`, as in `interface eth0` and then `mtu 9000`.
+    /// Modes which handlers enter themselves can't be followed.
+    ///
+    /// When any line fails its check, nothing is executed, and each
+    /// of the lines which failed is returned with its error.
+    /// Otherwise, the outcomes are returned as from `execute_script`,
+    /// stopping at the first line which fails to execute.
+    pub fn execute_lines(&mut self,
+                         lines: &[&'p str])
+                         -> Result<Vec<ScriptOutcome<'p>>, Vec<LineError<'p>>> {
+        self.execute_lines_with(lines, &mut ())
+    }
+
+    /// Run `lines` as with `execute_lines`, giving each handler
+    /// access to `state` as with `execute_with`.
+    pub fn execute_lines_with<S: Any>(&mut self,
+                                      lines: &[&'p str],
+                                      state: &mut S)
+                                      -> Result<Vec<ScriptOutcome<'p>>, Vec<LineError<'p>>> {
+        let lines = lines.iter().enumerate().filter(|&(_, l)| !l.trim().is_empty());
+        let mut roots = self.modes.modes().iter().map(|m| m.root().clone()).collect::<Vec<_>>();
+        let mut errors = vec![];
+        for (index, &line) in lines.clone() {
+            let root = roots.last().cloned().unwrap_or_else(|| self.root.clone());
+            match self.check_from(root, line) {
+                Ok(()) => {
+                    match self.mode_change() {
+                        Some(ModeChange::Enter { root, .. }) => roots.push(root.clone()),
+                        Some(ModeChange::Exit) => {
+                            roots.pop();
+                        }
+                        Some(ModeChange::End) => roots.clear(),
+                        None => {}
+                    }
+                }
+                Err(error) => {
+                    errors.push(LineError {
+                        line: index + 1,
+                        error,
+                    })
+                }
+            }
+        }
+        if !errors.is_empty() {
+            self.reset();
+            return Err(errors);
+        }
+        let mut outcomes = vec![];
+        for (_, &line) in lines {
+            self.reset();
+            let result = match self.parse(line) {
+                Ok(()) => self.execute_with(&mut *state).map_err(ScriptError::Execute),
+                Err(error) => Err(ScriptError::Parse(error)),
+            };
+            let failed = result.is_err();
+            outcomes.push(ScriptOutcome {
+                text: line.trim(),
+                result,
+            });
+            if failed {
+                break;
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Parse and execute each of the commands in `script`, which are
+    /// separated by `;`, as in `interface eth0; mtu 9000`.
+    ///
+    /// Semicolons within double quotes don't separate commands, and
+    /// empty commands are skipped. Each command is parsed from the
+    /// root, and nothing more is run once one of them fails, so the
+    /// last outcome is the only one which can be an error.
+    pub fn execute_script(&mut self, script: &'p str) -> Vec<ScriptOutcome<'p>> {
+        self.execute_script_with(script, &mut ())
+    }
+
+    /// Run `script` as with `execute_script`, giving each handler
+    /// access to `state` as with `execute_with`.
+    pub fn execute_script_with<S: Any>(&mut self,
+                                       script: &'p str,
+                                       state: &mut S)
+                                       -> Vec<ScriptOutcome<'p>> {
+        let tokens = match tokenize(script) {
+            Ok(tokens) => tokens,
+            Err(error) => {
+                return vec![ScriptOutcome {
+                                text: script,
+                                result: Err(ScriptError::Parse(ParseError::Tokenize(error))),
+                            }]
+            }
+        };
+        let mut outcomes = vec![];
+        for segment in tokens.split(|t| t.token_type == TokenType::Word && t.text == ";") {
+            let mut words = segment.iter().filter(|t| t.token_type == TokenType::Word);
+            let (first, last) = match (words.clone().next(), words.next_back()) {
+                (Some(first), Some(last)) => (first, last),
+                _ => continue,
+            };
+            self.reset();
+            let result = match self.parse_tokens(segment.to_vec()) {
+                Ok(()) => self.execute_with(&mut *state).map_err(ScriptError::Execute),
+                Err(error) => Err(ScriptError::Parse(error)),
+            };
+            let failed = result.is_err();
+            outcomes.push(ScriptOutcome {
+                text: &script[first.span(script).start..last.span(script).end],
+                result,
+            });
+            if failed {
+                break;
+            }
+        }
+        outcomes
+    }
+
+    /// The parameters of the accepted command which still have to be
+    /// given before it can be executed: those which are `required` and
+    /// haven't been given, and those given fewer times than their
+    /// `min_repetitions`.
+    pub fn remaining_required(&self) -> Vec<Arc<dyn ParameterNode>> {
+        match self.command() {
+            Some(command) => {
+                command.parameters()
+                       .into_iter()
+                       .filter(|p| {
+                           let found = self.parameters.get(&*p.name()).map_or(0, |v| v.len());
+                           (p.required() && found == 0) || found < p.min_repetitions()
+                       })
+                       .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    /// Verify that the parser is in a valid state with
+    /// respect to having accepted a command and all
+    /// required parameters.
+    ///
+    /// The name of a named parameter must be followed by its value.
+    ///
+    /// Each parameter must be given at least as many times as its
+    /// `min_repetitions`. Its `max_repetitions` is already enforced
+    /// as the tokens are accepted.
+    ///
+    /// At least one member of each of the command's `RequiredGroup`s
+    /// must be given.
+    ///
+    /// Every parameter that was given must also have the
+    /// parameters that it `requires`. Since each given parameter
+    /// is checked, chains of requirements are enforced in full.
+    ///
+    /// All of the problems are reported at once, rather than only
+    /// the first, in the order in which the parameters that they are
+    /// about were declared, and each of them names the command path,
+    /// such as `show interface`. As this doesn't change the state of the
+    /// parser, it can be called after each token to report
+    /// problems as the user types.
+    ///
+    /// This must succeed before the command is executed, so that
+    /// handlers can rely on their parameters being consistent.
+    /// A wrapper, like `help`, doesn't need the parameters of the
+    /// command that it is given.
+    pub fn verify(&self) -> Result<(), Vec<VerifyError>> {
+        let result = self.verify_parameters();
+        if let Err(ref errors) = result {
+            self.notify(|o| o.on_verify_error(errors));
+        }
+        result
+    }
+
+    /// `verify`, without telling the observers.
+    fn verify_parameters(&self) -> Result<(), Vec<VerifyError>> {
+        if self.wrapper().is_some() {
+            return Ok(());
+        }
+        let command = match self.command() {
+            Some(command) => command,
+            None => return Err(vec![VerifyError::NoCommandAccepted]),
+        };
+        let path = self.path_to(command);
+        let parameters = command.parameters();
+        // Each error is kept with the position of the parameter that
+        // it is about, so that they can be reported in the order in
+        // which the parameters were declared.
+        let mut errors = vec![];
+        let missing = self.tokens.len();
+        let unvalued = self.current_node
+                           .as_parameter_name()
+                           .map(|n| n.parameter().name());
+        for (position, expected) in parameters.iter().enumerate() {
+            let found = self.parameters.get(&*expected.name()).map_or(0, |v| v.len());
+            if unvalued.as_deref() == Some(&expected.name()) {
+                errors.push((position,
+                             VerifyError::MissingValue {
+                                 command: path.clone(),
+                                 parameter: expected.name().to_string(),
+                                 index: missing - 1,
+                             }));
+            } else if expected.required() && found == 0 {
+                errors.push((position,
+                             VerifyError::MissingParameter {
+                                 command: path.clone(),
+                                 name: expected.name().to_string(),
+                                 index: missing,
+                             }));
+            } else if found < expected.min_repetitions() {
+                errors.push((position,
+                             VerifyError::InsufficientRepetitions {
+                                 command: path.clone(),
+                                 name: expected.name().to_string(),
+                                 min: expected.min_repetitions(),
+                                 found,
+                                 index: missing,
+                             }));
+            }
+            if found == 0 {
+                continue;
+            }
+            for required in expected.requires() {
+                if !self.parameters.contains_key(&required) {
+                    let index = self.given_at(&expected.name());
+                    errors.push((position,
+                                 VerifyError::MissingDependency {
+                                     command: path.clone(),
+                                     dependent: expected.name().to_string(),
+                                     required,
+                                     index,
+                                 }));
+                }
+            }
+            for conflict in expected.conflicts_with() {
+                if self.parameters.contains_key(conflict) {
+                    let index = self.given_at(&expected.name()).max(self.given_at(conflict));
+                    errors.push((position,
+                                 VerifyError::ConflictingParameters {
+                                     command: path.clone(),
+                                     parameter: expected.name().to_string(),
+                                     conflicts_with: conflict.clone(),
+                                     index,
+                                 }));
+                }
+            }
+        }
+        // A group is reported where its first member was declared.
+        for group in command.required_groups() {
+            let members = group.members();
+            if !members.iter().any(|m| self.parameters.contains_key(&*m.name())) {
+                let position = parameters.iter()
+                                         .position(|p| members.iter().any(|m| m.name() == p.name()))
+                                         .unwrap_or(parameters.len());
+                errors.push((position,
+                             VerifyError::RequiredGroupMissing {
+                                 command: path.clone(),
+                                 group: group.name().to_string(),
+                                 options: members.iter().map(|m| m.name().to_string()).collect(),
+                                 index: missing,
+                             }));
+            }
+        }
+        // The sort is stable, so the errors about each parameter stay
+        // in the order above.
+        errors.sort_by_key(|&(position, _)| position);
+        let errors = errors.into_iter().map(|(_, error)| error).collect::<Vec<_>>();
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// The index of the last token which gave the parameter called
+    /// `name`, or the number of tokens if none did.
+    fn given_at(&self, name: &str) -> usize {
+        self.nodes.iter().rposition(|n| &*n.name() == name).unwrap_or(self.tokens.len())
+    }
+
+    /// The bytes of `line`, the line which was parsed, of the
+    /// accepted token at `index`, or an empty range after the last
+    /// token for the index after it, as for the `index` of a
+    /// `VerifyError` about something missing.
+    pub fn token_span(&self, index: usize, line: &str) -> Range<usize> {
+        match self.tokens.get(index) {
+            Some(token) => token.span(line),
+            None => {
+                let end = self.tokens.last().map_or(0, |t| t.span(line).end);
+                end..end
+            }
+        }
+    }
+
+    /// The names of the commands which were accepted on the way to
+    /// `command`, joined by spaces, like `show interface`.
+    fn path_to(&self, command: &CommandNode) -> String {
+        let path = self.nodes
+                       .iter()
+                       .filter(|n| n.as_command().is_some())
+                       .map(|n| n.name())
+                       .collect::<Vec<_>>();
+        if path.last() == Some(&command.name()) {
+            path.join(" ")
+        } else {
+            command.name().to_string()
+        }
+    }
+
+    /// Find the exclusive group, if any, in which a member other than
+    /// the parameter named `name` has already been given.
+    fn exclusive_with_given(&self, name: &str) -> Option<Arc<MutuallyExclusiveGroup>> {
+        self.command().and_then(|command| {
+            command.exclusive_groups().into_iter().find(|group| {
+                group.contains(name) &&
+                group.members()
+                     .iter()
+                     .any(|m| &*m.name() != name && self.parameters.contains_key(&*m.name()))
+            })
+        })
+    }
+
+    /// Refuse to accept `node` for `token` when another member of one
+    /// of its exclusive groups has already been given.
+    fn check_exclusive(&self, node: &dyn Node, token: Token<'p>) -> Result<(), ParseError<'p>> {
+        if node.as_parameter().is_none() {
+            return Ok(());
+        }
+        match self.exclusive_with_given(&node.name()) {
+            Some(group) => {
+                let mut given = group.members()
+                                     .iter()
+                                     .map(|m| m.name().to_string())
+                                     .filter(|m| self.parameters.contains_key(m))
+                                     .collect::<Vec<_>>();
+                given.push(node.name().to_string());
+                Err(ParseError::MutuallyExclusive {
+                    group: group.name().to_string(),
+                    given: given.into_boxed_slice(),
+                    token,
+                    index: self.tokens.len(),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Does the parameter named `name` conflict with a parameter
+    /// which has already been given? Conflicts may be declared by
+    /// either of the two parameters.
+    fn conflicts_with_given(&self, name: &str) -> bool {
+        match self.command() {
+            Some(command) => {
+                command.parameters().iter().any(|p| {
+                    if &*p.name() == name {
+                        p.conflicts_with().iter().any(|c| self.parameters.contains_key(c))
+                    } else {
+                        self.parameters.contains_key(&*p.name()) &&
+                        p.conflicts_with().iter().any(|c| c == name)
+                    }
+                })
+            }
+            None => false,
+        }
+    }
+}
+
+/// The byte at which the unclosed double quote at the end of `text`
+/// opens, skipping quotes escaped with a backslash.
+fn opening_quote(text: &str) -> usize {
+    let mut opening = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' if !quoted => {
+                quoted = true;
+                opening = index;
+            }
+            '"' => quoted = false,
+            _ => {}
+        }
+    }
+    opening
+}
+
+/// The byte after the end of the token in `line` which starts at
+/// byte `start` and runs to at least `cursor`.
+fn token_end(line: &str, start: usize, cursor: usize) -> usize {
+    let quoted = line[start..].starts_with('"');
+    let mut escaped = false;
+    for (index, c) in line[cursor..].char_indices() {
+        let ends_word = c.is_whitespace() || c == '"' || c == ';' || c == '|';
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' if quoted && cursor + index > start => return cursor + index + 1,
+            _ if !quoted && ends_word => return cursor + index,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+/// Narrow down the nodes matching `token` to the one to accept.
+///
+/// A node whose name is exactly `token` is preferred, so that a
+/// command isn't ambiguous with longer commands that it is a prefix
+/// of. Otherwise, the nodes with the highest priority are kept. More
+/// than one node remaining means that the match is ambiguous.
+fn break_ties(token: &str, matches: Vec<Arc<dyn Node>>) -> Vec<Arc<dyn Node>> {
+    if matches.len() < 2 {
+        return matches;
+    }
+    let exact = matches.iter()
+                       .filter(|n| n.case_sensitivity().equals(&n.name(), token))
+                       .cloned()
+                       .collect::<Vec<_>>();
+    if exact.len() == 1 {
+        return exact;
+    }
+    let candidates = if exact.is_empty() { matches } else { exact };
+    let priority = candidates.iter().map(|n| n.priority()).max().unwrap_or(PRIORITY_MINIMUM);
+    candidates.into_iter().filter(|n| n.priority() == priority).collect()
+}
+
+/// Is `node` not a command which has been disabled?
+fn enabled(node: &dyn Node) -> bool {
+    node.as_command().is_none_or(|c| c.enabled())
+}
+
+/// Wrap each of `values` as a `ParamValue::Str`.
+fn strings(values: &[String]) -> Vec<ParamValue> {
+    values.iter().map(|v| ParamValue::Str(v.clone())).collect()
+}
+
+/// What a `Parser` has parsed, as saved by `Parser::snapshot`.
+///
+/// This holds the accepted nodes, tokens and values, but shares
+/// the nodes with the tree rather than copying them.
+#[derive(Clone)]
+pub struct ParserSnapshot<'p> {
+    current_node: Arc<dyn Node>,
+    nodes: Vec<Arc<dyn Node>>,
+    tokens: Vec<Token<'p>>,
+    commands: Vec<Arc<dyn Node>>,
+    parameters: HashMap<String, Vec<String>>,
+    resume_node: Option<Arc<dyn Node>>,
+    history: Vec<ParseStep>,
+    warnings: Vec<ParseWarning>,
+    leftover_tokens: Vec<Token<'p>>,
+}
+
+/// How a `Parser` handles tokens which match nothing once the
+/// command is complete, as set with `Parser::set_trailing_tokens`.
+///
+/// A token which matches nothing before the command is complete
+/// is an error either way.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum TrailingTokens {
+    /// The token is an error, as with `ParseError::NoMatches`.
+    #[default]
+    Strict,
+    /// The token and every token after it are kept in
+    /// `Parser::leftover_tokens`, raising a
+    /// `ParseWarning::IgnoredTrailingToken` for each, and the command
+    /// can be executed as if they hadn't been given, as for scripts
+    /// written for a newer version of an application.
+    Permissive,
+}
+
+/// The state of a `Parser` before it accepted a token.
+#[derive(Clone)]
+struct ParseStep {
+    current_node: Arc<dyn Node>,
+    resume_node: Option<Arc<dyn Node>>,
+    commands: usize,
+    warnings: usize,
+    /// The name under which the token was bound, and the values that
+    /// it had before, rather than a copy of all of the values.
+    binding: Option<(String, Option<Vec<String>>)>,
+    /// The other nodes which the token matched, which backtracking
+    /// may accept instead.
+    alternatives: Vec<Arc<dyn Node>>,
+}
+
+/// Errors that calling `parse` on the `Parser` can raise.
+///
+/// The `index` of a token counts the words which were accepted
+/// before it, from 0, so that the token can be pointed out to the
+/// user. Its `location` gives its position within the line.
+#[derive(Clone,Debug)]
+pub enum ParseError<'t> {
+    /// The parser is in an invalid state.
+    InvalidState,
+    /// There were no matches for the token.
+    NoMatches {
+        /// The token which matched nothing.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+        /// The help symbols of what could have been given instead.
+        acceptable: Box<[String]>,
+        /// The names among those which are close to the token, as
+        /// found by `util::suggestions`.
+        suggestions: Box<[String]>,
+    },
+    /// There was more than 1 possible match for the token.
+    AmbiguousMatch {
+        /// The token which matched several nodes.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+        /// The names of the nodes which it matched.
+        candidates: Vec<String>,
+    },
+    /// The token isn't a valid value for a parameter.
+    InvalidParameterValue {
+        /// The name of the parameter.
+        parameter: String,
+        /// The token which was given as its value.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+        /// Why the value isn't valid, boxed to keep `ParseError` small.
+        reason: Box<str>,
+    },
+    /// The token came after a command which can't be followed by
+    /// anything more.
+    UnexpectedToken {
+        /// The token which was given.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The token gave a parameter which isn't repeatable for a
+    /// second time.
+    DuplicateParameter {
+        /// The name of the parameter.
+        parameter: String,
+        /// The token which gave it again.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The token matched a command which isn't `negatable` after
+    /// a `NegationNode`.
+    NotNegatable {
+        /// The name of the command.
+        command: String,
+        /// The token which matched it.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The token matched a command whose precondition is not satisfied.
+    CommandUnavailable {
+        /// The token which matched it.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The token only matched commands which have been disabled
+    /// with `CommandNode::disable`.
+    DisabledCommand {
+        /// The name of the command.
+        command: String,
+        /// The token which matched it.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The token matched a node which the user isn't permitted to use.
+    PermissionDenied {
+        /// The token which matched it.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The token was left over after all of the command's positional
+    /// parameters had been given.
+    TooManyArguments {
+        /// The token which was left over.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+        /// The names of the positional parameters of the command.
+        expected: Vec<String>,
+    },
+    /// The token came after as many tokens as `set_max_tokens`
+    /// allows.
+    TooManyTokens {
+        /// The token which was given.
+        token: Token<'t>,
+        /// The index of the token, counting those left over in
+        /// `TrailingTokens::Permissive` mode.
+        index: usize,
+        /// The number of tokens which are allowed.
+        max: usize,
+    },
+    /// More than one member of a `MutuallyExclusiveGroup` was given.
+    MutuallyExclusive {
+        /// The name of the group.
+        group: String,
+        /// The members of the group which were given, in the order
+        /// in which they were given, boxed to keep `ParseError` small.
+        given: Box<[String]>,
+        /// The token which gave the last of them.
+        token: Token<'t>,
+        /// The index of the token.
+        index: usize,
+    },
+    /// The node given to `reset_to_node` isn't in the parser's tree.
+    NodeNotInTree(String),
+    /// The line given to `parse` couldn't be tokenized.
+    Tokenize(TokenizerError),
+    /// A warning was raised while the parser was in strict mode.
+    StrictModeViolation(ParseWarning),
+}
+
+impl<'t> ParseError<'t> {
+    /// The token which the error is about, if there is one.
+    pub fn token(&self) -> Option<Token<'t>> {
+        match *self {
+            ParseError::NoMatches { token, .. } |
+            ParseError::AmbiguousMatch { token, .. } |
+            ParseError::InvalidParameterValue { token, .. } |
+            ParseError::UnexpectedToken { token, .. } |
+            ParseError::DuplicateParameter { token, .. } |
+            ParseError::NotNegatable { token, .. } |
+            ParseError::CommandUnavailable { token, .. } |
+            ParseError::DisabledCommand { token, .. } |
+            ParseError::PermissionDenied { token, .. } |
+            ParseError::TooManyArguments { token, .. } |
+            ParseError::TooManyTokens { token, .. } |
+            ParseError::MutuallyExclusive { token, .. } => Some(token),
+            ParseError::InvalidState |
+            ParseError::NodeNotInTree(_) |
+            ParseError::Tokenize(_) |
+            ParseError::StrictModeViolation(_) => None,
+        }
+    }
+
+    /// The zero-based index of the `token` among the words of the
+    /// line, if the error is about a token.
+    pub fn index(&self) -> Option<usize> {
+        match *self {
+            ParseError::NoMatches { index, .. } |
+            ParseError::AmbiguousMatch { index, .. } |
+            ParseError::InvalidParameterValue { index, .. } |
+            ParseError::UnexpectedToken { index, .. } |
+            ParseError::DuplicateParameter { index, .. } |
+            ParseError::NotNegatable { index, .. } |
+            ParseError::CommandUnavailable { index, .. } |
+            ParseError::DisabledCommand { index, .. } |
+            ParseError::PermissionDenied { index, .. } |
+            ParseError::TooManyArguments { index, .. } |
+            ParseError::TooManyTokens { index, .. } |
+            ParseError::MutuallyExclusive { index, .. } => Some(index),
+            ParseError::InvalidState |
+            ParseError::NodeNotInTree(_) |
+            ParseError::Tokenize(_) |
+            ParseError::StrictModeViolation(_) => None,
+        }
+    }
+
+    /// The bytes of `line`, the line which was parsed, of the
+    /// `token`, as with `Token::span`.
+    pub fn span(&self, line: &str) -> Option<Range<usize>> {
+        self.token().map(|t| t.span(line))
+    }
+
+    /// Point at the `token` within `line`, the line which was parsed,
+    /// as `util::caret_diagnostic` does.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    /// use commands::parser::Parser;
+    ///
+    /// let root = RootNode::new();
+    /// root.add_successor(CommandNode::new("show",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     None,
+    ///                                     vec![])).unwrap();
+    ///
+    /// let line = "show version";
+    /// let error = Parser::new(root).parse(line).unwrap_err();
+    /// assert_eq!(error.index(), Some(1));
+    /// assert_eq!(error.diagnostic(line).unwrap(), "show version\n     ^~~~~~~");
+    /// ```
+    pub fn diagnostic(&self, line: &str) -> Option<String> {
+        self.span(line).map(|span| caret_diagnostic(line, span))
+    }
+}
+
+impl<'t> Error for ParseError<'t> {}
+
+impl<'t> fmt::Display for ParseError<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ParseError::TooManyArguments { ref token, ref expected, .. } => {
+                let expected = expected.iter()
+                                       .map(|e| format!("<{}>", e))
+                                       .collect::<Vec<_>>();
+                write!(f,
+                       "too many arguments at '{}', expected: {}",
+                       token.text,
+                       expected.join(" "))
+            }
+            ParseError::TooManyTokens { ref token, max, .. } => {
+                write!(f, "too many tokens at '{}', at most {} may be given", token.text, max)
+            }
+            ParseError::MutuallyExclusive { ref group, ref given, .. } => {
+                write!(f,
+                       "only one of the parameters in '{}' may be given, but got: {}",
+                       group,
+                       given.join(", "))
+            }
+            ParseError::NodeNotInTree(ref name) => {
+                write!(f, "node '{}' is not in the command tree", name)
+            }
+            ParseError::Tokenize(ref error) => error.fmt(f),
+            ParseError::StrictModeViolation(ref warning) => {
+                write!(f, "{} (not allowed in strict mode)", warning)
+            }
+            ParseError::NoMatches { ref token, ref suggestions, .. }
+                if !suggestions.is_empty() => {
+                let suggestions = suggestions.iter()
+                                             .map(|s| format!("'{}'", s))
+                                             .collect::<Vec<_>>();
+                write!(f,
+                       "'{}' doesn't match anything, did you mean {}?",
+                       token.text,
+                       suggestions.join(" or "))
+            }
+            ParseError::NoMatches { ref token, ref acceptable, .. } => {
+                write!(f,
+                       "'{}' doesn't match anything, expected one of: {}",
+                       token.text,
+                       acceptable.join(", "))
+            }
+            ParseError::AmbiguousMatch { ref token, ref candidates, .. } => {
+                write!(f, "'{}' is ambiguous: {}", token.text, candidates.join(", "))
+            }
+            ParseError::InvalidParameterValue { ref parameter, ref token, ref reason, .. } => {
+                write!(f,
+                       "'{}' is not a valid value for '{}': {}",
+                       token.text,
+                       parameter,
+                       reason)
+            }
+            ParseError::DuplicateParameter { ref parameter, .. } => {
+                write!(f, "parameter '{}' may only be given once", parameter)
+            }
+            ParseError::NotNegatable { ref command, .. } => {
+                write!(f, "command '{}' can't be negated", command)
+            }
+            ParseError::UnexpectedToken { ref token, .. } => {
+                write!(f, "unexpected '{}'", token.text)
+            }
+            ParseError::InvalidState => "Invalid state.".fmt(f),
+            ParseError::CommandUnavailable { .. } => "Command unavailable.".fmt(f),
+            ParseError::DisabledCommand { ref command, .. } => {
+                write!(f, "command '{}' is disabled", command)
+            }
+            ParseError::PermissionDenied { .. } => "Permission denied.".fmt(f),
+        }
+    }
+}
+
+/// Problems found while parsing which don't prevent the command
+/// from being used.
+#[derive(Clone,Debug,PartialEq)]
+pub enum ParseWarning {
+    /// A command which has been deprecated was matched.
+    DeprecatedCommand {
+        /// The name of the command.
+        command: String,
+        /// The deprecation message of the command.
+        message: String,
+    },
+    /// A token which matched nothing after a command was skipped,
+    /// as the parser was told to `set_ignore_unknown_parameters`.
+    IgnoredUnknownParameter {
+        /// The name of the command.
+        command: String,
+        /// The text of the token which was skipped.
+        token: String,
+    },
+    /// A token matched several nodes and one of them was chosen,
+    /// having a higher priority than the others.
+    AmbiguousButResolved {
+        /// The text of the token.
+        token: String,
+        /// The name of the node which was chosen.
+        chosen: String,
+        /// The names of all of the nodes which the token matched.
+        candidates: Vec<String>,
+    },
+    /// A token after a complete command was left over, as the parser
+    /// was told to allow `TrailingTokens::Permissive`.
+    IgnoredTrailingToken {
+        /// The text of the token.
+        token: String,
+    },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ParseWarning::DeprecatedCommand { ref command, ref message } => {
+                write!(f, "command '{}' is deprecated: {}", command, message)
+            }
+            ParseWarning::IgnoredUnknownParameter { ref command, ref token } => {
+                write!(f, "ignored unknown parameter '{}' of '{}'", token, command)
+            }
+            ParseWarning::AmbiguousButResolved { ref token, ref chosen, ref candidates } => {
+                write!(f,
+                       "'{}' is ambiguous, chose '{}' from: {}",
+                       token,
+                       chosen,
+                       candidates.join(", "))
+            }
+            ParseWarning::IgnoredTrailingToken { ref token } => {
+                write!(f, "ignored trailing token '{}'", token)
+            }
+        }
+    }
+}
+
+/// Errors that calling `verify` on the `Parser` can raise.
+///
+/// Other than `NoCommandAccepted`, each of them names the path of
+/// the command which was accepted, like `show interface`.
+#[derive(Clone,Debug)]
+pub enum VerifyError {
+    /// No command has been accepted by the parser.
+    NoCommandAccepted,
+    /// A required parameter is missing.
+    MissingParameter {
+        /// The command which was accepted.
+        command: String,
+        /// The name of the parameter.
+        name: String,
+        /// The number of tokens which were given, as the index of
+        /// the one which is missing.
+        index: usize,
+    },
+    /// The name of a named parameter was the last token, without
+    /// its value.
+    MissingValue {
+        /// The command which was accepted.
+        command: String,
+        /// The name of the parameter.
+        parameter: String,
+        /// The index of the name of the parameter.
+        index: usize,
+    },
+    /// A parameter was given fewer times than its `min_repetitions`.
+    InsufficientRepetitions {
+        /// The command which was accepted.
+        command: String,
+        /// The name of the parameter.
+        name: String,
+        /// The number of times that it must be given.
+        min: usize,
+        /// The number of times that it was given.
+        found: usize,
+        /// The number of tokens which were given, as the index of
+        /// the one which is missing.
+        index: usize,
+    },
+    /// No member of a `RequiredGroup` was given.
+    RequiredGroupMissing {
+        /// The command which was accepted.
+        command: String,
+        /// The name of the group.
+        group: String,
+        /// The parameters of which one must be given.
+        options: Vec<String>,
+        /// The number of tokens which were given, as the index of
+        /// the one which is missing.
+        index: usize,
+    },
+    /// A parameter was given without a parameter that it requires.
+    MissingDependency {
+        /// The command which was accepted.
+        command: String,
+        /// The parameter which was given.
+        dependent: String,
+        /// The parameter which it requires.
+        required: String,
+        /// The index of the token which gave `dependent`.
+        index: usize,
+    },
+    /// Two parameters were given which may not be used together.
+    ConflictingParameters {
+        /// The command which was accepted.
+        command: String,
+        /// The parameter declaring the conflict.
+        parameter: String,
+        /// The parameter which it conflicts with.
+        conflicts_with: String,
+        /// The index of the token which gave the later of the two.
+        index: usize,
+    },
+}
+
+impl VerifyError {
+    /// The zero-based index of the token which the error is about,
+    /// or of the one which is missing.
+    pub fn index(&self) -> Option<usize> {
+        match *self {
+            VerifyError::NoCommandAccepted => None,
+            VerifyError::MissingParameter { index, .. } |
+            VerifyError::MissingValue { index, .. } |
+            VerifyError::InsufficientRepetitions { index, .. } |
+            VerifyError::RequiredGroupMissing { index, .. } |
+            VerifyError::MissingDependency { index, .. } |
+            VerifyError::ConflictingParameters { index, .. } => Some(index),
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            VerifyError::MissingParameter { ref command, ref name, .. } => {
+                write!(f, "{}: required parameter '{}' is missing", command, name)
+            }
+            VerifyError::MissingValue { ref command, ref parameter, .. } => {
+                write!(f, "{}: parameter '{}' is missing its value", command, parameter)
+            }
+            VerifyError::InsufficientRepetitions { ref command, ref name, min, found, .. } => {
+                write!(f,
+                       "{}: parameter '{}' must be given at least {} times, but was given {}",
+                       command,
+                       name,
+                       min,
+                       found)
+            }
+            VerifyError::RequiredGroupMissing { ref command, ref group, ref options, .. } => {
+                write!(f,
+                       "{}: one of the parameters in '{}' must be given: {}",
+                       command,
+                       group,
+                       options.join(", "))
+            }
+            VerifyError::MissingDependency { ref command, ref dependent, ref required, .. } => {
+                write!(f,
+                       "{}: parameter '{}' requires '{}' to also be specified",
+                       command,
+                       dependent,
+                       required)
+            }
+            VerifyError::ConflictingParameters { ref command,
+                                                 ref parameter,
+                                                 ref conflicts_with,
+                                                 .. } => {
+                write!(f,
+                       "{}: parameter '{}' conflicts with '{}'",
+                       command,
+                       parameter,
+                       conflicts_with)
+            }
+            VerifyError::NoCommandAccepted => {
+                "No command has been accepted by the parser.".fmt(f)
+            }
+        }
+    }
+}
+
+/// The outcome of one of the commands run by `execute_script`.
+#[derive(Debug)]
+pub struct ScriptOutcome<'t> {
+    /// The text of the command.
+    pub text: &'t str,
+    /// The output of the command, or why it failed.
+    pub result: Result<Option<String>, ScriptError<'t>>,
+}
+
+/// A line given to `Parser::execute_lines` which failed its check.
+#[derive(Debug)]
+pub struct LineError<'t> {
+    /// The number of the line, counting from 1.
+    pub line: usize,
+    /// Why it failed.
+    pub error: ScriptError<'t>,
+}
+
+impl<'t> fmt::Display for LineError<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+/// Why a command run by `execute_script` failed.
+#[derive(Debug)]
+pub enum ScriptError<'t> {
+    /// The command couldn't be parsed.
+    Parse(ParseError<'t>),
+    /// The command couldn't be executed.
+    Execute(ExecuteError),
+}
+
+impl<'t> Error for ScriptError<'t> {}
+
+impl<'t> fmt::Display for ScriptError<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ScriptError::Parse(ref error) => error.fmt(f),
+            ScriptError::Execute(ref error) => error.fmt(f),
+        }
+    }
+}
+
+/// Errors that calling `execute` on the `Parser` can raise.
+#[derive(Clone,Debug)]
+pub enum ExecuteError {
+    /// No command has been accepted by the parser.
+    NoCommandAccepted,
+    /// The command which was accepted has no handler to run.
+    NoHandler(String),
+    /// The command was disabled with `CommandNode::disable` after it
+    /// had been accepted.
+    DisabledCommand(String),
+    /// The parser failed to `verify`, for each of these reasons.
+    Verify(Vec<VerifyError>),
+    /// The handler of the command failed.
+    Handler {
+        /// The path of the command, like `show interfaces`.
+        command: String,
+        /// Why the handler failed.
+        error: HandlerError,
+    },
+    /// The handler of the command ran for longer than its timeout.
+    Timeout {
+        /// The path of the command.
+        command: String,
+        /// The timeout of the command.
+        timeout: Duration,
+    },
+    /// The handler of a command with a timeout panicked on its
+    /// thread.
+    Panicked {
+        /// The path of the command.
+        command: String,
+    },
+}
+
+impl From<Vec<VerifyError>> for ExecuteError {
+    fn from(errors: Vec<VerifyError>) -> Self {
+        ExecuteError::Verify(errors)
+    }
+}
+
+impl Error for ExecuteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ExecuteError::Handler { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ExecuteError::NoCommandAccepted => {
+                "No command has been accepted by the parser.".fmt(f)
+            }
+            ExecuteError::NoHandler(ref command) => {
+                write!(f, "command '{}' has nothing to execute", command)
+            }
+            ExecuteError::DisabledCommand(ref command) => {
+                write!(f, "command '{}' is disabled", command)
+            }
+            ExecuteError::Verify(ref errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                errors.join("; ").fmt(f)
+            }
+            ExecuteError::Handler { ref command, ref error } => {
+                write!(f, "{}: {}", command, error)
+            }
+            ExecuteError::Timeout { ref command, timeout } => {
+                write!(f, "{}: timed out after {:?}", command, timeout)
+            }
+            ExecuteError::Panicked { ref command } => write!(f, "{}: handler panicked", command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::ops::Range;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use super::builder::{CommandTree, Command, Parameter, ParameterKind};
+    use super::nodes::*;
+    use super::*;
+    use tokenizer::tokenize;
+
+    #[test]
+    #[should_panic]
+    fn verify_signals_no_command() {
+        let root = RootNode::new();
+        let parser = Parser::new(root);
+        if let [VerifyError::NoCommandAccepted] = parser.verify().unwrap_err()[..] { panic!() }
+    }
+
+    #[test]
+    fn precondition_gates_completion_and_parsing() {
+        let staged = Arc::new(AtomicBool::new(false));
+        let root = RootNode::new();
+        let mut commit = CommandNode::new("commit", PRIORITY_DEFAULT, false, None, None, vec![]);
+        {
+            let staged = staged.clone();
+            Arc::get_mut(&mut commit)
+                .unwrap()
+                .set_precondition(Box::new(move || staged.load(Ordering::SeqCst)));
+        }
+        root.add_successor(commit).unwrap();
+        root.add_successor(CommandNode::new("status",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+
+        let parser = Parser::new(root.clone());
+        let completions = parser.complete(None);
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].help_symbol, "status");
+
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("commit") {
+            Err(ParseError::CommandUnavailable { token, .. }) => assert_eq!(token.text, "commit"),
+            _ => panic!(),
+        }
+
+        staged.store(true, Ordering::SeqCst);
+
+        let parser = Parser::new(root.clone());
+        assert_eq!(parser.complete(None).len(), 2);
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("commit").is_ok());
+    }
+
+    fn parameter(name: &str, requires: &[&str]) -> Arc<dyn ParameterNode> {
+        constrained_parameter(name, requires, &[])
+    }
+
+    fn constrained_parameter(name: &str,
+                             requires: &[&str],
+                             conflicts: &[&str])
+                             -> Arc<dyn ParameterNode> {
+        let mut node = FlagParameterNode::with_options(name,
+                                                       None,
+                                                       false,
+                                                       PRIORITY_PARAMETER,
+                                                       vec![],
+                                                       false,
+                                                       None,
+                                                       false);
+        for required in requires {
+            node.add_requirement(required).unwrap();
+        }
+        for conflict in conflicts {
+            node.add_conflict(conflict);
+        }
+        Arc::new(node)
+    }
+
+    fn given(parser: &mut Parser, names: &[&str]) {
+        for name in names {
+            parser.parameters.insert(name.to_string(), vec!["x".to_string()]);
+        }
+    }
+
+    #[test]
+    fn verify_checks_requirements() {
+        let connect = CommandNode::new("connect",
+                                       PRIORITY_DEFAULT,
+                                       false,
+                                       None,
+                                       None,
+                                       vec![parameter("key", &["cert"]),
+                                            parameter("cert", &["ca"]),
+                                            parameter("ca", &[]),
+                                            parameter("verbose", &[])]);
+        let root = RootNode::new();
+
+        let mut parser = Parser::new(root.clone());
+        parser.commands.push(connect.clone());
+        given(&mut parser, &["verbose"]);
+        assert!(parser.verify().is_ok());
+
+        let mut parser = Parser::new(root.clone());
+        parser.commands.push(connect.clone());
+        given(&mut parser, &["key"]);
+        match &parser.verify().unwrap_err()[..] {
+            [e @ VerifyError::MissingDependency { .. }] => {
+                assert_eq!(e.to_string(),
+                           "connect: parameter 'key' requires 'cert' to also be specified");
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root.clone());
+        parser.commands.push(connect.clone());
+        given(&mut parser, &["key", "cert"]);
+        match &parser.verify().unwrap_err()[..] {
+            [VerifyError::MissingDependency { dependent, required, .. }] => {
+                assert_eq!(dependent, "cert");
+                assert_eq!(required, "ca");
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        parser.commands.push(connect);
+        given(&mut parser, &["key", "cert", "ca"]);
+        assert!(parser.verify().is_ok());
+    }
+
+    fn noop_handler(_context: &HandlerContext) -> HandlerResult {
+        Ok(None)
+    }
+
+    #[test]
+    fn execute_runs_post_hook() {
+        let log = Arc::new(Mutex::new(Vec::<(String, Duration)>::new()));
+        let mut show = CommandNode::new("show",
+                                        PRIORITY_DEFAULT,
+                                        false,
+                                        None,
+                                        Some(Box::new(noop_handler)),
+                                        vec![]);
+        {
+            let log = log.clone();
+            Arc::get_mut(&mut show)
+                .unwrap()
+                .set_post_hook(Box::new(move |name, elapsed| {
+                    log.lock().unwrap().push((name.to_string(), elapsed))
+                }));
+        }
+
+        let mut parser = Parser::new(RootNode::new());
+        parser.commands.push(show);
+        assert!(parser.execute().is_ok());
+        assert!(parser.execute().is_ok());
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].0, "show");
+        assert_eq!(log[1].0, "show");
+    }
+
+    #[test]
+    fn post_hook_needs_handler() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let mut show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        {
+            let ran = ran.clone();
+            Arc::get_mut(&mut show).unwrap().set_post_hook(Box::new(move |_, _| {
+                ran.store(true, Ordering::SeqCst)
+            }));
+        }
+
+        let mut parser = Parser::new(RootNode::new());
+        parser.commands.push(show);
+        match parser.execute() {
+            Err(ExecuteError::NoHandler(command)) => assert_eq!(command, "show"),
+            _ => panic!(),
+        }
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn verify_checks_conflicts() {
+        let output = CommandNode::new("output",
+                                      PRIORITY_DEFAULT,
+                                      false,
+                                      None,
+                                      None,
+                                      vec![constrained_parameter("json", &[], &["quiet"]),
+                                           constrained_parameter("quiet", &[], &[]),
+                                           constrained_parameter("verbose", &[], &[])]);
+        let root = RootNode::new();
+
+        let mut parser = Parser::new(root.clone());
+        parser.commands.push(output.clone());
+        given(&mut parser, &["json", "verbose"]);
+        assert!(parser.verify().is_ok());
+
+        let mut parser = Parser::new(root);
+        parser.commands.push(output);
+        given(&mut parser, &["quiet", "json"]);
+        match &parser.verify().unwrap_err()[..] {
+            [e @ VerifyError::ConflictingParameters { .. }] => {
+                assert_eq!(e.to_string(), "output: parameter 'json' conflicts with 'quiet'");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn conflicts_are_not_completed() {
+        let output = CommandNode::new("output",
+                                      PRIORITY_DEFAULT,
+                                      false,
+                                      None,
+                                      None,
+                                      vec![constrained_parameter("json", &[], &["quiet"]),
+                                           constrained_parameter("quiet", &[], &[]),
+                                           constrained_parameter("verbose", &[], &[])]);
+
+        let mut parser = Parser::new(RootNode::new());
+        parser.current_node = output.clone();
+        parser.commands.push(output.clone());
+        assert_eq!(parser.complete(None).len(), 3);
+
+        given(&mut parser, &["json"]);
+        let completions = parser.complete(None);
+        let names = completions.iter()
+                               .map(|c| c.options[0].option_string.as_str())
+                               .collect::<Vec<_>>();
+        assert_eq!(names, vec!["json", "verbose"]);
+
+        let mut parser = Parser::new(RootNode::new());
+        parser.current_node = output.clone();
+        parser.commands.push(output);
+        given(&mut parser, &["quiet"]);
+        let completions = parser.complete(None);
+        let names = completions.iter()
+                               .map(|c| c.options[0].option_string.as_str())
+                               .collect::<Vec<_>>();
+        assert_eq!(names, vec!["quiet", "verbose"]);
+        assert!(parser.ambiguous_completions("j").is_empty());
+    }
+
+    fn positional_node(name: &str, required: bool, repeatable: bool) -> SimpleParameterNode {
+        SimpleParameterNode::new(name,
+                                 None,
+                                 false,
+                                 PRIORITY_PARAMETER,
+                                 vec![],
+                                 repeatable,
+                                 None,
+                                 required)
+    }
+
+    fn positional(name: &str, required: bool, repeatable: bool) -> Arc<dyn ParameterNode> {
+        Arc::new(positional_node(name, required, repeatable))
+    }
+
+    fn copy_tree(parameters: Vec<Arc<dyn ParameterNode>>) -> Arc<RootNode> {
+        let root = RootNode::new();
+        root.add_successor(CommandNode::new("copy",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(noop_handler)),
+                                            parameters)).unwrap();
+        root
+    }
+
+    #[test]
+    fn positional_parameters_in_order() {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy a b").is_ok());
+        assert!(parser.verify().is_ok());
+        assert_eq!(parser.parameters["src"], vec!["a"]);
+        assert_eq!(parser.parameters["dst"], vec!["b"]);
+    }
+
+    #[test]
+    fn positional_parameter_missing() {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy a").is_ok());
+        match &parser.verify().unwrap_err()[..] {
+            [VerifyError::MissingParameter { name, .. }] => assert_eq!(name, "dst"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn too_many_positional_parameters() {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+        let mut parser = Parser::new(root);
+        match parser.parse("copy a b c") {
+            Err(e @ ParseError::TooManyArguments { .. }) => {
+                assert_eq!(e.to_string(), "too many arguments at 'c', expected: <src> <dst>");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn required_positional_parameters_first() {
+        let root = copy_tree(vec![positional("mode", false, false),
+                                  positional("src", true, false),
+                                  positional("dst", true, false)]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy a b c").is_ok());
+        assert_eq!(parser.parameters["src"], vec!["a"]);
+        assert_eq!(parser.parameters["dst"], vec!["b"]);
+        assert_eq!(parser.parameters["mode"], vec!["c"]);
+    }
+
+    #[test]
+    fn repeatable_positional_parameter() {
+        let root = copy_tree(vec![positional("dst", true, false), positional("src", true, true)]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy d a b c").is_ok());
+        assert_eq!(parser.parameters["dst"], vec!["d"]);
+        assert_eq!(parser.parameters["src"], vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn keywords_structure_commands() {
+        let root = RootNode::new();
+        let rename = CommandNode::new("rename",
+                                      PRIORITY_DEFAULT,
+                                      false,
+                                      None,
+                                      Some(Box::new(noop_handler)),
+                                      vec![positional("old", true, false),
+                                           positional("new", true, false)]);
+        rename.add_successor(KeywordNode::new("to", PRIORITY_DEFAULT, false, None)).unwrap();
+        root.add_successor(rename).unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("rename a").is_ok());
+        assert_eq!(completion_names(&parser), vec!["to", "<new>"]);
+
+        assert!(parser.parse("to b").is_ok());
+        assert!(parser.verify().is_ok());
+        assert_eq!(parser.commands.len(), 1);
+        assert_eq!(&*parser.command().unwrap().name(), "rename");
+        assert_eq!(parser.parameters["old"], vec!["a"]);
+        assert_eq!(parser.parameters["new"], vec!["b"]);
+    }
+
+    #[test]
+    fn wrapper_completes_wrapped_commands() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").finalize());
+        tree.command(Command::new("reload").finalize());
+        tree.command(Command::new("help").wraps("".to_string()).finalize());
+        let root = tree.finalize().unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("help").is_ok());
+        let completions = parser.complete(None);
+        let names = completions.iter().map(|c| c.help_symbol.as_str()).collect::<Vec<_>>();
+        // The wrapper has already been accepted, so it isn't offered again.
+        assert_eq!(names, vec!["show", "reload"]);
+    }
+
+    #[test]
+    fn named_parameter_value() {
+        let count = Arc::new(NamedParameterNode::with_options("count",
+                                                              None,
+                                                              false,
+                                                              PRIORITY_PARAMETER,
+                                                              vec![],
+                                                              false,
+                                                              None,
+                                                              true));
+        let ping = CommandNode::new("ping",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    None,
+                                    Some(Box::new(noop_handler)),
+                                    vec![]);
+        ping.add_parameter_name(ParameterNameNode::new("count",
+                                                       false,
+                                                       None,
+                                                       None,
+                                                       None,
+                                                       count)).unwrap();
+        let root = RootNode::new();
+        root.add_successor(ping).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("ping").is_ok());
+        match &parser.verify().unwrap_err()[..] {
+            [VerifyError::MissingParameter { name, .. }] => assert_eq!(name, "count"),
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("ping count 5").is_ok());
+        assert!(parser.verify().is_ok());
+        assert_eq!(parser.parameters["count"], vec!["5"]);
+    }
+
+    #[test]
+    fn named_parameter_without_value() {
+        let count = Arc::new(NamedParameterNode::with_options("count",
+                                                              None,
+                                                              false,
+                                                              PRIORITY_PARAMETER,
+                                                              vec![],
+                                                              true,
+                                                              None,
+                                                              true));
+        let ping = CommandNode::new("ping",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    None,
+                                    Some(Box::new(noop_handler)),
+                                    vec![]);
+        ping.add_parameter_name(ParameterNameNode::new("count",
+                                                       true,
+                                                       None,
+                                                       None,
+                                                       None,
+                                                       count)).unwrap();
+        let root = RootNode::new();
+        root.add_successor(ping).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("ping count").is_ok());
+        // Only the value can follow the name.
+        assert_eq!(completion_names(&parser), vec!["<count>..."]);
+        match &parser.verify().unwrap_err()[..] {
+            [error @ VerifyError::MissingValue { parameter, .. }] => {
+                assert_eq!(parameter, "count");
+                assert_eq!(error.to_string(), "ping: parameter 'count' is missing its value");
+            }
+            _ => panic!(),
+        }
+
+        // A repeatable name is offered again once its value is given.
+        assert!(parser.parse("5").is_ok());
+        assert!(parser.verify().is_ok());
+        assert_eq!(completion_names(&parser), vec!["count <count>..."]);
+        assert!(parser.parse("count 6").is_ok());
+        assert_eq!(parser.parameters["count"], vec!["5", "6"]);
+    }
+
+    thread_local! {
+        static HANDLED: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    }
+
+    fn recording_handler(context: &HandlerContext) -> HandlerResult {
+        HANDLED.with(|h| h.borrow_mut().push(context.node().name().to_string()));
+        Ok(None)
+    }
+
+    #[test]
+    fn wrapper_handler_receives_target() {
+        let root = RootNode::new();
+        root.add_successor(CommandNode::new("show",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(noop_handler)),
+                                            vec![])).unwrap();
+        root.add_successor(WrapperNode::with_options("help",
+                                                     PRIORITY_DEFAULT,
+                                                     false,
+                                                     None,
+                                                     Some(Box::new(recording_handler)),
+                                                     root.clone())).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("help show").is_ok());
+        assert!(parser.execute().is_ok());
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("help").is_ok());
+        assert!(parser.execute().is_ok());
+        HANDLED.with(|h| assert_eq!(*h.borrow(), vec!["show", "__root__"]));
+    }
+
+    #[test]
+    fn subparser_switches_trees() {
+        let route = RootNode::with_name("route");
+        let add = CommandNode::new("add",
+                                   PRIORITY_DEFAULT,
+                                   false,
+                                   None,
+                                   Some(Box::new(recording_handler)),
+                                   vec![]);
+        add.add_parameter(positional("destination", true, false)).unwrap();
+        route.add_successor(add).unwrap();
+        route.add_successor(CommandNode::new("show",
+                                             PRIORITY_DEFAULT,
+                                             false,
+                                             None,
+                                             Some(Box::new(noop_handler)),
+                                             vec![])).unwrap();
+
+        let root = RootNode::new();
+        root.add_successor(SubparserNode::new("route", route)).unwrap();
+        root.add_successor(CommandNode::new("reload",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(noop_handler)),
+                                            vec![])).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("route").is_ok());
+        assert!(parser.current_node.is_root());
+        assert_eq!(&*parser.current_node.name(), "route");
+        let completions = parser.complete(None);
+        let names = completions.iter().map(|c| c.help_symbol.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["add", "show"]);
+        assert!(parser.parse("add 10.0.0.0/8").is_ok());
+        assert!(parser.verify().is_ok());
+        assert!(parser.execute().is_ok());
+        HANDLED.with(|h| assert_eq!(*h.borrow(), vec!["add"]));
+
+        let mut parser = Parser::new(root);
+        match parser.parse("route reload") {
+            Err(error @ ParseError::NoMatches { .. }) => {
+                assert_eq!(error.to_string(),
+                           "'reload' doesn't match anything, expected one of: add, show")
+            }
+            _ => panic!(),
+        }
+    }
+
+    fn limited(flag: bool, max_repetitions: Option<usize>) -> Arc<RootNode> {
+        let mut parameter = if flag {
+            Box::new(FlagParameterNode::with_options("verbose",
+                                                     None,
+                                                     false,
+                                                     PRIORITY_PARAMETER,
+                                                     vec![],
+                                                     true,
+                                                     None,
+                                                     false)) as Box<dyn ParameterNode>
+        } else {
+            Box::new(positional_node("file", false, true)) as Box<dyn ParameterNode>
+        };
+        parameter.set_max_repetitions(max_repetitions);
+        copy_tree(vec![Arc::from(parameter)])
+    }
+
+    fn accepts(flag: bool, max_repetitions: Option<usize>, line: &str) -> bool {
+        let mut parser = Parser::new(limited(flag, max_repetitions));
+        parser.parse(line).is_ok()
+    }
+
+    #[test]
+    fn max_repetitions() {
+        assert!(accepts(true, None, "copy verbose verbose verbose"));
+        assert!(accepts(false, None, "copy a b c"));
+
+        assert!(!accepts(true, Some(0), "copy verbose"));
+        assert!(!accepts(false, Some(0), "copy a"));
+
+        assert!(accepts(true, Some(1), "copy verbose"));
+        assert!(!accepts(true, Some(1), "copy verbose verbose"));
+        assert!(accepts(false, Some(1), "copy a"));
+        assert!(!accepts(false, Some(1), "copy a b"));
+
+        assert!(accepts(true, Some(2), "copy verbose verbose"));
+        assert!(!accepts(true, Some(2), "copy verbose verbose verbose"));
+        assert!(accepts(false, Some(2), "copy a b"));
+        assert!(!accepts(false, Some(2), "copy a b c"));
+    }
+
+    #[test]
+    fn max_repetitions_not_completed() {
+        let mut parser = Parser::new(limited(true, Some(2)));
+        assert!(parser.parse("copy verbose").is_ok());
+        assert_eq!(parser.complete(None).len(), 1);
+        assert!(parser.parse("verbose").is_ok());
+        assert!(parser.complete(None).is_empty());
+    }
+
+    #[test]
+    fn min_repetitions() {
+        let mut file = positional_node("file", true, true);
+        file.set_min_repetitions(2);
+        let root = copy_tree(vec![Arc::new(file)]);
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy a").is_ok());
+        match &parser.verify().unwrap_err()[..] {
+            [error @ VerifyError::InsufficientRepetitions { .. }] => {
+                assert_eq!(error.to_string(),
+                           "copy: parameter 'file' must be given at least 2 times, but was given 1")
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy a b").is_ok());
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn min_repetitions_default() {
+        assert_eq!(positional_node("file", true, true).min_repetitions(), 1);
+        assert_eq!(positional_node("file", false, true).min_repetitions(), 0);
+    }
+
+    fn exclusive_tree() -> Arc<RootNode> {
+        let verbose = Arc::new(FlagParameterNode::with_options("verbose",
+                                                               None,
+                                                               false,
+                                                               PRIORITY_PARAMETER,
+                                                               vec![],
+                                                               true,
+                                                               None,
+                                                               false));
+        let quiet = parameter("quiet", &[]);
+        let root = copy_tree(vec![verbose.clone(), quiet.clone(), parameter("force", &[])]);
+        let verbosity = MutuallyExclusiveGroup::new("verbosity");
+        constraints::add_to_group(&verbosity, verbose);
+        constraints::add_to_group(&verbosity, quiet);
+        root.successors()[0].as_command().unwrap().add_exclusive_group(verbosity).unwrap();
+        root
+    }
+
+    #[test]
+    fn mutually_exclusive_parameters() {
+        let mut parser = Parser::new(exclusive_tree());
+        match parser.parse("copy verbose force quiet") {
+            Err(error @ ParseError::MutuallyExclusive { .. }) => {
+                assert_eq!(error.to_string(),
+                           "only one of the parameters in 'verbosity' may be given, but got: \
+                            verbose, quiet")
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(exclusive_tree());
+        assert!(parser.parse("copy verbose verbose force").is_ok());
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn mutually_exclusive_not_completed() {
+        let mut parser = Parser::new(exclusive_tree());
+        assert!(parser.parse("copy quiet").is_ok());
+        let completions = parser.complete(None);
+        let names = completions.iter().map(|c| c.options[0].option_string.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), vec!["force"]);
+        assert!(parser.ambiguous_completions("verb").is_empty());
+        assert_eq!(parser.ambiguous_completions("force").len(), 1);
+    }
+
+    fn required_group_tree() -> Arc<RootNode> {
+        let recursive = parameter("recursive", &[]);
+        let force = parameter("force", &[]);
+        let root = copy_tree(vec![recursive.clone(), force.clone(), parameter("quiet", &[])]);
+        let mode = constraints::RequiredGroup::new("mode");
+        constraints::add_to_required_group(&mode, recursive);
+        constraints::add_to_required_group(&mode, force);
+        root.successors()[0].as_command().unwrap().add_required_group(mode).unwrap();
+        root
+    }
+
+    #[test]
+    fn required_group_all_missing() {
+        let mut parser = Parser::new(required_group_tree());
+        assert!(parser.parse("copy quiet").is_ok());
+        match &parser.verify().unwrap_err()[..] {
+            [error @ VerifyError::RequiredGroupMissing { .. }] => {
+                assert_eq!(error.to_string(),
+                           "copy: one of the parameters in 'mode' must be given: recursive, force")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn required_group_one_given() {
+        let mut parser = Parser::new(required_group_tree());
+        assert!(parser.parse("copy force").is_ok());
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn required_group_all_given() {
+        let mut parser = Parser::new(required_group_tree());
+        assert!(parser.parse("copy recursive force quiet").is_ok());
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn shared_tree_across_threads() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("ping")
+                         .parameter(Parameter::new("host").kind(ParameterKind::Simple).finalize())
+                         .parameter(Parameter::new("count").kind(ParameterKind::Named).finalize())
+                         .handler(noop_handler)
+                         .finalize());
+        tree.command(Command::new("show").finalize());
+        let root = tree.finalize().unwrap();
+
+        let sessions = (0..8).map(|i| {
+            let root = root.clone();
+            thread::spawn(move || {
+                let input = format!("ping host{} count {}", i, i);
+                let mut parser = Parser::new(root);
+                assert!(parser.parse(&input).is_ok());
+                assert!(parser.verify().is_ok());
+                (parser.parameters["host"].clone(), parser.parameters["count"].clone())
+            })
+        });
+        for (i, session) in sessions.collect::<Vec<_>>().into_iter().enumerate() {
+            let (host, count) = session.join().unwrap();
+            assert_eq!(host, vec![format!("host{}", i)]);
+            assert_eq!(count, vec![i.to_string()]);
+        }
+    }
+
+    fn named_parameter(name: &str) -> Arc<dyn ParameterNode> {
+        Arc::new(NamedParameterNode::with_options(name,
+                                                  None,
+                                                  false,
+                                                  PRIORITY_PARAMETER,
+                                                  vec![],
+                                                  false,
+                                                  None,
+                                                  false))
+    }
+
+    #[test]
+    fn consecutive_named_parameters() {
+        let root = copy_tree(vec![named_parameter("format"), named_parameter("file")]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy format json file out.json").is_ok());
+        assert_eq!(parser.parameters["format"], vec!["json"]);
+        assert_eq!(parser.parameters["file"], vec!["out.json"]);
+    }
+
+    fn dependency_tree() -> Arc<RootNode> {
+        let format = named_parameter("format");
+        let file = named_parameter("file");
+        constraints::add_dependency(&format, &file).unwrap();
+        copy_tree(vec![format, file])
+    }
+
+    #[test]
+    fn dependency_missing() {
+        let mut parser = Parser::new(dependency_tree());
+        assert!(parser.parse("copy format json").is_ok());
+        match &parser.verify().unwrap_err()[..] {
+            [VerifyError::MissingDependency { command, dependent, required, .. }] => {
+                assert_eq!(command, "copy");
+                assert_eq!(dependent, "format");
+                assert_eq!(required, "file");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn verify_reports_every_problem() {
+        let format = named_parameter("format");
+        let file = named_parameter("file");
+        constraints::add_dependency(&format, &file).unwrap();
+        let root = copy_tree(vec![positional("src", true, false),
+                                  positional("dst", true, false),
+                                  format,
+                                  file]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy a format json").is_ok());
+        let errors = parser.verify()
+                           .unwrap_err()
+                           .iter()
+                           .map(|e| e.to_string())
+                           .collect::<Vec<_>>();
+        assert_eq!(errors,
+                   vec!["copy: required parameter 'dst' is missing",
+                        "copy: parameter 'format' requires 'file' to also be specified"]);
+
+        // Verifying doesn't change the state of the parser.
+        assert_eq!(parser.verify().unwrap_err().len(), 2);
+        assert!(parser.advance(tokenize("b").unwrap()[0]).is_ok());
+        assert_eq!(parser.verify().unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn dependency_satisfied() {
+        let mut parser = Parser::new(dependency_tree());
+        assert!(parser.parse("copy format json file out.json").is_ok());
+        assert!(parser.verify().is_ok());
+
+        // The required parameter may be given by itself.
+        let mut parser = Parser::new(dependency_tree());
+        assert!(parser.parse("copy file out.json").is_ok());
+        assert!(parser.verify().is_ok());
+    }
+
+    fn undo_tree() -> Arc<RootNode> {
+        let show = CommandNode::new("show",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    None,
+                                    Some(Box::new(noop_handler)),
+                                    vec![parameter("verbose", &[])]);
+        show.add_successor(CommandNode::new("interface",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(noop_handler)),
+                                            vec![])).unwrap();
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+        root
+    }
+
+    fn completion_names(parser: &Parser) -> Vec<String> {
+        parser.complete(None).iter().map(|c| c.help_symbol.clone()).collect()
+    }
+
+    #[test]
+    fn undo_single_token() {
+        let mut parser = Parser::new(undo_tree());
+        assert!(parser.parse("show verbose").is_ok());
+        assert!(parser.parameters.contains_key("verbose"));
+
+        assert!(parser.undo());
+        assert!(!parser.parameters.contains_key("verbose"));
+        assert_eq!(parser.nodes.len(), 1);
+        assert_eq!(parser.tokens.len(), 1);
+        assert_eq!(completion_names(&parser), vec!["interface", "[verbose]"]);
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn undo_two_tokens() {
+        let mut parser = Parser::new(undo_tree());
+        assert!(parser.parse("show interface").is_ok());
+        assert_eq!(&*parser.command().unwrap().name(), "interface");
+
+        assert!(parser.undo());
+        assert_eq!(&*parser.command().unwrap().name(), "show");
+        assert!(parser.undo());
+        assert!(parser.nodes.is_empty());
+        assert!(parser.tokens.is_empty());
+        assert!(parser.command().is_none());
+        assert_eq!(completion_names(&parser), vec!["show"]);
+
+        // The parser can carry on from the restored state.
+        assert!(parser.parse("show verbose").is_ok());
+        assert!(parser.parameters.contains_key("verbose"));
+    }
+
+    #[test]
+    fn undo_repeated_values() {
+        let mut parser = Parser::new(copy_tree(vec![positional("source", true, true)]));
+        assert!(parser.parse("copy a b c").is_ok());
+        assert_eq!(parser.parameters["source"], vec!["a", "b", "c"]);
+
+        assert!(parser.undo());
+        assert_eq!(parser.parameters["source"], vec!["a", "b"]);
+        assert!(parser.undo());
+        assert!(parser.undo());
+        assert!(parser.parameters.is_empty());
+        assert!(parser.parse("d").is_ok());
+        assert_eq!(parser.parameters["source"], vec!["d"]);
+    }
+
+    #[test]
+    fn undo_past_beginning() {
+        let mut parser = Parser::new(undo_tree());
+        assert!(!parser.undo());
+
+        assert!(parser.parse("show").is_ok());
+        assert!(parser.undo());
+        assert!(!parser.undo());
+        assert!(parser.nodes.is_empty());
+    }
+
+    fn command_node(name: &str, priority: i32, hidden: bool) -> Arc<CommandNode> {
+        CommandNode::new(name, priority, hidden, None, Some(Box::new(noop_handler)), vec![])
+    }
+
+    #[test]
+    fn advance_token_by_token() {
+        let interfaces = CommandNode::new("interfaces",
+                                          PRIORITY_DEFAULT,
+                                          false,
+                                          None,
+                                          Some(Box::new(noop_handler)),
+                                          vec![positional("name", true, false)]);
+        let show = command_node("show", PRIORITY_DEFAULT, false);
+        show.add_successor(interfaces).unwrap();
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+
+        let mut parser = Parser::new(root);
+        for token in tokenize("show interfaces eth0").unwrap() {
+            if token.token_type == TokenType::Word {
+                assert!(parser.advance(token).is_ok());
+            }
+        }
+        assert_eq!(&*parser.current_node.name(), "interfaces");
+        assert_eq!(parser.parameters["name"], vec!["eth0"]);
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn parse_whole_lines() {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy \"My Documents\" backup\\ 1").is_ok());
+        assert_eq!(parser.parameters["src"], vec!["\"My Documents\""]);
+        assert_eq!(parser.parameters["dst"], vec!["backup\\ 1"]);
+
+        // Blank lines select nothing.
+        for line in &["", "   "] {
+            let mut parser = Parser::new(root.clone());
+            assert!(parser.parse(line).is_ok());
+            assert!(parser.nodes.is_empty());
+            assert!(parser.command().is_none());
+        }
+
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("copy a b c") {
+            Err(ParseError::TooManyArguments { token, .. }) => {
+                assert_eq!(token.text, "c");
+                assert_eq!(token.location.start.char, 9);
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        match parser.parse("copy \"a") {
+            Err(ParseError::Tokenize(TokenizerError::UnclosedDoubleQuoteAtEndOfInput)) => {}
+            _ => panic!(),
+        }
+        assert!(parser.nodes.is_empty());
+    }
+
+    #[test]
+    fn advance_breaks_ties() {
+        let root = RootNode::new();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("showall", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("reload", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("reset", PRIORITY_DEFAULT + 1, false)).unwrap();
+        root.add_successor(command_node("status", PRIORITY_DEFAULT, true)).unwrap();
+        root.add_successor(command_node("stop", PRIORITY_DEFAULT, false)).unwrap();
+
+        let parse = |text| -> Result<String, ()> {
+            let mut parser = Parser::new(root.clone());
+            match parser.parse(text) {
+                Ok(()) => Ok(parser.nodes[0].name().to_string()),
+                Err(ParseError::AmbiguousMatch { .. }) => Err(()),
+                Err(_) => panic!(),
+            }
+        };
+        // An exact name wins over longer names.
+        assert_eq!(parse("show"), Ok("show".to_string()));
+        assert_eq!(parse("showa"), Ok("showall".to_string()));
+        // Then the highest priority does.
+        assert_eq!(parse("re"), Ok("reset".to_string()));
+        // Hidden nodes can still be matched.
+        assert_eq!(parse("stat"), Ok("status".to_string()));
+        assert_eq!(parse("st"), Err(()));
+    }
+
+    #[test]
+    fn deprecated_commands_warn() {
+        let mut sh = CommandNode::new("sh",
+                                      PRIORITY_DEFAULT,
+                                      false,
+                                      Some("Show things.".to_string()),
+                                      Some(Box::new(noop_handler)),
+                                      vec![]);
+        Arc::get_mut(&mut sh).unwrap().set_deprecated("use 'show' instead");
+        let root = RootNode::new();
+        root.add_successor(sh).unwrap();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+
+        let parser = Parser::new(root.clone());
+        let completions = parser.complete(None);
+        assert_eq!(completions[0].help_text.as_deref(), Some("Show things. [DEPRECATED]"));
+        assert_eq!(completions[1].help_text, None);
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("show").is_ok());
+        assert!(parser.warnings().is_empty());
+
+        parser.reset();
+        assert!(parser.parse("sh").is_ok());
+        assert_eq!(parser.warnings(),
+                   &[ParseWarning::DeprecatedCommand {
+                         command: "sh".to_string(),
+                         message: "use 'show' instead".to_string(),
+                     }]);
+        assert_eq!(parser.warnings()[0].to_string(),
+                   "command 'sh' is deprecated: use 'show' instead");
+        assert!(parser.execute().is_ok());
+
+        assert!(parser.undo());
+        assert!(parser.warnings().is_empty());
+    }
+
+    fn keyword_tree() -> Arc<RootNode> {
+        let show = command_node("show", PRIORITY_DEFAULT, false);
+        show.add_successor(KeywordNode::new("interfaces", PRIORITY_DEFAULT, false, None)).unwrap();
+        show.add_successor(KeywordNode::new("internal", PRIORITY_DEFAULT, false, None)).unwrap();
+        show.add_successor(Arc::new(positional_node("name", true, false))).unwrap();
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+        root
+    }
+
+    #[test]
+    fn keyword_wins_over_parameter() {
+        let mut parser = Parser::new(keyword_tree());
+        assert!(parser.parse("show eth0").is_ok());
+        assert_eq!(parser.parameters["name"], vec!["eth0"]);
+
+        let mut parser = Parser::new(keyword_tree());
+        assert!(parser.parse("show interfaces").is_ok());
+        assert_eq!(&*parser.nodes[1].name(), "interfaces");
+        assert!(parser.parameters.is_empty());
+
+        let mut parser = Parser::new(keyword_tree());
+        assert!(parser.parse("show interf").is_ok());
+        assert_eq!(&*parser.nodes[1].name(), "interfaces");
+
+        // The parameter is offered after the keywords.
+        let mut parser = Parser::new(keyword_tree());
+        assert!(parser.parse("show").is_ok());
+        assert_eq!(completion_names(&parser), vec!["interfaces", "internal", "<name>"]);
+    }
+
+    #[test]
+    fn same_priority_keywords_are_ambiguous() {
+        let mut parser = Parser::new(keyword_tree());
+        match parser.parse("show inte") {
+            Err(error @ ParseError::AmbiguousMatch { .. }) => {
+                assert_eq!(error.to_string(), "'inte' is ambiguous: interfaces, internal")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn resolved_ambiguity_warns() {
+        let mut parser = Parser::new(keyword_tree());
+        assert!(parser.parse("show interfaces").is_ok());
+        assert!(parser.warnings().is_empty());
+
+        let mut parser = Parser::new(keyword_tree());
+        assert!(parser.parse("show interf").is_ok());
+        assert_eq!(parser.warnings(),
+                   &[ParseWarning::AmbiguousButResolved {
+                         token: "interf".to_string(),
+                         chosen: "interfaces".to_string(),
+                         candidates: vec!["interfaces".to_string(), "name".to_string()],
+                     }]);
+        assert_eq!(parser.take_warnings().len(), 1);
+        assert!(parser.warnings().is_empty());
+        // Warnings which have been taken aren't brought back by `undo`.
+        assert!(parser.undo());
+        assert!(parser.warnings().is_empty());
+    }
+
+    #[test]
+    fn unknown_parameters_can_be_ignored() {
+        let root = RootNode::new();
+        root.add_successor(command_node("reload", PRIORITY_DEFAULT, false)).unwrap();
+        let mut parser = Parser::new(root.clone());
+        assert!(matches!(parser.parse("reload now"), Err(ParseError::UnexpectedToken { .. })));
+
+        let mut parser = Parser::new(root);
+        parser.set_ignore_unknown_parameters(true);
+        assert!(matches!(parser.parse("now"), Err(ParseError::NoMatches { .. })));
+        parser.reset();
+        assert!(parser.parse("reload now").is_ok());
+        assert_eq!(parser.tokens.len(), 1);
+        assert_eq!(parser.warnings(),
+                   &[ParseWarning::IgnoredUnknownParameter {
+                         command: "reload".to_string(),
+                         token: "now".to_string(),
+                     }]);
+        assert_eq!(parser.warnings()[0].to_string(), "ignored unknown parameter 'now' of 'reload'");
+        assert!(parser.execute().is_ok());
+
+        // Tokens go to positional parameters until they have all been
+        // given, and only then are they ignored.
+        let mut parser = Parser::new(copy_tree(vec![positional("source", true, false)]));
+        parser.set_ignore_unknown_parameters(true);
+        assert!(parser.parse("copy a b").is_ok());
+        assert_eq!(parser.parameters["source"], vec!["a"]);
+        assert_eq!(parser.warnings(),
+                   &[ParseWarning::IgnoredUnknownParameter {
+                         command: "copy".to_string(),
+                         token: "b".to_string(),
+                     }]);
+    }
+
+    #[test]
+    fn strict_mode() {
+        let mut sh = command_node("sh", PRIORITY_DEFAULT, false);
+        Arc::get_mut(&mut sh).unwrap().set_deprecated("use 'show' instead");
+        let root = RootNode::new();
+        root.add_successor(sh).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        parser.set_strict_mode(false);
+        assert!(parser.parse("sh").is_ok());
+        assert_eq!(parser.warnings().len(), 1);
+
+        let mut parser = Parser::new(root);
+        parser.set_strict_mode(true);
+        match parser.parse("sh") {
+            Err(error @ ParseError::StrictModeViolation(_)) => {
+                assert_eq!(error.to_string(),
+                           "command 'sh' is deprecated: use 'show' instead \
+                            (not allowed in strict mode)")
+            }
+            _ => panic!(),
+        }
+        assert!(parser.nodes.is_empty());
+        assert!(parser.commands.is_empty());
+        assert!(parser.warnings().is_empty());
+
+        let mut parser = Parser::new(keyword_tree());
+        parser.set_strict_mode(true);
+        assert!(parser.parse("show interfaces").is_ok());
+        parser.reset();
+        match parser.parse("show interf") {
+            Err(ParseError::StrictModeViolation(ParseWarning::AmbiguousButResolved { .. })) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn ambiguous_completions_keep_ties() {
+        let root = RootNode::new();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("shutdown", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("shell", PRIORITY_DEFAULT - 1, false)).unwrap();
+        root.add_successor(command_node("shadow", PRIORITY_DEFAULT, true)).unwrap();
+        root.add_successor(command_node("reload", PRIORITY_DEFAULT, false)).unwrap();
+        let parser = Parser::new(root);
+
+        let names = |token| -> Vec<String> {
+            parser.ambiguous_completions(token)
+                  .iter()
+                  .map(|c| c.help_symbol.clone())
+                  .collect()
+        };
+        assert!(names("x").is_empty());
+        assert_eq!(names("rel"), vec!["reload"]);
+        assert_eq!(names("sh"), vec!["show", "shutdown", "shell"]);
+    }
+
+    #[test]
+    fn parse_errors_have_context() {
+        let show = command_node("show", PRIORITY_DEFAULT, false);
+        show.add_successor(command_node("interface", PRIORITY_DEFAULT, false)).unwrap();
+        show.add_successor(command_node("route", PRIORITY_DEFAULT, false)).unwrap();
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+        root.add_successor(command_node("shutdown", PRIORITY_DEFAULT, false)).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("sh") {
+            Err(error @ ParseError::AmbiguousMatch { .. }) => {
+                assert_eq!(error.to_string(), "'sh' is ambiguous: show, shutdown");
+                if let ParseError::AmbiguousMatch { index, .. } = error {
+                    assert_eq!(index, 0);
+                }
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("show vlan") {
+            Err(error @ ParseError::NoMatches { .. }) => {
+                assert_eq!(error.to_string(),
+                           "'vlan' doesn't match anything, expected one of: interface, route");
+                if let ParseError::NoMatches { token, index, acceptable, suggestions } = error {
+                    assert_eq!(token.text, "vlan");
+                    assert_eq!(index, 1);
+                    assert_eq!(*acceptable, ["interface", "route"]);
+                    assert!(suggestions.is_empty());
+                }
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("show rotue") {
+            Err(error @ ParseError::NoMatches { .. }) => {
+                assert_eq!(error.to_string(),
+                           "'rotue' doesn't match anything, did you mean 'route'?")
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        match parser.parse("show route  detail") {
+            Err(error @ ParseError::UnexpectedToken { .. }) => {
+                assert_eq!(error.to_string(), "unexpected 'detail'");
+                if let ParseError::UnexpectedToken { token, index } = error {
+                    assert_eq!(index, 2);
+                    assert_eq!(token.location.start.char, 12);
+                }
+            }
+            _ => panic!(),
+        }
+    }
+
+    fn flag(name: &str) -> Arc<dyn ParameterNode> {
+        FlagParameterNode::new(name, None)
+    }
+
+    /// `show log [verbose]` and `show <name> [verbose] <count>`, and
+    /// with `ambiguous`, `show <file> <count>`.
+    fn backtracking_tree(ambiguous: bool) -> Arc<RootNode> {
+        let show = command_node("show", PRIORITY_DEFAULT, false);
+        show.add_successor(CommandNode::new("log",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(noop_handler)),
+                                            vec![flag("verbose")])).unwrap();
+        let name = PositionalCommandNode::new("name",
+                                              PRIORITY_DEFAULT,
+                                              None,
+                                              Some(Box::new(noop_handler)),
+                                              vec![flag("verbose"),
+                                                   positional("count", true, false)]);
+        show.add_successor(name).unwrap();
+        if ambiguous {
+            let file = PositionalCommandNode::new("file",
+                                                  PRIORITY_MINIMUM,
+                                                  None,
+                                                  Some(Box::new(noop_handler)),
+                                                  vec![positional("count", true, false)]);
+            show.add_successor(file).unwrap();
+        }
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+        root
+    }
+
+    #[test]
+    fn backtracking() {
+        let root = backtracking_tree(false);
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("show log 5").is_err());
+
+        let mut parser = Parser::new(root.clone());
+        parser.set_backtracking(1);
+        assert!(parser.parse("show log").is_ok());
+        assert_eq!(&*parser.nodes[1].name(), "log");
+        assert!(parser.parse("5").is_ok());
+        assert_eq!(&*parser.nodes[1].name(), "name");
+        assert_eq!(parser.values().get_str("name"), Some("log"));
+        assert_eq!(parser.values().get_str("count"), Some("5"));
+        assert!(parser.verify().is_ok());
+
+        // Undoing steps back over the reinterpreted tokens one by one.
+        assert!(parser.undo());
+        assert_eq!(parser.command_path(), "show log");
+        assert!(!parser.values().contains("count"));
+    }
+
+    #[test]
+    fn backtracking_depth() {
+        let root = backtracking_tree(false);
+        let mut parser = Parser::new(root.clone());
+        parser.set_backtracking(1);
+        assert!(parser.parse("show log verbose").is_ok());
+        assert!(parser.parse("5").is_err());
+        assert_eq!(parser.command_path(), "show log verbose");
+
+        let mut parser = Parser::new(root);
+        parser.set_backtracking(2);
+        assert!(parser.parse("show log verbose 5").is_ok());
+        assert_eq!(parser.command_path(), "show log verbose 5");
+        assert_eq!(parser.values().get_str("name"), Some("log"));
+        assert!(parser.values().contains("verbose"));
+    }
+
+    #[test]
+    fn backtracking_ambiguity() {
+        let mut parser = Parser::new(backtracking_tree(true));
+        parser.set_backtracking(1);
+        match parser.parse("show log 5") {
+            Err(ParseError::AmbiguousMatch { token, index, candidates }) => {
+                assert_eq!(token.text, "log");
+                assert_eq!(index, 1);
+                assert_eq!(candidates, vec!["name", "file"]);
+            }
+            _ => panic!(),
+        }
+        assert_eq!(parser.command_path(), "show log");
+    }
+
+    fn max_tokens_parser(max_tokens: usize) -> Parser<'static> {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show interfaces")
+                         .parameter(Parameter::new("detail")
+                                        .kind(ParameterKind::Flag)
+                                        .finalize())
+                         .handler(|_| Ok(None))
+                         .finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        parser.set_max_tokens(max_tokens);
+        parser
+    }
+
+    #[test]
+    fn max_tokens() {
+        let mut parser = max_tokens_parser(4);
+        assert!(parser.parse("show interfaces detail").is_ok());
+
+        let mut parser = max_tokens_parser(3);
+        assert!(parser.parse("show interfaces detail").is_ok());
+        assert!(parser.verify().is_ok());
+
+        let mut parser = max_tokens_parser(2);
+        match parser.parse("show interfaces detail") {
+            Err(error @ ParseError::TooManyTokens { .. }) => {
+                assert_eq!(error.to_string(),
+                           "too many tokens at 'detail', at most 2 may be given")
+            }
+            _ => panic!(),
+        }
+        assert_eq!(parser.tokens.len(), 2);
+
+        // Undoing a token makes room for another.
+        assert!(parser.undo());
+        assert!(parser.parse("interfaces").is_ok());
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").finalize());
+        tree.command(Command::new("show interfaces")
+                         .parameter(Parameter::new("detail")
+                                        .kind(ParameterKind::Flag)
+                                        .finalize())
+                         .parameter(Parameter::new("mtu").kind(ParameterKind::Named).finalize())
+                         .handler(|_| Ok(None))
+                         .finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("show interfaces detail").is_ok());
+        let snapshot = parser.snapshot();
+        assert!(parser.parse("mtu 1500").is_ok());
+        assert!(parser.values().contains("mtu"));
+
+        parser.restore(&snapshot);
+        assert!(!parser.values().contains("mtu"));
+        assert!(parser.values().contains("detail"));
+        assert_eq!(parser.accepted_nodes().len(), 3);
+        assert_eq!(completion_names(&parser), vec!["[mtu <mtu>]"]);
+        assert!(parser.undo());
+        assert_eq!(parser.accepted_nodes().len(), 2);
+
+        // The snapshot can be restored again.
+        parser.restore(&snapshot);
+        assert!(parser.parse("mtu 9000").is_ok());
+        assert_eq!(parser.values().get_str("mtu"), Some("9000"));
+    }
+
+    #[test]
+    fn reset_to_valid_node() {
+        let root = undo_tree();
+        let show = root.successors()[0].clone();
+        let interface = subcommands(&*show)[0].clone();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("show verbose").is_ok());
+        assert!(parser.reset_to_node(show.clone()).is_ok());
+        assert!(parser.nodes.is_empty());
+        assert!(parser.parameters.is_empty());
+        assert_eq!(completion_names(&parser), vec!["interface", "[verbose]"]);
+        assert!(!parser.undo());
+
+        // Input continues from the node.
+        assert!(parser.parse("verbose").is_ok());
+        assert!(parser.parameters.contains_key("verbose"));
+        assert!(parser.verify().is_ok());
+
+        assert!(parser.reset_to_node(interface).is_ok());
+        assert_eq!(&*parser.command().unwrap().name(), "interface");
+    }
+
+    #[test]
+    fn reset_to_invalid_node() {
+        let mut parser = Parser::new(undo_tree());
+        assert!(parser.parse("show").is_ok());
+        let other: Arc<dyn Node> = command_node("show", PRIORITY_DEFAULT, false);
+        match parser.reset_to_node(other) {
+            Err(error @ ParseError::NodeNotInTree(_)) => {
+                assert_eq!(error.to_string(), "node 'show' is not in the command tree")
+            }
+            _ => panic!(),
+        }
+        // The parse state is left alone.
+        assert_eq!(parser.nodes.len(), 1);
+    }
+
+    #[test]
+    fn mode_hooks() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut interface = CommandNode::new("interface",
+                                             PRIORITY_DEFAULT,
+                                             false,
+                                             None,
+                                             Some(Box::new(noop_handler)),
+                                             vec![positional("name", true, false)]);
+        let (enter_log, exit_log) = (log.clone(), log.clone());
+        let command = Arc::get_mut(&mut interface).unwrap();
+        command.set_on_enter(Box::new(move |context| {
+            let name = context.values().get_str("name").unwrap_or_default();
+            enter_log.lock().unwrap().push(format!("enter {} {}", context.node().name(), name));
+        }));
+        command.set_on_exit(Box::new(move |context| {
+            exit_log.lock().unwrap().push(format!("exit {}", context.node().name()));
+        }));
+        let router = command_node("router", PRIORITY_DEFAULT, false);
+        let root = RootNode::new();
+        root.add_successor(interface.clone()).unwrap();
+        root.add_successor(router.clone()).unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("interface eth0").is_ok());
+        assert!(parser.reset_to_node(interface.clone()).is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!["enter interface eth0"]);
+        assert_eq!(parser.mode_name().as_deref(), Some("interface"));
+        parser.reset();
+        assert_eq!(&*parser.command().unwrap().name(), "interface");
+
+        // Staying in the mode for another line runs no hooks.
+        assert!(parser.reset_to_node(interface.clone()).is_ok());
+        assert_eq!(log.lock().unwrap().len(), 1);
+
+        assert!(parser.reset_to_node(router).is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!["enter interface eth0", "exit interface"]);
+
+        assert!(parser.reset_to_node(interface).is_ok());
+        parser.exit_mode();
+        assert_eq!(log.lock().unwrap()[3], "exit interface");
+        assert_eq!(&*parser.current_node.name(), "__root__");
+        parser.exit_mode();
+        assert_eq!(log.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn trailing_tokens() {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+        let mut parser = Parser::new(root.clone());
+        parser.set_trailing_tokens(TrailingTokens::Permissive);
+        assert!(parser.parse("copy a b c copy").is_ok());
+        let leftovers = parser.leftover_tokens().iter().map(|t| t.text).collect::<Vec<_>>();
+        assert_eq!(leftovers, vec!["c", "copy"]);
+        assert_eq!(parser.warnings()[0].to_string(), "ignored trailing token 'c'");
+        assert_eq!(parser.warnings().len(), 2);
+        assert!(parser.execute().is_ok());
+        parser.reset();
+        assert!(parser.leftover_tokens().is_empty());
+
+        // Before the command is complete, a token that doesn't fit is
+        // still an error.
+        assert!(matches!(parser.parse("bogus"), Err(ParseError::NoMatches { .. })));
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(command_node("route", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(show).unwrap();
+        parser.reset();
+        assert!(matches!(parser.parse("show bogus"), Err(ParseError::NoMatches { .. })));
+
+        parser.reset();
+        parser.set_strict_mode(true);
+        match parser.parse("copy a b c") {
+            Err(ParseError::StrictModeViolation(ParseWarning::IgnoredTrailingToken { token })) => {
+                assert_eq!(token, "c")
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        assert!(matches!(parser.parse("copy a b c"), Err(ParseError::TooManyArguments { .. })));
+    }
+
+    #[test]
+    fn reset_between_lines() {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+        let reboot = command_node("reboot", PRIORITY_DEFAULT, false);
+        reboot.set_required_role("admin").unwrap();
+        root.add_successor(reboot).unwrap();
+
+        let mut parser = Parser::new(root);
+        parser.set_permission_checker(Box::new(permissions::RoleChecker), "operator");
+        assert!(parser.parse("copy a b").is_ok());
+        assert!(parser.verify().is_ok());
+
+        parser.reset();
+        assert!(parser.nodes.is_empty());
+        assert!(parser.command().is_none());
+        assert!(parser.parse("copy c").is_ok());
+        assert_eq!(parser.parameters["src"], vec!["c"]);
+        assert!(!parser.parameters.contains_key("dst"));
+        assert!(parser.verify().is_err());
+        // Only the steps of the second line can be undone.
+        assert!(parser.undo() && parser.undo());
+        assert!(!parser.undo());
+
+        // The permission checker is kept.
+        parser.reset();
+        match parser.parse("reboot") {
+            Err(ParseError::PermissionDenied { token, .. }) => assert_eq!(token.text, "reboot"),
+            _ => panic!(),
+        }
+    }
+
+    fn completion_tree() -> Arc<RootNode> {
+        let ping = CommandNode::new("ping",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    Some("Ping a host.".to_string()),
+                                    Some(Box::new(noop_handler)),
+                                    vec![parameter("verbose", &[]),
+                                         parameter("debug", &[]),
+                                         positional("host", true, false)]);
+        let root = RootNode::new();
+        root.add_successor(ping).unwrap();
+        root.add_successor(command_node("pong", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("purge", PRIORITY_DEFAULT, true)).unwrap();
+        root
+    }
+
+    #[test]
+    fn complete_with_prefix() {
+        let parser = Parser::new(completion_tree());
+        // Everything that is visible is offered without a token.
+        assert_eq!(completion_names(&parser), vec!["ping", "pong"]);
+
+        let token = tokenize("pi").unwrap()[0];
+        let completions = parser.complete(Some(token));
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].help_symbol, "ping");
+        assert_eq!(completions[0].help_text, Some("Ping a host.".to_string()));
+        assert_eq!(completions[0].options[0].option_string, "ping");
+        assert!(completions[0].options[0].complete);
+
+        // Hidden nodes are never offered.
+        let token = tokenize("pu").unwrap()[0];
+        assert!(parser.complete(Some(token)).is_empty());
+    }
+
+    #[test]
+    fn complete_parameters() {
+        let mut parser = Parser::new(completion_tree());
+        assert!(parser.parse("ping").is_ok());
+        assert_eq!(completion_names(&parser), vec!["[verbose]", "[debug]", "<host>"]);
+
+        // Given parameters aren't offered again.
+        assert!(parser.parse("verbose example.com").is_ok());
+        assert_eq!(completion_names(&parser), vec!["[debug]"]);
+    }
+
+    #[test]
+    fn completion_kinds() {
+        let kinds = |parser: &Parser| {
+            parser.complete(None)
+                  .iter()
+                  .map(|c| (c.help_symbol.clone(), c.kind, c.terminal))
+                  .collect::<Vec<_>>()
+        };
+        let mut parser = Parser::new(completion_tree());
+        // `ping` needs a host before it can be executed.
+        assert_eq!(kinds(&parser),
+                   vec![("ping".to_string(), CompletionKind::Command, false),
+                        ("pong".to_string(), CompletionKind::Command, true)]);
+
+        assert!(parser.parse("ping").is_ok());
+        assert_eq!(kinds(&parser),
+                   vec![("[verbose]".to_string(), CompletionKind::Flag, false),
+                        ("[debug]".to_string(), CompletionKind::Flag, false),
+                        ("<host>".to_string(), CompletionKind::Value, true)]);
+
+        // Completions can be kept by a frontend.
+        let cached = parser.complete(None).clone();
+        assert!(format!("{:?}", cached[2]).contains("<host>"));
+    }
+
+    fn cursor_tree() -> Arc<RootNode> {
+        let show = command_node("show", PRIORITY_DEFAULT, false);
+        show.add_successor(command_node("interfaces", PRIORITY_DEFAULT, false)).unwrap();
+        show.add_successor(command_node("ip", PRIORITY_DEFAULT, false)).unwrap();
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+        root.add_successor(command_node("shutdown", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(CommandNode::new("echo",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(noop_handler)),
+                                            vec![positional("text", true, false)])).unwrap();
+        root
+    }
+
+    fn completions_at(line: &str, cursor: usize) -> (Vec<String>, Range<usize>) {
+        let mut parser = Parser::new(cursor_tree());
+        let result = parser.complete_at(line, cursor).unwrap();
+        let symbols = result.completions.iter().map(|c| c.help_symbol.clone()).collect();
+        (symbols, result.replace)
+    }
+
+    #[test]
+    fn complete_at_cursor() {
+        assert_eq!(completions_at("sh", 2),
+                   (vec!["show".to_string(), "shutdown".to_string()], 0..2));
+        assert_eq!(completions_at("show i", 6),
+                   (vec!["interfaces".to_string(), "ip".to_string()], 5..6));
+        // What follows the cursor is replaced, but not matched.
+        assert_eq!(completions_at("show intxx brief", 8), (vec!["interfaces".to_string()], 5..10));
+        assert_eq!(completions_at("show ixx", 6),
+                   (vec!["interfaces".to_string(), "ip".to_string()], 5..8));
+        // After whitespace, the options are inserted at the cursor.
+        assert_eq!(completions_at("show  ip", 5),
+                   (vec!["interfaces".to_string(), "ip".to_string()], 5..5));
+        assert_eq!(completions_at("show ip", 99).1, 5..7);
+
+        let mut parser = Parser::new(cursor_tree());
+        assert!(parser.complete_at("shw ip", 6).is_err());
+    }
+
+    #[test]
+    fn complete_at_cursor_within_quotes() {
+        assert_eq!(completions_at("echo \"hello world\" x", 8),
+                   (vec!["<text>".to_string()], 5..18));
+        assert_eq!(completions_at("echo \"hello", 8), (vec!["<text>".to_string()], 5..11));
+        assert_eq!(completions_at("echo \"hi\"", 9), (vec!["<text>".to_string()], 5..9));
+
+        let mut parser = Parser::new(cursor_tree());
+        let result = parser.complete_at("echo \"he", 8).unwrap();
+        assert_eq!(result.completions[0].token.unwrap().text, "he");
+    }
+
+    #[test]
+    fn complete_current_or_next_word() {
+        let mut parser = Parser::new(cursor_tree());
+        let result = parser.complete_line("show int").unwrap();
+        assert_eq!(result.mode, CompletionMode::CurrentWord);
+        assert_eq!(result.completions[0].help_symbol, "interfaces");
+        assert_eq!(parser.accepted_nodes().len(), 1);
+
+        for line in &["show ", "show   "] {
+            let result = parser.complete_line(line).unwrap();
+            assert_eq!(result.mode, CompletionMode::NextWord);
+            assert_eq!(result.completions.len(), 2);
+            assert_eq!(result.replace, line.len()..line.len());
+        }
+
+        // A quote starts a word, even though nothing follows it yet.
+        let result = parser.complete_line("echo \"").unwrap();
+        assert_eq!(result.mode, CompletionMode::CurrentWord);
+        assert_eq!(result.replace, 5..6);
+    }
+
+    #[test]
+    fn disabled_commands() {
+        let reload = command_node("reload", PRIORITY_DEFAULT, false);
+        let root = RootNode::new();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(reload.clone()).unwrap();
+        root.freeze();
+        for _ in 0..2 {
+            reload.disable();
+            assert!(!reload.enabled());
+            let mut parser = Parser::new(root.clone());
+            assert_eq!(completion_names(&parser), vec!["show"]);
+            match parser.parse("reload") {
+                Err(error @ ParseError::DisabledCommand { .. }) => {
+                    assert_eq!(error.to_string(), "command 'reload' is disabled")
+                }
+                _ => panic!(),
+            }
+
+            reload.enable();
+            let mut parser = Parser::new(root.clone());
+            assert_eq!(completion_names(&parser), vec!["show", "reload"]);
+            assert!(parser.parse("reload").is_ok());
+            assert!(parser.execute().is_ok());
+
+            // Disabling the command after it was parsed stops it.
+            reload.disable();
+            match parser.execute() {
+                Err(ExecuteError::DisabledCommand(command)) => assert_eq!(command, "reload"),
+                _ => panic!(),
+            }
+            reload.enable();
+        }
+    }
+
+    #[test]
+    fn disabled_commands_are_not_ambiguous() {
+        let reload = command_node("reload", PRIORITY_DEFAULT, false);
+        let root = RootNode::new();
+        root.add_successor(reload.clone()).unwrap();
+        root.add_successor(command_node("reset", PRIORITY_DEFAULT, false)).unwrap();
+        let parser = Parser::new(root);
+        let ambiguous = |parser: &Parser| {
+            parser.ambiguous_completions("re")
+                  .iter()
+                  .map(|c| c.help_symbol.clone())
+                  .collect::<Vec<_>>()
+        };
+        assert_eq!(ambiguous(&parser), vec!["reload", "reset"]);
+        reload.disable();
+        assert_eq!(ambiguous(&parser), vec!["reset"]);
+    }
+
+    #[test]
+    fn error_positions() {
+        let mut parser = Parser::new(cursor_tree());
+        let error = parser.parse("show  bogus").unwrap_err();
+        assert_eq!((error.index(), error.span("show  bogus")), (Some(1), Some(6..11)));
+
+        // Spans are in bytes, while the caret is placed by character.
+        let mut parser = Parser::new(cursor_tree());
+        let error = parser.parse("shöw").unwrap_err();
+        assert_eq!(error.span("shöw"), Some(0..5));
+        assert_eq!(error.diagnostic("shöw").unwrap(), "shöw\n^~~~");
+
+        let mut parser = Parser::new(cursor_tree());
+        assert!(parser.parse("echo").is_ok());
+        let errors = parser.verify().unwrap_err();
+        assert_eq!(errors[0].index(), Some(1));
+        assert_eq!(parser.token_span(1, "echo"), 4..4);
+
+        let mut parser = Parser::new(exclusive_tree());
+        let line = "copy verbose force quiet";
+        assert_eq!(parser.parse(line).unwrap_err().diagnostic(line).unwrap(),
+                   "copy verbose force quiet\n                   ^~~~~");
+    }
+
+    #[test]
+    fn optional_flags() {
+        let root = copy_tree(vec![FlagParameterNode::new("force", None),
+                                  positional("file", true, false)]);
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy force notes.txt").is_ok());
+        assert!(parser.verify().is_ok());
+        let values = parser.values();
+        assert_eq!(values.get_as::<bool>("force"), Some(true));
+        // The flag doesn't take the token after it as its value.
+        assert_eq!(values.get_str("file"), Some("notes.txt"));
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy notes.txt").is_ok());
+        assert!(parser.verify().is_ok());
+        assert_eq!(parser.values().get_as::<bool>("force"), None);
+    }
+
+    #[test]
+    fn deduplicated_flags() {
+        let mut verbose = FlagParameterNode::with_options("verbose",
+                                                          None,
+                                                          false,
+                                                          PRIORITY_PARAMETER,
+                                                          vec![],
+                                                          true,
+                                                          None,
+                                                          false);
+        verbose.set_deduplicate(true);
+        let root = copy_tree(vec![Arc::new(verbose)]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy verbose verbose").is_ok());
+        assert_eq!(parser.values().get_all("verbose").len(), 1);
+        assert_eq!(parser.values().get_as::<bool>("verbose"), Some(true));
+    }
+
+    #[test]
+    fn exact_flags() {
+        let root = copy_tree(vec![FlagParameterNode::new("force", None),
+                                  positional("file", true, false)]);
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy f").is_ok());
+        let values = parser.values();
+        // A flag isn't given by a prefix, which is a value.
+        assert_eq!(values.get_as::<bool>("force"), None);
+        assert_eq!(values.get_str("file"), Some("f"));
+
+        // It is still completed from one.
+        let mut parser = Parser::new(root);
+        let result = parser.complete_at("copy fo", 7).unwrap();
+        let names = result.completions.iter().map(|c| c.help_symbol.clone()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["[force]", "<file>"]);
+    }
+
+    #[test]
+    fn positional_parameters() {
+        let file = SimpleParameterNode::positional("file", None, true, false);
+        assert_eq!(&*file.help_symbol(), "<file>");
+        assert!(file.matches("notes.txt"));
+        assert!(file.matches("--"));
+        let files = SimpleParameterNode::positional("file", None, true, true);
+        assert_eq!(&*files.help_symbol(), "<file>...");
+        let mode = SimpleParameterNode::positional("mode", None, false, false);
+        assert_eq!(&*mode.help_symbol(), "[<mode>]");
+
+        let root = copy_tree(vec![files, mode]);
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy a.txt b.txt").is_ok());
+        assert!(parser.verify().is_ok());
+        assert_eq!(parser.values().get_all("file"),
+                   &[ParamValue::Str("a.txt".to_string()), ParamValue::Str("b.txt".to_string())]);
+    }
+
+    #[test]
+    fn every_problem_is_verified() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("route")
+                         .parameter(Parameter::new("metric")
+                                        .kind(ParameterKind::Named)
+                                        .conflicts_with("auto")
+                                        .finalize())
+                         .parameter(Parameter::new("destination").required(true).finalize())
+                         .parameter(Parameter::new("gateway")
+                                        .kind(ParameterKind::Named)
+                                        .required(true)
+                                        .finalize())
+                         .parameter(Parameter::new("auto").kind(ParameterKind::Flag).finalize())
+                         .handler(|_| Ok(None))
+                         .finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("route auto metric 5").is_ok());
+        let errors = parser.verify().unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(messages,
+                   vec!["route: parameter 'metric' conflicts with 'auto'",
+                        "route: required parameter 'destination' is missing",
+                        "route: required parameter 'gateway' is missing"]);
+        match parser.execute() {
+            Err(ExecuteError::Verify(errors)) => assert_eq!(errors.len(), 3),
+            _ => panic!(),
+        }
+    }
+
+    /// A `copy` command with a `mode` given by name, which may only be
+    /// `644` or `755`.
+    fn named_mode_tree(required: bool) -> Arc<RootNode> {
+        let modes = FuzzyMatchNode::new(SimpleParameterNode::new("mode",
+                                                                 None,
+                                                                 false,
+                                                                 PRIORITY_PARAMETER,
+                                                                 vec![],
+                                                                 false,
+                                                                 None,
+                                                                 true),
+                                        vec!["644".to_string(), "755".to_string()],
+                                        0);
+        copy_tree(vec![NamedParameterNode::new("mode", Box::new(modes), None, required),
+                       positional("file", true, false)])
+    }
+
+    #[test]
+    fn optional_named_parameters() {
+        let root = named_mode_tree(false);
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy mode 644 notes.txt").is_ok());
+        assert!(parser.verify().is_ok());
+        let values = parser.values();
+        assert_eq!(values.get_str("mode"), Some("644"));
+        assert_eq!(values.get_str("file"), Some("notes.txt"));
+
+        // The name and its value are both taken.
+        let names = parser.accepted_nodes()
+                          .iter()
+                          .map(|n| n.name().to_string())
+                          .collect::<Vec<_>>();
+        assert_eq!(names, vec!["copy", "mode", "mode", "file"]);
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("copy notes.txt mode").is_ok());
+        match parser.verify() {
+            Err(errors) => {
+                assert_eq!(errors[0].to_string(), "copy: parameter 'mode' is missing its value")
+            }
+            Ok(()) => panic!(),
+        }
+    }
+
+    #[test]
+    fn named_parameter_values() {
+        let mut parser = Parser::new(named_mode_tree(false));
+        match parser.parse("copy mode 600 notes.txt") {
+            Err(ParseError::InvalidParameterValue { parameter, index, .. }) => {
+                assert_eq!(parameter, "mode");
+                assert_eq!(index, 2);
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(named_mode_tree(false));
+        assert!(parser.complete_line("copy mode ").is_ok());
+        let completions = parser.complete(None);
+        assert_eq!(completions[0].help_symbol, "<mode>");
+        let options = completions[0].options.iter().map(|o| o.option_string.as_str());
+        assert_eq!(options.collect::<Vec<_>>(), vec!["644", "755"]);
+    }
+
+    #[test]
+    fn required_named_parameters() {
+        let mut parser = Parser::new(named_mode_tree(true));
+        assert!(parser.parse("copy notes.txt").is_ok());
+        match parser.verify() {
+            Err(errors) => {
+                match errors[0] {
+                    VerifyError::MissingParameter { ref name, .. } => assert_eq!(name, "mode"),
+                    _ => panic!(),
+                }
+            }
+            Ok(()) => panic!(),
+        }
+
+        let mut parser = Parser::new(named_mode_tree(true));
+        assert!(parser.parse("copy mode 755 notes.txt").is_ok());
+        assert!(parser.verify().is_ok());
+    }
+
+    fn loose_completions(parser: &mut Parser, line: &'static str)
+                         -> (Vec<String>, CompletionMatching) {
+        let result = parser.complete_line(line).unwrap();
+        let symbols = result.completions.iter().map(|c| c.help_symbol.clone()).collect();
+        (symbols, result.matching)
+    }
+
+    #[test]
+    fn loose_completion() {
+        let root = RootNode::new();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("interface-config", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("if-counters", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("ifconfig", PRIORITY_DEFAULT, true)).unwrap();
+        let mut parser = Parser::new(root);
+        assert!(loose_completions(&mut parser, "SH").0.is_empty());
+        assert!(loose_completions(&mut parser, "ifc").0.is_empty());
+
+        parser.set_case_insensitive_completion(true);
+        parser.set_fuzzy_completion(true);
+        let result = parser.complete_line("SH").unwrap();
+        assert_eq!(result.matching, CompletionMatching::CaseInsensitive);
+        assert_eq!(result.completions.len(), 1);
+        assert_eq!(result.completions[0].options[0].option_string, "show");
+
+        // The tightest match comes first, and hidden nodes are never
+        // offered.
+        assert_eq!(loose_completions(&mut parser, "ifc"),
+                   (vec!["if-counters".to_string(), "interface-config".to_string()],
+                    CompletionMatching::Fuzzy));
+        // Fuzzy matches are only offered when nothing else matches.
+        assert_eq!(loose_completions(&mut parser, "if"),
+                   (vec!["if-counters".to_string()], CompletionMatching::Prefix));
+    }
+
+    #[test]
+    fn renamed_commands() {
+        let status = command_node("status", PRIORITY_DEFAULT, false);
+        let verb = PositionalCommandNode::new("verb", PRIORITY_MINIMUM, None, None, vec![]);
+        let root = RootNode::new();
+        root.add_successor(status.clone()).unwrap();
+        root.add_successor(verb.clone()).unwrap();
+        // Commands can be renamed once they are part of a tree.
+        status.rename("state").unwrap();
+        assert_eq!(&*status.help_symbol(), "state");
+        verb.as_command().unwrap().rename("action").unwrap();
+        assert_eq!(&*verb.help_symbol(), "<action>");
+
+        let mut parser = Parser::new(root.clone());
+        assert_eq!(completion_names(&parser), vec!["state", "<action>"]);
+        assert!(parser.parse("state").is_ok());
+        assert_eq!(&*parser.nodes[0].name(), "state");
+
+        // The old name is now only a value of the positional command.
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("status").is_ok());
+        assert_eq!(parser.values().get_str("action"), Some("status"));
+
+        status.freeze();
+        assert!(status.rename("status").is_err());
+        assert_eq!(&*status.name(), "state");
+    }
+
+    fn remaining_names(parser: &Parser) -> Vec<String> {
+        parser.remaining_required().iter().map(|p| p.name().to_string()).collect()
+    }
+
+    #[test]
+    fn remaining_required_parameters() {
+        let root = copy_tree(vec![positional("src", true, false),
+                                  positional("dst", true, false),
+                                  parameter("force", &[])]);
+        let mut parser = Parser::new(root);
+        assert!(remaining_names(&parser).is_empty());
+
+        assert!(parser.parse("copy").is_ok());
+        assert_eq!(remaining_names(&parser), vec!["src", "dst"]);
+
+        assert!(parser.parse("a force").is_ok());
+        assert_eq!(remaining_names(&parser), vec!["dst"]);
+
+        assert!(parser.parse("b").is_ok());
+        assert!(remaining_names(&parser).is_empty());
+        assert!(parser.verify().is_ok());
+    }
+
+    #[test]
+    fn remaining_required_without_required_parameters() {
+        let mut parser = Parser::new(copy_tree(vec![parameter("force", &[])]));
+        assert!(parser.parse("copy").is_ok());
+        assert!(parser.remaining_required().is_empty());
+    }
+
+    thread_local! {
+        static COUNTS: RefCell<Vec<u32>> = const { RefCell::new(vec![]) };
+    }
+
+    fn count_handler(context: &HandlerContext) -> HandlerResult {
+        COUNTS.with(|c| c.borrow_mut().extend(context.values().get_as::<u32>("count")));
+        Ok(None)
+    }
+
+    #[test]
+    fn parameter_values() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("ping")
+                         .parameter(Parameter::new("host").kind(ParameterKind::Simple).finalize())
+                         .parameter(Parameter::new("count").kind(ParameterKind::Named).finalize())
+                         .parameter(Parameter::new("via")
+                                        .kind(ParameterKind::Named)
+                                        .repeatable(true)
+                                        .finalize())
+                         .parameter(Parameter::new("verbose").kind(ParameterKind::Flag).finalize())
+                         .parameter(Parameter::new("quiet").kind(ParameterKind::Flag).finalize())
+                         .handler(count_handler)
+                         .finalize());
+        let root = tree.finalize().unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("ping example.com count 3 via a verbose via b").is_ok());
+        let values = parser.values();
+        assert_eq!(values.get_str("host"), Some("example.com"));
+        assert_eq!(values.get_as::<u32>("count"), Some(3));
+        assert_eq!(values.get("verbose"), Some(&ParamValue::Bool(true)));
+        assert_eq!(values.get("quiet"), None);
+        assert_eq!(values.get_all("via"),
+                   &[ParamValue::Str("a".to_string()), ParamValue::Str("b".to_string())]);
+
+        COUNTS.with(|c| c.borrow_mut().clear());
+        assert!(parser.execute().is_ok());
+        COUNTS.with(|c| assert_eq!(*c.borrow(), vec![3]));
+    }
+
+    fn dns_tree(deduplicate: bool) -> Arc<RootNode> {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("set")
+                         .parameter(Parameter::new("dns-server")
+                                        .kind(ParameterKind::Named)
+                                        .repeatable(true)
+                                        .deduplicate(deduplicate)
+                                        .finalize())
+                         .parameter(Parameter::new("domain").kind(ParameterKind::Named).finalize())
+                         .parameter(Parameter::new("verbose").kind(ParameterKind::Flag).finalize())
+                         .handler(noop_handler)
+                         .finalize());
+        tree.finalize().unwrap()
+    }
+
+    #[test]
+    fn repeated_values_in_order() {
+        let line = "set dns-server 8.8.8.8 dns-server 1.1.1.1 dns-server 8.8.8.8";
+        let mut parser = Parser::new(dns_tree(false));
+        assert!(parser.parse(line).is_ok());
+        assert_eq!(parser.values().get_all("dns-server"),
+                   &[ParamValue::Str("8.8.8.8".to_string()),
+                     ParamValue::Str("1.1.1.1".to_string()),
+                     ParamValue::Str("8.8.8.8".to_string())]);
+
+        let mut parser = Parser::new(dns_tree(true));
+        assert!(parser.parse(line).is_ok());
+        assert_eq!(parser.values().get_all("dns-server"),
+                   &[ParamValue::Str("8.8.8.8".to_string()),
+                     ParamValue::Str("1.1.1.1".to_string())]);
+    }
+
+    #[test]
+    fn duplicate_parameters() {
+        let mut parser = Parser::new(dns_tree(false));
+        match parser.parse("set domain a.example domain b.example") {
+            Err(ParseError::DuplicateParameter { parameter, token, index }) => {
+                assert_eq!(parameter, "domain");
+                assert_eq!(token.text, "domain");
+                assert_eq!(index, 3);
+            }
+            _ => panic!(),
+        }
+        let mut parser = Parser::new(dns_tree(false));
+        match parser.parse("set verbose verbose") {
+            Err(error @ ParseError::DuplicateParameter { .. }) => {
+                assert_eq!(error.to_string(), "parameter 'verbose' may only be given once")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[derive(Default)]
+    struct AppState {
+        hostname: String,
+        commits: usize,
+    }
+
+    #[test]
+    fn closure_handlers_capture_state() {
+        let state = Arc::new(Mutex::new(AppState::default()));
+        let mut tree = CommandTree::new();
+        let hostname_state = state.clone();
+        tree.command(Command::new("hostname")
+                         .parameter(Parameter::new("name").required(true).finalize())
+                         .handler(move |context| {
+                             let name = context.values().get_str("name").ok_or("no name")?;
+                             hostname_state.lock().unwrap().hostname = name.to_string();
+                             Ok(None)
+                         })
+                         .finalize());
+        let commit_state = state.clone();
+        tree.command(Command::new("commit")
+                         .handler(move |context| {
+                             assert_eq!(context.nodes().len(), 1);
+                             let mut state = commit_state.lock().unwrap();
+                             if state.hostname.is_empty() {
+                                 return Err("no hostname has been set".into());
+                             }
+                             state.commits += 1;
+                             Ok(Some(format!("commit {} done", state.commits)))
+                         })
+                         .finalize());
+        let root = tree.finalize().unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("commit").is_ok());
+        match parser.execute() {
+            Err(error @ ExecuteError::Handler { .. }) => {
+                assert_eq!(error.to_string(), "commit: no hostname has been set")
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("hostname router1").is_ok());
+        assert!(parser.execute().is_ok());
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("commit").is_ok());
+        assert_eq!(parser.execute().unwrap(), Some("commit 1 done".to_string()));
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.hostname, "router1");
+        assert_eq!(state.commits, 1);
+    }
+
+    fn set_hostname(state: &mut AppState, context: &HandlerContext) -> HandlerResult {
+        state.hostname = context.values().get_str("name").unwrap_or_default().to_string();
+        Ok(None)
+    }
+
+    fn commit(state: &mut AppState, _context: &HandlerContext) -> HandlerResult {
+        state.commits += 1;
+        Ok(None)
+    }
+
+    #[test]
+    fn execute_with_state() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("hostname")
+                         .parameter(Parameter::new("name").required(true).finalize())
+                         .state_handler(set_hostname)
+                         .finalize());
+        tree.command(Command::new("commit").state_handler(commit).finalize());
+        let root = tree.finalize().unwrap();
+
+        let mut state = AppState::default();
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("hostname router1").is_ok());
+        assert!(parser.execute_with(&mut state).is_ok());
+        for _ in 0..2 {
+            let mut parser = Parser::new(root.clone());
+            assert!(parser.parse("commit").is_ok());
+            assert!(parser.execute_with(&mut state).is_ok());
+        }
+        assert_eq!(state.hostname, "router1");
+        assert_eq!(state.commits, 2);
+
+        // Without state, or with the wrong state, the handler can't run.
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("commit").is_ok());
+        match parser.execute() {
+            Err(error @ ExecuteError::Handler { .. }) => {
+                assert_eq!(error.to_string(), "commit: the application state is missing")
+            }
+            _ => panic!(),
+        }
+        assert!(parser.execute_with(&mut 0u32).is_err());
+        assert_eq!(state.commits, 2);
+    }
+
+    fn script_tree(log: &Arc<Mutex<Vec<String>>>) -> Arc<RootNode> {
+        let mut tree = CommandTree::new();
+        for (name, parameter) in [("interface", "name"), ("mtu", "size"), ("description", "text")] {
+            let log = log.clone();
+            tree.command(Command::new(name)
+                             .parameter(Parameter::new(parameter).required(true).finalize())
+                             .handler(move |context| {
+                                 let value = context.values().get_str(parameter).unwrap();
+                                 log.lock().unwrap().push(format!("{} {}", name, value));
+                                 Ok(None)
+                             })
+                             .finalize());
+        }
+        tree.command(Command::new("no").finalize());
+        tree.command(Command::new("no shutdown").handler(noop_handler).finalize());
+        tree.finalize().unwrap()
+    }
+
+    #[test]
+    fn scripts() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(script_tree(&log));
+        let outcomes = parser.execute_script("interface eth0; mtu 9000;no shutdown ; ;");
+        let texts = outcomes.iter().map(|o| o.text).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["interface eth0", "mtu 9000", "no shutdown"]);
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+        assert_eq!(*log.lock().unwrap(), vec!["interface eth0", "mtu 9000"]);
+
+        log.lock().unwrap().clear();
+        let outcomes = parser.execute_script("description \"uplink; core\"");
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(*log.lock().unwrap(), vec!["description \"uplink; core\""]);
+    }
+
+    #[test]
+    fn multibyte_scripts() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(script_tree(&log));
+        let outcomes = parser.execute_script("description café; mtu 1500");
+        let texts = outcomes.iter().map(|o| o.text).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["description café", "mtu 1500"]);
+        assert_eq!(*log.lock().unwrap(), vec!["description café", "mtu 1500"]);
+    }
+
+    #[test]
+    fn scripts_stop_at_the_first_failure() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(script_tree(&log));
+        let outcomes = parser.execute_script("mtu 1500; bogus; mtu 9000");
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].result.is_ok());
+        assert_eq!(outcomes[1].text, "bogus");
+        match outcomes[1].result {
+            Err(ScriptError::Parse(ParseError::NoMatches { ref token, .. })) => {
+                assert_eq!(token.text, "bogus")
+            }
+            _ => panic!(),
+        }
+        assert_eq!(*log.lock().unwrap(), vec!["mtu 1500"]);
+
+        let outcomes = parser.execute_script("mtu");
+        match outcomes[0].result {
+            Err(ref error @ ScriptError::Execute(_)) => {
+                assert_eq!(error.to_string(), "mtu: required parameter 'size' is missing")
+            }
+            _ => panic!(),
+        }
+    }
+
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Observer for Recorder {
+        fn on_accept(&self, node: &dyn Node, token: &Token) {
+            self.record(format!("accept {} {}", node.name(), token.text));
+        }
+
+        fn on_execute(&self, command: &str, values: &ParameterValues) {
+            self.record(format!("execute {} {}", command, values.len()));
+        }
+
+        fn on_result(&self,
+                     command: &str,
+                     result: &Result<Option<String>, ExecuteError>,
+                     _elapsed: Duration) {
+            self.record(format!("result {} {}", command, result.is_ok()));
+        }
+
+        fn on_parse_error(&self, error: &ParseError) {
+            self.record(format!("parse error {}", error.token().unwrap().text));
+        }
+
+        fn on_verify_error(&self, errors: &[VerifyError]) {
+            self.record(format!("verify errors {}", errors.len()));
+        }
+    }
+
+    impl Recorder {
+        fn record(&self, event: String) {
+            if self.name == "panicky" {
+                panic!("observer failed");
+            }
+            self.log.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn observers() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let events = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(script_tree(&log));
+        for name in ["panicky", "recorder"] {
+            parser.add_observer(Box::new(Recorder {
+                name,
+                log: events.clone(),
+            }));
+        }
+        assert!(parser.parse("mtu 9000").is_ok());
+        assert!(parser.execute().is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!["mtu 9000"]);
+        assert_eq!(*events.lock().unwrap(),
+                   vec!["accept mtu mtu", "accept size 9000", "execute mtu 1", "result mtu true"]);
+
+        events.lock().unwrap().clear();
+        parser.reset();
+        assert!(parser.parse("mtu").is_ok());
+        assert!(parser.execute().is_err());
+        parser.reset();
+        assert!(parser.parse("bogus").is_err());
+        assert_eq!(*events.lock().unwrap(),
+                   vec!["accept mtu mtu",
+                        "verify errors 1",
+                        "parse error bogus"]);
+
+        // Completing and checking don't act on the line.
+        events.lock().unwrap().clear();
+        assert!(parser.complete_at("mtu 9000 bo", 11).is_ok());
+        assert!(parser.check("mtu").is_err());
+        assert!(parser.check("bogus").is_err());
+        assert!(events.lock().unwrap().is_empty());
+        parser.reset();
+        assert!(parser.parse("mtu").is_ok());
+        assert_eq!(*events.lock().unwrap(), vec!["accept mtu mtu"]);
+    }
+
+    #[test]
+    fn checked_lines() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(script_tree(&log));
+        assert!(parser.check("mtu 1500").is_ok());
+        assert!(matches!(parser.check("mtu"), Err(ScriptError::Execute(_))));
+        assert!(matches!(parser.check("no"),
+                         Err(ScriptError::Execute(ExecuteError::NoHandler(_)))));
+        assert!(matches!(parser.check("bogus"), Err(ScriptError::Parse(_))));
+
+        let errors = parser.execute_lines(&["interface eth0", "", "mtu", "bogus"]).unwrap_err();
+        let lines = errors.iter().map(|e| e.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![3, 4]);
+        assert_eq!(errors[0].to_string(), "line 3: mtu: required parameter 'size' is missing");
+        assert!(log.lock().unwrap().is_empty());
+
+        let outcomes = parser.execute_lines(&["interface eth0", "", "mtu 9000"]).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+        assert_eq!(*log.lock().unwrap(), vec!["interface eth0", "mtu 9000"]);
+    }
+
+    fn fallback_tree() -> Arc<RootNode> {
+        let root = copy_tree(vec![positional("src", true, false), positional("dst", true, false)]);
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(command_node("shutdown", PRIORITY_DEFAULT, false)).unwrap();
+        root.set_fallback(CommandNode::new("eval",
+                                           PRIORITY_MINIMUM,
+                                           false,
+                                           None,
+                                           Some(Box::new(noop_handler)),
+                                           vec![positional("expression", true, true)])).unwrap();
+        root
+    }
+
+    #[test]
+    fn unmatched_lines_fall_back() {
+        let root = fallback_tree();
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("x + 1").is_ok());
+        assert_eq!(parser.parameters["expression"], vec!["x", "+", "1"]);
+        assert!(parser.verify().is_ok());
+        assert!(parser.execute().is_ok());
+        while parser.undo() {}
+        assert_eq!(&*parser.current_node.name(), "__root__");
+        assert!(parser.command().is_none());
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy a b").is_ok());
+        assert_eq!(&*parser.command().unwrap().name(), "copy");
+
+        // Only the first token falls back.
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("copy a b c").is_err());
+
+        let parser = Parser::new(root);
+        let completions = parser.complete(None);
+        assert!(completions.iter().all(|c| c.help_symbol != "eval"));
+    }
+
+    #[test]
+    fn fallback_keeps_ambiguity_and_can_be_disabled() {
+        let root = fallback_tree();
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("sh") {
+            Err(ParseError::AmbiguousMatch { candidates, .. }) => {
+                assert_eq!(candidates, vec!["show", "shutdown"])
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        parser.set_use_fallback(false);
+        match parser.parse("x + 1") {
+            Err(ParseError::NoMatches { token, .. }) => assert_eq!(token.text, "x"),
+            _ => panic!(),
+        }
+        assert!(parser.command().is_none());
+    }
+
+    fn positional_command_tree() -> Arc<RootNode> {
+        let handler = |context: &HandlerContext| {
+            let values = context.values();
+            let verb = values.get_str("verb").unwrap_or_default();
+            Ok(Some(format!("{} {}", verb, values.get_str("resource").unwrap_or_default())))
+        };
+        let verb = PositionalCommandNode::new("verb", PRIORITY_DEFAULT, None, None, vec![]);
+        verb.add_successor(PositionalCommandNode::new("resource",
+                                                      PRIORITY_DEFAULT,
+                                                      None,
+                                                      Some(Box::new(handler)),
+                                                      vec![])).unwrap();
+        let root = RootNode::new();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root.add_successor(verb).unwrap();
+        let other = PositionalCommandNode::new("other", PRIORITY_MINIMUM, None, None, vec![]);
+        root.add_successor(other).unwrap();
+        root
+    }
+
+    #[test]
+    fn positional_commands() {
+        let root = positional_command_tree();
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("restart web").is_ok());
+        assert_eq!(&*parser.nodes[0].name(), "verb");
+        assert_eq!(parser.execute().unwrap().as_deref(), Some("restart web"));
+
+        let parser = Parser::new(root);
+        let completions = parser.complete(None);
+        let symbols = completions.iter().map(|c| c.help_symbol.as_str()).collect::<Vec<_>>();
+        assert_eq!(symbols, vec!["show", "<verb>", "<other>"]);
+    }
+
+    #[test]
+    fn named_commands_beat_positional_commands() {
+        let root = positional_command_tree();
+        for line in ["show", "sh"] {
+            let mut parser = Parser::new(root.clone());
+            assert!(parser.parse(line).is_ok());
+            assert_eq!(&*parser.nodes[0].name(), "show");
+            assert!(parser.warnings().is_empty());
+            assert!(!parser.values().contains("verb"));
+        }
+
+        // Anything else falls through to the positional command with
+        // the highest priority.
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("shutdown").is_ok());
+        assert_eq!(&*parser.nodes[0].name(), "verb");
+        assert_eq!(parser.values().get_str("verb"), Some("shutdown"));
+    }
+
+    fn mode_tree(log: &Arc<Mutex<Vec<String>>>) -> Arc<RootNode> {
+        let interface = RootNode::new();
+        let log = log.clone();
+        let mtu = move |context: &HandlerContext| {
+            log.lock().unwrap().push(context.values().get_str("size").unwrap().to_string());
+            Ok(None)
+        };
+        interface.add_successor(CommandNode::new("mtu",
+                                                 PRIORITY_DEFAULT,
+                                                 false,
+                                                 None,
+                                                 Some(Box::new(mtu)),
+                                                 vec![positional("size", true, false)])).unwrap();
+        builtins::install_mode_commands(&(interface.clone() as Arc<dyn Node>)).unwrap();
+
+        let config = RootNode::new();
+        let mut enter_interface = CommandNode::new("interface",
+                                                   PRIORITY_DEFAULT,
+                                                   false,
+                                                   None,
+                                                   Some(Box::new(noop_handler)),
+                                                   vec![positional("name", true, false)]);
+        Arc::get_mut(&mut enter_interface).unwrap().set_mode_change(ModeChange::Enter {
+            name: "config-if".to_string(),
+            root: interface,
+        });
+        config.add_successor(enter_interface).unwrap();
+        config.add_successor(command_node("hostname", PRIORITY_DEFAULT, false)).unwrap();
+        builtins::install_mode_commands(&(config.clone() as Arc<dyn Node>)).unwrap();
+
+        let root = RootNode::new();
+        let configure = move |context: &HandlerContext| {
+            context.enter_mode(config.clone(), "config").map(|_| None)
+        };
+        root.add_successor(CommandNode::new("configure",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(configure)),
+                                            vec![])).unwrap();
+        root.add_successor(command_node("show", PRIORITY_DEFAULT, false)).unwrap();
+        root
+    }
+
+    #[test]
+    fn nested_modes() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(mode_tree(&log));
+        assert!(parser.mode_name().is_none());
+        let outcomes = parser.execute_script("configure ; interface eth0");
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+        parser.reset();
+        assert_eq!(parser.mode_name().as_deref(), Some("config-if"));
+        assert_eq!(completion_names(&parser), vec!["mtu", "exit", "end"]);
+
+        assert!(parser.execute_script("mtu 1500 ; exit").iter().all(|o| o.result.is_ok()));
+        assert_eq!(*log.lock().unwrap(), vec!["1500"]);
+        parser.reset();
+        assert_eq!(parser.mode_name().as_deref(), Some("config"));
+        assert!(parser.parse("hostname").is_ok());
+        parser.reset();
+        assert!(parser.parse("show").is_err());
+
+        assert!(parser.execute_script("interface eth1 ; end").iter().all(|o| o.result.is_ok()));
+        parser.reset();
+        assert!(parser.mode_name().is_none());
+        assert!(parser.parse("show").is_ok());
+        assert_eq!(parser.modes().depth(), 0);
+    }
+
+    #[test]
+    fn execute_lines_follows_modes() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut parser = Parser::new(mode_tree(&log));
+        assert!(parser.execute_script("configure").iter().all(|o| o.result.is_ok()));
+
+        // `hostname` is only in the mode outside `interface`.
+        match parser.execute_lines(&["interface eth0", "hostname"]) {
+            Err(errors) => assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), vec![2]),
+            Ok(_) => panic!(),
+        }
+        assert_eq!(parser.mode_name().as_deref(), Some("config"));
+
+        let lines = ["interface eth0", "mtu 9000", "exit", "hostname", "interface eth1", "end"];
+        let outcomes = parser.execute_lines(&lines).unwrap();
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+        assert_eq!(*log.lock().unwrap(), vec!["9000"]);
+        assert_eq!(parser.modes().depth(), 0);
+    }
+
+    #[test]
+    fn mode_hooks_run_on_every_transition() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let config = RootNode::new();
+        builtins::install_mode_commands(&(config.clone() as Arc<dyn Node>)).unwrap();
+        let enter_config = move |context: &HandlerContext| {
+            context.enter_mode(config.clone(), "config").map(|_| None)
+        };
+        let mut configure = CommandNode::new("configure",
+                                             PRIORITY_DEFAULT,
+                                             false,
+                                             None,
+                                             Some(Box::new(enter_config)),
+                                             vec![]);
+        let (enter_log, exit_log) = (log.clone(), log.clone());
+        let command = Arc::get_mut(&mut configure).unwrap();
+        command.set_on_enter(Box::new(move |context| {
+            enter_log.lock().unwrap().push(format!("enter {}", context.node().name()));
+        }));
+        command.set_on_exit(Box::new(move |context| {
+            exit_log.lock().unwrap().push(format!("exit {}", context.node().name()));
+        }));
+        let root = RootNode::new();
+        root.add_successor(configure).unwrap();
+
+        // Entered by the handler and left with `exit`.
+        let mut parser = Parser::new(root);
+        assert!(parser.execute_script("configure ; exit").iter().all(|o| o.result.is_ok()));
+        assert_eq!(*log.lock().unwrap(), vec!["enter configure", "exit configure"]);
+
+        // Left with `end`, with `Parser::exit_mode` and on the stack.
+        assert!(parser.execute_script("configure ; end").iter().all(|o| o.result.is_ok()));
+        assert_eq!(log.lock().unwrap().len(), 4);
+        assert!(parser.execute_script("configure").iter().all(|o| o.result.is_ok()));
+        parser.exit_mode();
+        assert_eq!(parser.modes().depth(), 0);
+        assert_eq!(log.lock().unwrap().len(), 6);
+        assert!(parser.execute_script("configure").iter().all(|o| o.result.is_ok()));
+        assert_eq!(parser.modes().pop().unwrap().name(), "config");
+        assert_eq!(log.lock().unwrap()[7], "exit configure");
+    }
+
+    fn negation_tree(log: &Arc<Mutex<Vec<String>>>) -> Arc<RootNode> {
+        let mut tree = CommandTree::new();
+        let log = log.clone();
+        tree.command(Command::new("shutdown")
+                         .negatable(true)
+                         .handler(move |context| {
+                             log.lock().unwrap().push(format!("negated {}", context.negated()));
+                             Ok(None)
+                         })
+                         .finalize());
+        tree.command(Command::new("reload").finalize());
+        tree.command(Command::new("ip").finalize());
+        tree.command(Command::new("ip route").negatable(true).finalize());
+        tree.negation("");
+        tree.finalize().unwrap()
+    }
+
+    #[test]
+    fn negated_commands() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let root = negation_tree(&log);
+        for line in ["shutdown", "no shutdown"] {
+            let mut parser = Parser::new(root.clone());
+            assert!(parser.parse(line).is_ok());
+            assert!(parser.execute().is_ok());
+        }
+        assert_eq!(*log.lock().unwrap(), vec!["negated false", "negated true"]);
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("no ip route").is_ok());
+        assert!(parser.negated());
+
+        let mut parser = Parser::new(root);
+        match parser.parse("no reload") {
+            Err(error @ ParseError::NotNegatable { .. }) => {
+                assert_eq!(error.to_string(), "command 'reload' can't be negated")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn negation_completes_negatable_commands() {
+        let root = negation_tree(&Arc::new(Mutex::new(vec![])));
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("no").is_ok());
+        let completions = parser.complete(None);
+        let names = completions.iter().map(|c| c.help_symbol.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["shutdown", "ip"]);
+    }
+
+    #[test]
+    fn negation_of_cyclic_commands() {
+        let root = RootNode::new();
+        let repeat = command_node("repeat", PRIORITY_DEFAULT, false);
+        repeat.add_successor(repeat.clone()).unwrap();
+        root.add_successor(repeat).unwrap();
+        let tree: Arc<dyn Node> = root.clone();
+        root.add_successor(NegationNode::new("no", tree)).unwrap();
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("no").is_ok());
+        assert!(parser.complete(None).is_empty());
+        parser.reset();
+        assert!(matches!(parser.parse("no repeat"), Err(ParseError::NotNegatable { .. })));
+    }
+
+    #[test]
+    fn handlers_time_out() {
+        let mut tree = CommandTree::new();
+        for (name, sleep) in [("slow", 1000), ("fast", 0)] {
+            tree.command(Command::new(name)
+                             .timeout(Duration::from_millis(100))
+                             .handler(move |_| {
+                                 thread::sleep(Duration::from_millis(sleep));
+                                 Ok(Some("done".to_string()))
+                             })
+                             .finalize());
+        }
+        tree.command(Command::new("broken")
+                         .timeout(Duration::from_millis(100))
+                         .handler(|_| panic!("broken"))
+                         .finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("slow").is_ok());
+        match parser.execute() {
+            Err(error @ ExecuteError::Timeout { .. }) => {
+                assert_eq!(error.to_string(), "slow: timed out after 100ms")
+            }
+            _ => panic!(),
+        }
+
+        parser.reset();
+        assert!(parser.parse("fast").is_ok());
+        assert_eq!(parser.execute().unwrap().as_deref(), Some("done"));
+
+        parser.reset();
+        assert!(parser.parse("broken").is_ok());
+        match parser.execute() {
+            Err(error @ ExecuteError::Panicked { .. }) => {
+                assert_eq!(error.to_string(), "broken: handler panicked")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn post_hook_runs_on_timeout() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let mut slow = CommandNode::new("slow",
+                                        PRIORITY_DEFAULT,
+                                        false,
+                                        None,
+                                        Some(Box::new(|_: &HandlerContext| {
+                                            thread::sleep(Duration::from_millis(1000));
+                                            Ok(None)
+                                        })),
+                                        vec![]);
+        {
+            let ran = ran.clone();
+            let slow = Arc::get_mut(&mut slow).unwrap();
+            slow.set_timeout(Duration::from_millis(10));
+            slow.set_post_hook(Box::new(move |_, _| ran.store(true, Ordering::SeqCst)));
+        }
+
+        let mut parser = Parser::new(RootNode::new());
+        parser.commands.push(slow);
+        match parser.execute() {
+            Err(ExecuteError::Timeout { command, .. }) => assert_eq!(command, "slow"),
+            _ => panic!(),
+        }
+        assert!(ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn accepted_command_path() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").finalize());
+        tree.command(Command::new("show interfaces")
+                         .parameter(Parameter::new("name").required(true).finalize())
+                         .parameter(Parameter::new("mtu")
+                                        .kind(ParameterKind::Named)
+                                        .finalize())
+                         .parameter(Parameter::new("detail")
+                                        .kind(ParameterKind::Flag)
+                                        .hidden(true)
+                                        .finalize())
+                         .handler(|_| Ok(None))
+                         .finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("sh int detail eth0 mtu 1500").is_ok());
+        assert_eq!(parser.command_path(), "show interfaces detail eth0 mtu 1500");
+        let names = parser.accepted_nodes()
+                          .iter()
+                          .map(|n| n.name().to_string())
+                          .collect::<Vec<_>>();
+        assert_eq!(names, vec!["show", "interfaces", "detail", "name", "mtu", "mtu"]);
+    }
+
+    #[test]
+    fn handler_errors_name_the_command_path() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").finalize());
+        tree.command(Command::new("show interfaces")
+                         .parameter(Parameter::new("name").required(true).finalize())
+                         .handler(|context| {
+                             let name = context.values().get_str("name").unwrap_or_default();
+                             let cause = fmt::Error;
+                             let message = format!("no such interface {}", name);
+                             Err(HandlerError::with_source(&message, cause))
+                         })
+                         .finalize());
+        let mut parser = Parser::new(tree.finalize().unwrap());
+        assert!(parser.parse("show interfaces eth9").is_ok());
+        match parser.execute() {
+            Err(error @ ExecuteError::Handler { .. }) => {
+                assert_eq!(error.to_string(), "show interfaces: no such interface eth9");
+                let source = error.source().unwrap();
+                assert_eq!(source.to_string(), "no such interface eth9");
+                assert!(source.source().is_some());
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn execute_runs_accepted_handler() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("show").handler(recording_handler).finalize());
+        tree.command(Command::new("show route")
+                         .parameter(Parameter::new("prefix").required(true).finalize())
+                         .handler(recording_handler)
+                         .finalize());
+        tree.command(Command::new("reload").finalize());
+        let root = tree.finalize().unwrap();
+        HANDLED.with(|h| h.borrow_mut().clear());
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("show route 10.0.0.0/8").is_ok());
+        assert!(parser.execute().is_ok());
+        HANDLED.with(|h| assert_eq!(*h.borrow(), vec!["route"]));
+
+        // Verification runs before the handler.
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("show route").is_ok());
+        match parser.execute() {
+            Err(ExecuteError::Verify(errors)) => {
+                match &errors[..] {
+                    [VerifyError::MissingParameter { command, name, .. }] => {
+                        assert_eq!(command, "show route");
+                        assert_eq!(name, "prefix");
+                    }
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+        HANDLED.with(|h| assert_eq!(h.borrow().len(), 1));
+    }
+
+    #[test]
+    fn execute_without_handler() {
+        let mut tree = CommandTree::new();
+        tree.command(Command::new("reload").finalize());
+        let root = tree.finalize().unwrap();
+
+        let parser = Parser::new(root.clone());
+        match parser.execute() {
+            Err(error @ ExecuteError::NoCommandAccepted) => {
+                assert_eq!(error.to_string(), "No command has been accepted by the parser.")
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("reload").is_ok());
+        match parser.execute() {
+            Err(error @ ExecuteError::NoHandler(_)) => {
+                assert_eq!(error.to_string(), "command 'reload' has nothing to execute")
+            }
+            _ => panic!(),
         }
     }
 }
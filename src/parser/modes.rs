@@ -0,0 +1,215 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Modes
+//!
+//! Nested contexts like those of network devices, where
+//! `configure terminal` enters a configuration mode with commands
+//! of its own and `exit` goes back. Each `Parser` has a `ModeStack`
+//! of the modes that have been entered, and parses against the root
+//! of the innermost one once it is `reset`.
+//!
+//! Handlers enter and leave modes with `HandlerContext::enter_mode`,
+//! `exit_mode` and `end_mode`. A command can be given a `ModeChange`
+//! instead, which the `Parser` makes once its handler has succeeded,
+//! and which `Parser::execute_lines` follows while it checks lines.
+//! `builtins::install_mode_commands` adds `exit` and `end` commands
+//! which leave modes in this way. `Parser::reset_to_node` enters a
+//! node of the tree as a mode on the same stack.
+//!
+//! A mode entered by a command runs the `on_enter` hook of that
+//! command, and its `on_exit` hook when it is left, however that is.
+//!
+//! ```
+//! use commands::parser::nodes::*;
+//! use commands::parser::Parser;
+//!
+//! let config = RootNode::new();
+//! config.add_successor(CommandNode::new("hostname",
+//!                                       PRIORITY_DEFAULT,
+//!                                       false,
+//!                                       None,
+//!                                       None,
+//!                                       vec![])).unwrap();
+//!
+//! let mut parser = Parser::new(RootNode::new());
+//! parser.modes().push(config, "config");
+//! parser.reset();
+//! assert_eq!(parser.mode_name().as_deref(), Some("config"));
+//! assert!(parser.parse("hostname").is_ok());
+//! ```
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use parser::nodes::{CommandNode, HandlerContext, ModeHook, Node, RootNode};
+use parser::values::ParameterValues;
+
+/// A mode which has been entered, with the commands of its tree.
+#[derive(Clone,Debug)]
+pub struct Mode {
+    name: String,
+    root: Arc<dyn Node>,
+    /// The command which entered the mode, if any.
+    command: Option<EnteredBy>,
+}
+
+/// The command which entered a mode, and the line which led to it,
+/// which its hooks are given.
+#[derive(Clone,Debug)]
+struct EnteredBy {
+    command: Arc<dyn Node>,
+    nodes: Vec<Arc<dyn Node>>,
+    values: ParameterValues,
+}
+
+impl Mode {
+    /// Construct a mode called `name`, in which lines are parsed from
+    /// `root`. This is usually the root of a tree of its own, but it
+    /// may be a node of the tree, as with `Parser::reset_to_node`.
+    pub fn new(name: &str, root: Arc<dyn Node>) -> Self {
+        Mode {
+            name: name.to_string(),
+            root,
+            command: None,
+        }
+    }
+
+    /// Run the `on_enter` and `on_exit` hooks of `command` as this
+    /// mode is entered and left, given `context` of the line which
+    /// led to the mode.
+    pub fn with_command(mut self, command: Arc<dyn Node>, context: &HandlerContext) -> Self {
+        self.command = Some(EnteredBy {
+            command,
+            nodes: context.nodes().to_vec(),
+            values: context.values().clone(),
+        });
+        self
+    }
+
+    /// The name of the mode, as shown in a prompt like `config-if`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The node which lines are parsed from in the mode.
+    pub fn root(&self) -> &Arc<dyn Node> {
+        &self.root
+    }
+
+    /// The command which entered the mode, if it was given one with
+    /// `with_command`.
+    pub fn command(&self) -> Option<&Arc<dyn Node>> {
+        self.command.as_ref().map(|c| &c.command)
+    }
+
+    /// Run the hook which `hook` finds on the command which entered
+    /// this mode, if there is one.
+    fn run_hook(&self, hook: fn(&CommandNode) -> Option<&ModeHook>) {
+        if let Some(ref entered) = self.command {
+            if let Some(hook) = entered.command.as_command().and_then(hook) {
+                hook(&HandlerContext::new(&*entered.command, &entered.nodes, &entered.values));
+            }
+        }
+    }
+}
+
+/// How executing a command changes the modes, as set with
+/// `CommandNode::set_mode_change`.
+#[derive(Clone,Debug)]
+pub enum ModeChange {
+    /// Enter the mode called `name`, with the commands beneath `root`,
+    /// running the hooks of the command as for
+    /// `HandlerContext::enter_mode`.
+    Enter {
+        /// The name of the mode.
+        name: String,
+        /// The root of the tree of the mode.
+        root: Arc<RootNode>,
+    },
+    /// Leave the innermost mode.
+    Exit,
+    /// Leave every mode.
+    End,
+}
+
+/// The modes which have been entered, innermost last.
+///
+/// The stack can be changed through a shared reference, so that
+/// handlers can change it while the `Parser` executes them. However
+/// a mode is entered or left, the `on_enter` and `on_exit` hooks of
+/// the command which entered it are run.
+#[derive(Debug,Default)]
+pub struct ModeStack {
+    modes: RefCell<Vec<Mode>>,
+}
+
+impl ModeStack {
+    /// Construct an empty `ModeStack`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Enter the mode called `name`, with the commands beneath `root`.
+    pub fn push(&self, root: Arc<RootNode>, name: &str) {
+        self.enter(Mode::new(name, root));
+    }
+
+    /// Enter `mode`, running the `on_enter` hook of its command.
+    pub fn enter(&self, mode: Mode) {
+        mode.run_hook(CommandNode::on_enter);
+        self.modes.borrow_mut().push(mode);
+    }
+
+    /// Leave the innermost mode, returning it, if any has been entered.
+    /// The `on_exit` hook of its command is run.
+    pub fn pop(&self) -> Option<Mode> {
+        let mode = self.modes.borrow_mut().pop()?;
+        mode.run_hook(CommandNode::on_exit);
+        Some(mode)
+    }
+
+    /// Leave every mode, innermost first, returning whether any had
+    /// been entered.
+    pub fn clear(&self) -> bool {
+        let entered = self.depth() > 0;
+        while self.pop().is_some() {}
+        entered
+    }
+
+    /// The modes which have been entered, outermost first.
+    pub fn modes(&self) -> Vec<Mode> {
+        self.modes.borrow().clone()
+    }
+
+    /// The innermost mode, if any has been entered.
+    pub fn current(&self) -> Option<Mode> {
+        self.modes.borrow().last().cloned()
+    }
+
+    /// The number of modes which have been entered.
+    pub fn depth(&self) -> usize {
+        self.modes.borrow().len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stack() {
+        let modes = ModeStack::new();
+        assert!(modes.current().is_none());
+        assert!(!modes.clear());
+        modes.push(RootNode::new(), "config");
+        modes.push(RootNode::new(), "config-if");
+        assert_eq!(modes.depth(), 2);
+        assert_eq!(modes.current().unwrap().name(), "config-if");
+        assert_eq!(modes.pop().unwrap().name(), "config-if");
+        assert!(modes.clear());
+        assert!(modes.pop().is_none());
+    }
+}
@@ -10,7 +10,22 @@
 //! by the currently permissible set of commands and their
 //! parameters.
 
-use std::rc::Rc;
+use std::any::Any;
+use std::cell::{RefCell, RefMut};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use parser::completion::Completion;
+use parser::constraints::{MutuallyExclusiveGroup, RequiredGroup};
+use parser::modes::{Mode, ModeChange, ModeStack};
+use parser::values::ParameterValues;
+use tokenizer::Token;
+use util::levenshtein;
 
 /// Minimum priority.
 pub const PRIORITY_MINIMUM: i32 = -10000;
@@ -19,9 +34,255 @@ pub const PRIORITY_PARAMETER: i32 = -10;
 /// The default priority.
 pub const PRIORITY_DEFAULT: i32 = 0;
 
+/// The metadata key under which the role required to use
+/// a node is stored.
+pub const REQUIRED_ROLE_KEY: &str = "required-role";
+
+/// The handler of a command, called when it is executed.
+///
+/// Handlers may be closures, which can capture the state of the
+/// application, as long as it can be shared between threads.
+pub type Handler = Box<dyn Fn(&HandlerContext) -> HandlerResult + Send + Sync>;
+
+/// The result of running a `Handler`: any output for the frontend
+/// to show when it succeeds, or why it failed.
+pub type HandlerResult = Result<Option<String>, HandlerError>;
+
+/// Why a `Handler` failed, as a message for the user and, optionally,
+/// the error which caused it.
+#[derive(Clone,Debug)]
+pub struct HandlerError {
+    message: String,
+    source: Option<Arc<dyn Error + Send + Sync>>,
+}
+
+impl HandlerError {
+    /// Construct a new `HandlerError` with a message for the user.
+    pub fn new(message: &str) -> Self {
+        HandlerError {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    /// Construct a new `HandlerError` with a message for the user,
+    /// caused by `source`.
+    pub fn with_source<E: Error + Send + Sync + 'static>(message: &str, source: E) -> Self {
+        HandlerError {
+            message: message.to_string(),
+            source: Some(Arc::new(source)),
+        }
+    }
+
+    /// The message for the user.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<&str> for HandlerError {
+    fn from(message: &str) -> Self {
+        HandlerError::new(message)
+    }
+}
+
+impl From<String> for HandlerError {
+    fn from(message: String) -> Self {
+        HandlerError::new(&message)
+    }
+}
+
+impl Error for HandlerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|s| &**s as &(dyn Error + 'static))
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.message.fmt(f)
+    }
+}
+
+/// Why a node couldn't be modified: it has been frozen, along with
+/// the rest of its tree, by `Node::freeze`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct FrozenError {
+    /// The name of the frozen node.
+    pub node: String,
+}
+
+impl Error for FrozenError {}
+
+impl fmt::Display for FrozenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "node '{}' is frozen and can't be modified", self.node)
+    }
+}
+
+/// What a `Handler` is given when its command is executed.
+pub struct HandlerContext<'a> {
+    node: &'a dyn Node,
+    nodes: &'a [Arc<dyn Node>],
+    values: &'a ParameterValues,
+    state: Option<RefCell<&'a mut dyn Any>>,
+    negated: bool,
+    modes: Option<&'a ModeStack>,
+}
+
+impl<'a> HandlerContext<'a> {
+    /// Construct a new `HandlerContext`.
+    pub fn new(node: &'a dyn Node,
+               nodes: &'a [Arc<dyn Node>],
+               values: &'a ParameterValues)
+               -> Self {
+        HandlerContext {
+            node,
+            nodes,
+            values,
+            state: None,
+            negated: false,
+            modes: None,
+        }
+    }
+
+    /// Mark the command as negated, as by `no`.
+    pub fn with_negation(mut self, negated: bool) -> Self {
+        self.negated = negated;
+        self
+    }
+
+    /// Give the handler access to the state of the application.
+    pub fn with_state(mut self, state: &'a mut dyn Any) -> Self {
+        self.state = Some(RefCell::new(state));
+        self
+    }
+
+    /// Let the handler enter and leave the modes of `modes`.
+    pub fn with_modes(mut self, modes: &'a ModeStack) -> Self {
+        self.modes = Some(modes);
+        self
+    }
+
+    /// The command which is executed, or the node given to
+    /// a wrapper, like the command that `help` was asked about.
+    pub fn node(&self) -> &'a dyn Node {
+        self.node
+    }
+
+    /// All of the nodes which were accepted, in order.
+    pub fn nodes(&self) -> &'a [Arc<dyn Node>] {
+        self.nodes
+    }
+
+    /// The values given for the parameters.
+    pub fn values(&self) -> &'a ParameterValues {
+        self.values
+    }
+
+    /// Was the command negated by a `NegationNode`, as in `no shutdown`?
+    pub fn negated(&self) -> bool {
+        self.negated
+    }
+
+    /// The state of the application given to `Parser::execute_with`,
+    /// if it is an `S` and isn't already borrowed.
+    pub fn state<S: Any>(&self) -> Option<RefMut<'_, S>> {
+        let state = self.state.as_ref()?.try_borrow_mut().ok()?;
+        RefMut::filter_map(state, |s| s.downcast_mut::<S>()).ok()
+    }
+
+    /// Enter the mode called `name`, so that the commands beneath
+    /// `root` are parsed once the `Parser` is `reset` for the next line.
+    ///
+    /// The executed command is the one which entered the mode, so its
+    /// `on_enter` hook is run now and its `on_exit` hook once the mode
+    /// is left, both given what was parsed for this line.
+    pub fn enter_mode(&self, root: Arc<RootNode>, name: &str) -> Result<(), HandlerError> {
+        let modes = self.modes.ok_or("modes can't be entered here")?;
+        let mut mode = Mode::new(name, root);
+        if let Some(command) = self.nodes.iter().find(|n| ***n == *self.node) {
+            mode = mode.with_command(command.clone(), self);
+        }
+        modes.enter(mode);
+        Ok(())
+    }
+
+    /// Leave the current mode, returning whether one had been entered.
+    pub fn exit_mode(&self) -> bool {
+        self.modes.and_then(|m| m.pop()).is_some()
+    }
+
+    /// Leave every mode, returning whether any had been entered.
+    pub fn end_mode(&self) -> bool {
+        self.modes.is_some_and(|m| m.clear())
+    }
+}
+
+/// A predicate which decides whether a command is available.
+pub type Precondition = Box<dyn Fn() -> bool + Send + Sync>;
+
+/// A hook called with the name of a command and the time taken
+/// by its handler.
+pub type PostHook = Box<dyn Fn(&str, Duration) + Send + Sync>;
+
+/// A hook run when the `Parser` enters or leaves the mode of a
+/// command, given the command and the line which led to it.
+pub type ModeHook = Box<dyn Fn(&HandlerContext) + Send + Sync>;
+
+/// Application data attached to a command, such as a descriptor
+/// read by the layer which executes it.
+pub type Payload = Arc<dyn Any + Send + Sync>;
+
+/// Whether the names of nodes are matched against tokens with
+/// regard to case.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum CaseSensitivity {
+    /// `show` matches `show`, but not `SHOW`. This is the default.
+    #[default]
+    Sensitive,
+    /// `show` matches `show`, `Show` and `SHOW`.
+    Insensitive,
+}
+
+impl CaseSensitivity {
+    /// Does `text` start with `prefix`?
+    pub fn starts_with(self, text: &str, prefix: &str) -> bool {
+        match self {
+            CaseSensitivity::Sensitive => text.starts_with(prefix),
+            CaseSensitivity::Insensitive => {
+                text.to_lowercase().starts_with(&prefix.to_lowercase())
+            }
+        }
+    }
+
+    /// Are `a` and `b` the same?
+    pub fn equals(self, a: &str, b: &str) -> bool {
+        match self {
+            CaseSensitivity::Sensitive => a == b,
+            CaseSensitivity::Insensitive => a.to_lowercase() == b.to_lowercase(),
+        }
+    }
+}
+
 /// A node in the tree of commands and their parameters
 /// used by the `Parser`.
-pub trait Node {
+///
+/// Nodes are used as trait objects, `Arc<dyn Node>`, so this trait
+/// must remain object safe: its methods may be generic over
+/// lifetimes, but not over types. The kind of a node can be found
+/// without downcasting with `as_command`, `as_parameter` and the
+/// other `as_` methods.
+///
+/// Nodes must be `Send` and `Sync` so that a tree may be shared
+/// between threads. The interior mutability used while building
+/// a tree is guarded by locks for this reason, and handlers,
+/// preconditions and hooks must be `Send` and `Sync` as well.
+///
+/// The `Debug` output of a node names its successors rather
+/// than including them, as the tree may contain cycles. Use
+/// `dump_tree` to see the whole tree.
+pub trait Node: fmt::Debug + Send + Sync {
     /// The data describing this node.
     #[doc(hidden)]
     fn node_data(&self) -> &NodeFields;
@@ -29,14 +290,22 @@ pub trait Node {
     /// The text used to identify this node in help text.
     /// This is typically the node name, either in plain
     /// form or decorated for parameters.
-    fn help_symbol(&self) -> String {
-        // XXX:Make this return a &String.
-        self.node_data().help_symbol.clone()
+    fn help_symbol(&self) -> Arc<str> {
+        self.node_data().help_symbol.read().unwrap().clone()
     }
 
     /// Help text describing this node.
-    fn help_text(&self) -> &Option<String> {
-        &self.node_data().help_text
+    fn help_text(&self) -> Option<Arc<str>> {
+        self.node_data().help_text.read().unwrap().clone()
+    }
+
+    /// Replace the help text of this node.
+    ///
+    /// Fails if this node has been frozen.
+    fn set_help_text(&self, help_text: Option<String>) -> Result<(), FrozenError> {
+        self.node_data().check_mutable()?;
+        *self.node_data().help_text.write().unwrap() = help_text.map(Into::into);
+        Ok(())
     }
 
     /// Hidden nodes are still found for matching, but are
@@ -45,9 +314,10 @@ pub trait Node {
         self.node_data().hidden
     }
 
-    /// The name of this node.
-    fn name(&self) -> &String {
-        &self.node_data().name
+    /// The name of this node. This is shared rather than borrowed,
+    /// as a command may be renamed while it is shared.
+    fn name(&self) -> Arc<str> {
+        self.node_data().name.read().unwrap().clone()
     }
 
     /// This priority of this node during matching and completion.
@@ -57,15 +327,267 @@ pub trait Node {
 
     /// Nodes that are children of this node. Used to
     /// by the `Parser` during `advance`, `complete`, etc.
-    fn successors(&self) -> &Vec<Rc<Node>> {
-        &self.node_data().successors
+    ///
+    /// This is a snapshot, which isn't changed by successors
+    /// being added or removed later.
+    fn successors(&self) -> Arc<[Arc<dyn Node>]> {
+        self.node_data().successors.read().unwrap().clone()
+    }
+
+    /// Is this node at the end of a path through the tree, with
+    /// no successors?
+    fn is_leaf(&self) -> bool {
+        self.successors().is_empty()
+    }
+
+    /// Is this the root of a tree, as made by `RootNode::new` or
+    /// `RootNode::with_name`?
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    /// Add a node as a child of this node.
+    ///
+    /// Fails if this node has been frozen.
+    fn add_successor(&self, node: Arc<dyn Node>) -> Result<(), FrozenError> {
+        self.node_data().check_mutable()?;
+        self.node_data().push_successor(node);
+        Ok(())
+    }
+
+    /// Remove the successor named `name`, returning it, or `None`
+    /// when there is no such successor.
+    ///
+    /// Fails if this node has been frozen.
+    fn remove_successor(&self, name: &str) -> Result<Option<Arc<dyn Node>>, FrozenError> {
+        self.node_data().check_mutable()?;
+        let mut successors = self.node_data().successors.write().unwrap();
+        let index = successors.iter().position(|n| &*n.name() == name);
+        Ok(index.map(|index| {
+            let mut remaining = successors.to_vec();
+            let removed = remaining.remove(index);
+            *successors = remaining.into();
+            removed
+        }))
+    }
+
+    /// How the name of this node is compared with tokens.
+    fn case_sensitivity(&self) -> CaseSensitivity {
+        *self.node_data().case_sensitivity.read().unwrap()
+    }
+
+    /// Change how the name of this node is compared with tokens. Use
+    /// `RootNode::with_case_sensitivity` to change a whole tree.
+    ///
+    /// Fails if this node has been frozen.
+    fn set_case_sensitivity(&self, case_sensitivity: CaseSensitivity) -> Result<(), FrozenError> {
+        self.node_data().check_mutable()?;
+        *self.node_data().case_sensitivity.write().unwrap() = case_sensitivity;
+        Ok(())
+    }
+
+    /// Has this node been frozen by `freeze`?
+    fn frozen(&self) -> bool {
+        self.node_data().frozen.load(Ordering::SeqCst)
+    }
+
+    /// Freeze this node and every node which can be reached from it,
+    /// so that none of them can be modified any longer. Attempting
+    /// to add successors, parameters, constraints or metadata to a
+    /// frozen node, or to rename it, fails with a `FrozenError`.
+    ///
+    /// This guarantees that a tree doesn't change while it is being
+    /// used by a `Parser`. Use `CommandTree::freeze` to build a tree
+    /// which is frozen from the start.
+    fn freeze(&self) {
+        if self.node_data().frozen.swap(true, Ordering::SeqCst) {
+            // Already frozen, which also ends cycles in the tree.
+            return;
+        }
+        for successor in self.successors().iter() {
+            successor.freeze();
+        }
+        if let Some(command) = self.as_command() {
+            for parameter in command.parameters() {
+                parameter.freeze();
+            }
+        }
+        if let Some(wrapper) = self.as_wrapper() {
+            wrapper.root().freeze();
+        }
+        if let Some(subparser) = self.as_subparser() {
+            subparser.sub_root().freeze();
+        }
+    }
+
+    /// Does this node match `token`? The token may be shortened, so
+    /// by default, a node matches when its name starts with the token,
+    /// as compared with its `case_sensitivity`.
+    ///
+    /// This is used by the `Parser` to select the node for a token.
+    /// Node types which only accept certain values can refuse any
+    /// other tokens here.
+    fn matches(&self, token: &str) -> bool {
+        self.case_sensitivity().starts_with(&self.node_data().name.read().unwrap(), token)
+    }
+
+    /// Should this node be offered when completing `prefix`, the part
+    /// of a token typed so far? By default, it is when the node
+    /// `matches` the prefix.
+    ///
+    /// This is used by the `Parser` to filter the nodes which are
+    /// offered during completion. Nodes which only match a whole
+    /// token can still be offered for a prefix of it here.
+    fn completes(&self, prefix: &str) -> bool {
+        self.matches(prefix)
+    }
+
+    /// Provide the completion options for this node, given the
+    /// token being completed, if there is one.
+    ///
+    /// By default, a node completes to its name. Nodes with values
+    /// that can be listed, like paths or a fixed set of choices,
+    /// can override this to offer them.
+    fn complete<'t>(&self, token: Option<Token<'t>>) -> Completion<'t> {
+        // The options of a `Completion` are filtered by the token with
+        // regard to case, so a node which doesn't care about case has
+        // to leave that to `matches`.
+        let filter = match self.case_sensitivity() {
+            CaseSensitivity::Sensitive => token,
+            CaseSensitivity::Insensitive => None,
+        };
+        let mut completion = Completion::new(self.help_symbol().to_string(),
+                                             self.help_text().map(|t| t.to_string()),
+                                             filter,
+                                             true,
+                                             vec![&self.name()],
+                                             vec![]);
+        completion.token = token;
+        completion
+    }
+
+    /// Accept this node for `token`, recording any value that it
+    /// gives in `values`, under the name of the parameter.
+    ///
+    /// By default, nothing is recorded.
+    fn accept(&self, _token: &str, _values: &mut HashMap<String, Vec<String>>) {}
+
+    /// Whether or not this node is currently available. Nodes
+    /// which are not available are neither matched nor completed.
+    fn available(&self) -> bool {
+        true
+    }
+
+    /// Arbitrary key-value data attached to this node.
+    ///
+    /// This allows applications to annotate nodes, for example
+    /// with permissions or audit tags, without defining new
+    /// node types.
+    fn metadata(&self) -> RwLockReadGuard<'_, HashMap<String, String>> {
+        self.node_data().metadata.read().unwrap()
+    }
+
+    /// Attach a value to this node under `key`, replacing any
+    /// value which was previously stored there.
+    ///
+    /// Fails if this node has been frozen.
+    fn set_metadata(&self, key: &str, value: &str) -> Result<(), FrozenError> {
+        self.node_data().check_mutable()?;
+        self.node_data().metadata.write().unwrap().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Get the value attached to this node under `key`.
+    fn get_meta(&self, key: &str) -> Option<String> {
+        self.metadata().get(key).cloned()
+    }
+
+    /// Restrict this node to users with the given role.
+    ///
+    /// This is stored in the node's metadata and enforced by
+    /// the `PermissionChecker` given to the `Parser`.
+    fn set_required_role(&self, role: &str) -> Result<(), FrozenError> {
+        self.set_metadata(REQUIRED_ROLE_KEY, role)
+    }
+
+    /// The role required to use this node, if any.
+    fn required_role(&self) -> Option<String> {
+        self.get_meta(REQUIRED_ROLE_KEY)
+    }
+
+    /// This node as a `CommandNode`, if it is one.
+    fn as_command(&self) -> Option<&CommandNode> {
+        None
+    }
+
+    /// This node as a shared `CommandNode`, if it is one, as for
+    /// keeping the command that `as_command` gives.
+    fn into_command(self: Arc<Self>) -> Option<Arc<CommandNode>> {
+        None
+    }
+
+    /// This node as a `WrapperNode`, if it is one.
+    fn as_wrapper(&self) -> Option<&WrapperNode> {
+        None
+    }
+
+    /// This node as a `SubparserNode`, if it is one.
+    fn as_subparser(&self) -> Option<&SubparserNode> {
+        None
+    }
+
+    /// This node as a `NegationNode`, if it is one.
+    fn as_negation(&self) -> Option<&NegationNode> {
+        None
+    }
+
+    /// This node as a `PositionalCommandNode`, if it is one.
+    fn as_positional_command(&self) -> Option<&PositionalCommandNode> {
+        None
+    }
+
+    /// This node as a `ParameterNameNode`, if it is one.
+    fn as_parameter_name(&self) -> Option<&ParameterNameNode> {
+        None
+    }
+
+    /// This node as a `RepeatableNode`, if it is one.
+    fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+        None
+    }
+
+    /// This node as a `ParameterNode`, if it is one.
+    fn as_parameter(&self) -> Option<&dyn ParameterNode> {
+        None
+    }
+}
+
+impl<'a> dyn Node + 'a {
+    /// The address of this node, used as its identity.
+    ///
+    /// Only the data pointer is used: a pointer to a trait object
+    /// also carries a vtable pointer, which may differ for the same
+    /// node depending on how the trait object was created.
+    fn address(&self) -> *const u8 {
+        self as *const (dyn Node + 'a) as *const u8
     }
 }
 
-impl PartialEq for Node {
-    /// Nodes are equal based on pointer equality.
+impl<'a> PartialEq for dyn Node + 'a {
+    /// Nodes are equal based on identity rather than structure:
+    /// two `Arc` handles are equal when they refer to the same node.
     fn eq(&self, other: &Self) -> bool {
-        self as *const _ == other as *const _
+        self.address() == other.address()
+    }
+}
+
+impl<'a> Eq for dyn Node + 'a {}
+
+impl<'a> Hash for dyn Node + 'a {
+    /// Nodes hash by identity, consistently with `eq`. This allows
+    /// an `Arc<dyn Node>` to be used as a key to store per-node state.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state)
     }
 }
 
@@ -73,19 +595,74 @@ impl PartialEq for Node {
 #[doc(hidden)]
 pub struct NodeFields {
     /// The name of this node.
-    name: String,
+    name: RwLock<Arc<str>>,
     /// The text used to identify this node in help text.
     /// This is typically the node name, either in plain
     /// form or decorated for parameters.
-    help_symbol: String,
+    help_symbol: RwLock<Arc<str>>,
     /// Help text describing this node.
-    help_text: Option<String>,
+    help_text: RwLock<Option<Arc<str>>>,
     /// Hidden nodes are not completed. This doesn't modify matching.
     hidden: bool,
     /// Match and complete priority.
     priority: i32,
     /// Possible successor nodes. Collected while building.
-    successors: Vec<Rc<Node>>,
+    successors: RwLock<Arc<[Arc<dyn Node>]>>,
+    /// Application data attached to this node.
+    metadata: RwLock<HashMap<String, String>>,
+    /// Set once the tree containing this node has been frozen.
+    frozen: AtomicBool,
+    /// How the name of this node is compared with tokens.
+    case_sensitivity: RwLock<CaseSensitivity>,
+}
+
+impl NodeFields {
+    /// Fields for a new, unfrozen and case sensitive node
+    /// without any metadata.
+    fn new(name: &str,
+           help_symbol: String,
+           help_text: Option<String>,
+           hidden: bool,
+           priority: i32,
+           successors: Vec<Arc<dyn Node>>)
+           -> Self {
+        NodeFields {
+            name: RwLock::new(name.into()),
+            help_symbol: RwLock::new(help_symbol.into()),
+            help_text: RwLock::new(help_text.map(Into::into)),
+            hidden,
+            priority,
+            successors: RwLock::new(successors.into()),
+            metadata: RwLock::new(HashMap::new()),
+            frozen: AtomicBool::new(false),
+            case_sensitivity: RwLock::new(CaseSensitivity::Sensitive),
+        }
+    }
+
+    /// Fail if this node has been frozen and so may not be modified.
+    fn check_mutable(&self) -> Result<(), FrozenError> {
+        if self.frozen.load(Ordering::SeqCst) {
+            return Err(FrozenError { node: self.name.read().unwrap().to_string() });
+        }
+        Ok(())
+    }
+
+    /// Give this node a new name, and the `help_symbol` made for it.
+    fn rename(&self, new_name: &str, help_symbol: String) -> Result<(), FrozenError> {
+        self.check_mutable()?;
+        *self.name.write().unwrap() = new_name.into();
+        *self.help_symbol.write().unwrap() = help_symbol.into();
+        Ok(())
+    }
+
+    /// Add `node` to the end of the successors, replacing the
+    /// snapshot handed out by `successors`.
+    fn push_successor(&self, node: Arc<dyn Node>) {
+        let mut successors = self.successors.write().unwrap();
+        let mut extended = successors.to_vec();
+        extended.push(node);
+        *successors = extended.into();
+    }
 }
 
 /// The root of a command tree.
@@ -97,22 +674,142 @@ pub struct NodeFields {
 /// ```
 pub struct RootNode {
     node_fields: NodeFields,
+    fallback: RwLock<Option<Arc<CommandNode>>>,
 }
 
 impl RootNode {
     /// Create a new `RootNode`
-    pub fn new() -> Rc<Self> {
-        Rc::new(RootNode {
-            node_fields: NodeFields {
-                name: "__root__".to_string(),
-                help_symbol: "".to_string(),
-                help_text: None,
-                hidden: false,
-                priority: PRIORITY_DEFAULT,
-                successors: vec![],
-            },
+    pub fn new() -> Arc<Self> {
+        RootNode::with_name("__root__")
+    }
+
+    /// Create a new `RootNode` called `name`, as for the tree of a
+    /// `SubparserNode`, so that it can be told apart from the root of
+    /// the tree it is part of.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    ///
+    /// let root = RootNode::with_name("git");
+    /// assert_eq!(&*root.name(), "git");
+    /// assert!(root.is_root());
+    /// ```
+    pub fn with_name(name: &str) -> Arc<Self> {
+        Arc::new(RootNode {
+            node_fields: NodeFields::new(name,
+                                         "".to_string(),
+                                         None,
+                                         false,
+                                         PRIORITY_DEFAULT,
+                                         vec![]),
+            fallback: RwLock::new(None),
         })
     }
+
+    /// The command which is accepted when the first token of a line
+    /// matches none of the successors, if one has been set.
+    pub fn fallback(&self) -> Option<Arc<CommandNode>> {
+        self.fallback.read().unwrap().clone()
+    }
+
+    /// Set the command to accept, as if at `PRIORITY_MINIMUM`, when
+    /// the first token of a line matches none of the successors, like
+    /// an implicit `eval` for a bare expression.
+    ///
+    /// The command isn't a successor, so it is never completed. The
+    /// token which matched nothing is given to its first positional
+    /// parameter, which should be repeatable to take the whole line.
+    /// A `Parser` can be told not to use it with `set_use_fallback`.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use commands::parser::nodes::*;
+    /// use commands::parser::Parser;
+    ///
+    /// let expression = SimpleParameterNode::new("expression",
+    ///                                           None,
+    ///                                           false,
+    ///                                           PRIORITY_PARAMETER,
+    ///                                           vec![],
+    ///                                           true,
+    ///                                           None,
+    ///                                           true);
+    /// let root = RootNode::new();
+    /// let eval = |_: &HandlerContext| Ok(None);
+    /// root.set_fallback(CommandNode::new("eval",
+    ///                                    PRIORITY_MINIMUM,
+    ///                                    false,
+    ///                                    None,
+    ///                                    Some(Box::new(eval)),
+    ///                                    vec![Arc::new(expression)])).unwrap();
+    ///
+    /// let mut parser = Parser::new(root);
+    /// parser.parse("1 + 2").unwrap();
+    /// assert_eq!(parser.values().get_all("expression").len(), 3);
+    /// ```
+    pub fn set_fallback(&self, command: Arc<CommandNode>) -> Result<(), FrozenError> {
+        self.node_data().check_mutable()?;
+        *self.fallback.write().unwrap() = Some(command);
+        Ok(())
+    }
+
+    /// Set the `case_sensitivity` of every node which can be reached
+    /// from this root, returning the root.
+    ///
+    /// Nodes which are added to the tree afterwards keep their own
+    /// setting, so this is best done once the tree is complete. It
+    /// fails, leaving the tree as it was, if any of its nodes has
+    /// been frozen.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    ///
+    /// let root = RootNode::new();
+    /// root.add_successor(CommandNode::new("show",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     None,
+    ///                                     vec![])).unwrap();
+    /// let root = root.with_case_sensitivity(CaseSensitivity::Insensitive).unwrap();
+    /// assert!(root.successors()[0].matches("SH"));
+    /// ```
+    pub fn with_case_sensitivity(self: Arc<Self>,
+                                 case_sensitivity: CaseSensitivity)
+                                 -> Result<Arc<Self>, FrozenError> {
+        let root: Arc<dyn Node> = self.clone();
+        let mut nodes = HashSet::new();
+        collect_tree(&root, &mut nodes);
+        // Check the whole tree first, so that nothing is changed when
+        // part of it has been frozen.
+        if let Some(node) = nodes.iter().find(|n| n.frozen()) {
+            return Err(FrozenError { node: node.name().to_string() });
+        }
+        for node in nodes {
+            node.set_case_sensitivity(case_sensitivity)?;
+        }
+        Ok(self)
+    }
+}
+
+/// Collect every node which can be reached from `node`, including
+/// parameters and the trees of subparsers, into `seen`.
+fn collect_tree(node: &Arc<dyn Node>, seen: &mut HashSet<Arc<dyn Node>>) {
+    if !seen.insert(node.clone()) {
+        return;
+    }
+    for successor in node.successors().iter() {
+        collect_tree(successor, seen);
+    }
+    if let Some(command) = node.as_command() {
+        for parameter in command.parameters() {
+            collect_tree(&(parameter as Arc<dyn Node>), seen);
+        }
+    }
+    if let Some(subparser) = node.as_subparser() {
+        let sub_root: Arc<dyn Node> = subparser.sub_root().clone();
+        collect_tree(&sub_root, seen);
+    }
 }
 
 impl Node for RootNode {
@@ -120,6 +817,33 @@ impl Node for RootNode {
     fn node_data(&self) -> &NodeFields {
         &self.node_fields
     }
+
+    /// The root of a tree never matches a token.
+    fn matches(&self, _token: &str) -> bool {
+        false
+    }
+
+    fn is_root(&self) -> bool {
+        true
+    }
+
+    /// The fallback command is frozen along with the successors.
+    fn freeze(&self) {
+        if self.node_data().frozen.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for successor in self.successors().iter() {
+            successor.freeze();
+        }
+        if let Some(fallback) = self.fallback() {
+            fallback.freeze();
+        }
+    }
+
+    /// The root of a tree has nothing to complete.
+    fn complete<'t>(&self, token: Option<Token<'t>>) -> Completion<'t> {
+        Completion::new(self.help_symbol().to_string(), None, token, true, vec![], vec![])
+    }
 }
 
 /// A node representing a command.
@@ -129,32 +853,74 @@ pub struct CommandNode {
 }
 
 struct CommandNodeFields {
-    handler: Option<fn(&node: Node) -> ()>,
-    parameters: Vec<Rc<ParameterNode>>,
+    handler: Option<Handler>,
+    parameters: RwLock<Vec<Arc<dyn ParameterNode>>>,
+    precondition: Option<Precondition>,
+    post_hook: Option<PostHook>,
+    on_enter: Option<ModeHook>,
+    on_exit: Option<ModeHook>,
+    mode_change: Option<ModeChange>,
+    timeout: Option<Duration>,
+    payload: Option<Payload>,
+    deprecated: Option<String>,
+    negatable: bool,
+    /// Whether this is the command of a `PositionalCommandNode`.
+    positional: bool,
+    exclusive_groups: RwLock<Vec<Arc<MutuallyExclusiveGroup>>>,
+    required_groups: RwLock<Vec<Arc<RequiredGroup>>>,
+    disabled: AtomicBool,
 }
 
 impl CommandNode {
     /// Construct a new `CommandNode`.
+    ///
+    /// The `parameters` are registered as with `add_parameter`.
     pub fn new(name: &str,
                priority: i32,
                hidden: bool,
                help_text: Option<String>,
-               handler: Option<fn(&node: Node) -> ()>)
-               -> Rc<Self> {
-        Rc::new(CommandNode {
-            node_fields: NodeFields {
-                successors: vec![],
-                name: name.to_string(),
-                priority: priority,
-                help_symbol: name.to_string(),
-                help_text: help_text,
-                hidden: hidden,
-            },
+               handler: Option<Handler>,
+               parameters: Vec<Arc<dyn ParameterNode>>)
+               -> Arc<Self> {
+        Arc::new(CommandNode::unshared(name, priority, hidden, help_text, handler, parameters))
+    }
+
+    fn unshared(name: &str,
+                priority: i32,
+                hidden: bool,
+                help_text: Option<String>,
+                handler: Option<Handler>,
+                parameters: Vec<Arc<dyn ParameterNode>>)
+                -> Self {
+        let command = CommandNode {
+            node_fields: NodeFields::new(name,
+                                         name.to_string(),
+                                         help_text,
+                                         hidden,
+                                         priority,
+                                         vec![]),
             command_fields: CommandNodeFields {
-                handler: handler,
-                parameters: vec![],
+                handler,
+                parameters: RwLock::new(vec![]),
+                precondition: None,
+                post_hook: None,
+                on_enter: None,
+                on_exit: None,
+                mode_change: None,
+                timeout: None,
+                payload: None,
+                deprecated: None,
+                negatable: false,
+                positional: false,
+                exclusive_groups: RwLock::new(vec![]),
+                required_groups: RwLock::new(vec![]),
+                disabled: AtomicBool::new(false),
             },
-        })
+        };
+        for parameter in parameters {
+            command.register_parameter(parameter);
+        }
+        command
     }
 }
 impl Node for CommandNode {
@@ -162,289 +928,3089 @@ impl Node for CommandNode {
     fn node_data(&self) -> &NodeFields {
         &self.node_fields
     }
-}
 
-impl CommandNode {
-    /// The handler which is executed once this node has been accepted.
-    pub fn handler(&self) -> Option<fn(&node: Node) -> ()> {
-        self.command_fields.handler
+    /// A command is available when it has no precondition or
+    /// when its precondition is currently satisfied.
+    fn available(&self) -> bool {
+        match self.command_fields.precondition {
+            Some(ref precondition) => precondition(),
+            None => true,
+        }
     }
 
-    /// Get the parameter nodes for this command.
-    pub fn parameters(&self) -> &Vec<Rc<ParameterNode>> {
-        &self.command_fields.parameters
+    fn as_command(&self) -> Option<&CommandNode> {
+        Some(self)
     }
-}
 
-/// A wrapper node wraps another command.
-///
-/// This is used for the help command so that it can complete
-/// normal commands.
-///
-/// The `successors` will be those of the wrapped node.
-pub struct WrapperNode {
-    node_fields: NodeFields,
-    #[allow(dead_code)]
-    command_fields: CommandNodeFields,
-    root: Rc<Node>,
+    fn into_command(self: Arc<Self>) -> Option<Arc<CommandNode>> {
+        Some(self)
+    }
 }
 
-impl Node for WrapperNode {
-    #[doc(hidden)]
-    fn node_data(&self) -> &NodeFields {
-        &self.node_fields
+impl CommandNode {
+    /// The handler which is executed once this node has been accepted.
+    pub fn handler(&self) -> Option<&Handler> {
+        self.command_fields.handler.as_ref()
     }
 
-    fn successors(&self) -> &Vec<Rc<Node>> {
-        self.root.successors()
+    /// Gate the availability of this command on a predicate.
+    ///
+    /// The predicate is evaluated each time the command is
+    /// matched or completed, so it may depend on application
+    /// state that changes over time. When it returns `false`,
+    /// the command is not offered during completion and the
+    /// `Parser` rejects it with `ParseError::CommandUnavailable`.
+    pub fn set_precondition(&mut self, precondition: Precondition) {
+        self.command_fields.precondition = Some(precondition);
     }
-}
 
-/// A repeatable node is an internal helper for representing
-/// nodes that can be repeated, like some parameters.
-pub trait RepeatableNode: Node {
-    /// Internal data for a repeatable node.
-    #[doc(hidden)]
-    fn repeatable_data(&self) -> &RepeatableNodeFields;
+    /// Disable this command until it is `enable`d again, as while
+    /// the feature it belongs to is switched off.
+    ///
+    /// Unlike `hidden`, this can change after the tree has been
+    /// built, even once it is frozen. A disabled command is not
+    /// offered during completion, the `Parser` rejects it with
+    /// `ParseError::DisabledCommand`, and a command which was
+    /// disabled after it was parsed fails to execute with
+    /// `ExecuteError::DisabledCommand`.
+    pub fn disable(&self) {
+        self.command_fields.disabled.store(true, Ordering::SeqCst);
+    }
 
-    /// Whether or not this node can be repeated. A repeated
-    /// node can be `accept`ed multiple times.
-    fn repeatable(&self) -> bool {
-        self.repeatable_data().repeatable
+    /// Enable this command again after it was `disable`d. Commands
+    /// are enabled when they are constructed.
+    pub fn enable(&self) {
+        self.command_fields.disabled.store(false, Ordering::SeqCst);
     }
 
-    /// If present, this node will no longer be `acceptable`.
-    fn repeat_marker(&self) -> &Option<Rc<Node>> {
-        &self.repeatable_data().repeat_marker
+    /// Is this command enabled, as it is unless it was `disable`d?
+    pub fn enabled(&self) -> bool {
+        !self.command_fields.disabled.load(Ordering::SeqCst)
     }
-}
 
-/// The data for a repeatable node.
-#[doc(hidden)]
-pub struct RepeatableNodeFields {
-    repeatable: bool,
-    repeat_marker: Option<Rc<Node>>,
-}
+    /// The payload attached to this command, if there is one and
+    /// it is a `T`.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use commands::parser::nodes::*;
+    ///
+    /// struct Descriptor {
+    ///     audit_tag: &'static str,
+    /// }
+    ///
+    /// let mut show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+    /// Arc::get_mut(&mut show).unwrap().set_payload(Arc::new(Descriptor { audit_tag: "read" }));
+    /// assert_eq!(show.payload::<Descriptor>().unwrap().audit_tag, "read");
+    /// assert!(show.payload::<String>().is_none());
+    /// ```
+    pub fn payload<T: Any>(&self) -> Option<&T> {
+        self.command_fields.payload.as_ref().and_then(|p| p.downcast_ref::<T>())
+    }
 
-/// A node that represented the name portion of a named
-/// parameter.
-pub struct ParameterNameNode {
-    node_fields: NodeFields,
-    repeatable_fields: RepeatableNodeFields,
-    parameter: Rc<Node>,
-}
+    /// Attach application data to this command, replacing any
+    /// payload which it already had.
+    pub fn set_payload(&mut self, payload: Payload) {
+        self.command_fields.payload = Some(payload);
+    }
 
-impl Node for ParameterNameNode {
-    #[doc(hidden)]
-    fn node_data(&self) -> &NodeFields {
-        &self.node_fields
+    /// The deprecation message of this command, if it has been
+    /// deprecated.
+    pub fn deprecated(&self) -> Option<&str> {
+        self.command_fields.deprecated.as_deref()
     }
 
-    fn help_symbol(&self) -> String {
-        self.node_fields.name.clone() + " " + self.parameter.help_symbol().as_str()
+    /// Mark this command as deprecated. The `message` should say
+    /// what to use instead, like `"use 'show interfaces' instead"`.
+    ///
+    /// The command still works, but the `Parser` records a
+    /// `ParseWarning::DeprecatedCommand` when it is matched and
+    /// completions mark it as `[DEPRECATED]`.
+    pub fn set_deprecated(&mut self, message: &str) {
+        self.command_fields.deprecated = Some(message.to_string());
     }
-}
 
-impl RepeatableNode for ParameterNameNode {
-    #[doc(hidden)]
-    fn repeatable_data(&self) -> &RepeatableNodeFields {
-        &self.repeatable_fields
+    /// Rename this command, as when the commands of a plugin are
+    /// given names which don't clash with those of the application.
+    /// Its help symbol is made again for the new name.
+    ///
+    /// Fails if this node has been frozen.
+    pub fn rename(&self, new_name: &str) -> Result<(), FrozenError> {
+        self.node_fields.rename(new_name, self.help_symbol_for(new_name))
     }
-}
 
-/// Parameter nodes.
-pub trait ParameterNode: Node + RepeatableNode {
-    /// Internal data for a parameter node.
-    #[doc(hidden)]
-    fn parameter_data(&self) -> &ParameterNodeFields;
+    /// The help symbol of this command when it is called `name`.
+    fn help_symbol_for(&self, name: &str) -> String {
+        if self.command_fields.positional {
+            format!("<{}>", name)
+        } else {
+            name.to_string()
+        }
+    }
 
-    /// A `required` parameter must be supplied for the
-    /// command line being parsed to be valid.
-    fn required(&self) -> bool {
-        self.parameter_data().required
+    /// Whether this command may follow a `NegationNode`, as in
+    /// `no shutdown`.
+    pub fn negatable(&self) -> bool {
+        self.command_fields.negatable
     }
-}
 
-/// Data for parameter nodes.
-#[doc(hidden)]
-pub struct ParameterNodeFields {
-    required: bool,
+    /// Allow this command to follow a `NegationNode`.
+    pub fn set_negatable(&mut self, negatable: bool) {
+        self.command_fields.negatable = negatable;
+    }
+
+    /// The hook which is run after the handler returns.
+    pub fn post_hook(&self) -> Option<&PostHook> {
+        self.command_fields.post_hook.as_ref()
+    }
+
+    /// Run a hook each time the handler for this command returns.
+    ///
+    /// The hook is given the name of the command and the wall
+    /// time taken by the handler. This is intended for logging,
+    /// metrics and audit trails.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// use commands::parser::nodes::*;
+    ///
+    /// /// Record every command that is executed.
+    /// #[derive(Clone, Default)]
+    /// struct AuditLogger {
+    ///     entries: Arc<Mutex<Vec<(String, Duration)>>>,
+    /// }
+    ///
+    /// impl AuditLogger {
+    ///     fn hook(&self) -> PostHook {
+    ///         let entries = self.entries.clone();
+    ///         Box::new(move |name, elapsed| {
+    ///             entries.lock().unwrap().push((name.to_string(), elapsed))
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let logger = AuditLogger::default();
+    /// let mut command = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+    /// Arc::get_mut(&mut command).unwrap().set_post_hook(logger.hook());
+    /// ```
+    pub fn set_post_hook(&mut self, post_hook: PostHook) {
+        self.command_fields.post_hook = Some(post_hook);
+    }
+
+    /// How long the handler of this command may run, if limited.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.command_fields.timeout
+    }
+
+    /// Limit how long the handler of this command may run.
+    ///
+    /// The `Parser` then runs the handler on a thread of its own and
+    /// fails with `ExecuteError::Timeout` once `timeout` has passed.
+    /// The thread can't be stopped, so the handler runs on until it
+    /// returns and its result is discarded. As it may outlive the call
+    /// to `execute`, the handler isn't given the application state
+    /// or the modes.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.command_fields.timeout = Some(timeout);
+    }
+
+    /// The hook run when the parser enters the mode of this command.
+    pub fn on_enter(&self) -> Option<&ModeHook> {
+        self.command_fields.on_enter.as_ref()
+    }
+
+    /// Run a hook when the mode of this command is entered, as for
+    /// `interface eth0` in a device CLI, to set up the state which
+    /// the commands of the mode work on. The mode may be entered by
+    /// `Parser::reset_to_node`, by the `mode_change` of this command
+    /// or by its handler, with `HandlerContext::enter_mode`.
+    ///
+    /// The hook is given the values of the line which was parsed
+    /// before the mode was entered.
+    pub fn set_on_enter(&mut self, on_enter: ModeHook) {
+        self.command_fields.on_enter = Some(on_enter);
+    }
+
+    /// The hook run when the parser leaves the mode of this command.
+    pub fn on_exit(&self) -> Option<&ModeHook> {
+        self.command_fields.on_exit.as_ref()
+    }
+
+    /// Run a hook when the parser leaves the mode of this command,
+    /// for another mode or however its `ModeStack` is popped, to tear
+    /// down what the `on_enter` hook set up. It is given the same
+    /// values as the `on_enter` hook.
+    pub fn set_on_exit(&mut self, on_exit: ModeHook) {
+        self.command_fields.on_exit = Some(on_exit);
+    }
+
+    /// How executing this command changes the modes, if it does.
+    pub fn mode_change(&self) -> Option<&ModeChange> {
+        self.command_fields.mode_change.as_ref()
+    }
+
+    /// Change the modes with `mode_change` once the handler of this
+    /// command has succeeded. Unlike a handler which changes them
+    /// itself, this is known without running the handler, so that
+    /// `Parser::execute_lines` checks the lines after this one in the
+    /// modes which it changes to.
+    pub fn set_mode_change(&mut self, mode_change: ModeChange) {
+        self.command_fields.mode_change = Some(mode_change);
+    }
+
+    /// Get the parameter nodes for this command.
+    pub fn parameters(&self) -> Vec<Arc<dyn ParameterNode>> {
+        self.command_fields.parameters.read().unwrap().clone()
+    }
+
+    /// The number of parameters of this command.
+    pub fn param_count(&self) -> usize {
+        self.command_fields.parameters.read().unwrap().len()
+    }
+
+    /// The number of parameters of this command which are required.
+    pub fn required_param_count(&self) -> usize {
+        self.command_fields.parameters.read().unwrap().iter().filter(|p| p.required()).count()
+    }
+
+    /// A one-line synopsis of this command, like
+    /// `show <name> [verbose]`.
+    ///
+    /// This is the name of the command followed by the help symbol
+    /// of each of its parameters, in the order in which they were
+    /// registered. Named parameters are shown with their name, as
+    /// in `[count <count>]`. Hidden parameters are left out.
+    pub fn usage_string(&self) -> String {
+        let mut usage = self.name().to_string();
+        for parameter in self.parameters().iter().filter(|p| !p.hidden()) {
+            usage.push(' ');
+            if parameter.positional() || !parameter.takes_value() {
+                usage.push_str(&parameter.help_symbol());
+            } else {
+                let symbol = format!("{} {}", parameter.name(), parameter.help_symbol());
+                usage.push_str(&decorate_help_symbol(&symbol, parameter.required(), false));
+            }
+        }
+        usage
+    }
+
+    /// Register a parameter with this command.
+    ///
+    /// Flags are also added as successors of the command so that
+    /// the `Parser` can match them. Named parameters are given a
+    /// `ParameterNameNode`, as with `add_parameter_name`, carrying
+    /// the same help text and repeat settings as the parameter.
+    /// Positional parameters are bound by the `Parser` in the
+    /// order in which they were registered.
+    ///
+    /// Fails if this command has been frozen.
+    pub fn add_parameter(&self, parameter: Arc<dyn ParameterNode>) -> Result<(), FrozenError> {
+        self.node_fields.check_mutable()?;
+        self.register_parameter(parameter);
+        Ok(())
+    }
+
+    /// Register a parameter with this command, as with
+    /// `add_parameter`, without checking whether this command may be
+    /// modified.
+    fn register_parameter(&self, parameter: Arc<dyn ParameterNode>) {
+        if parameter.positional() {
+            self.command_fields.parameters.write().unwrap().push(parameter);
+        } else if parameter.takes_value() {
+            let name = ParameterNameNode::new(&parameter.name(),
+                                              parameter.repeatable(),
+                                              parameter.repeat_marker().clone(),
+                                              parameter.max_repetitions(),
+                                              parameter.help_text().map(|t| t.to_string()),
+                                              parameter.clone());
+            self.register_parameter_name(name);
+        } else {
+            self.node_fields.push_successor(parameter.clone());
+            self.command_fields.parameters.write().unwrap().push(parameter);
+        }
+    }
+
+    /// The groups of this command's parameters of which at most one
+    /// may be given.
+    pub fn exclusive_groups(&self) -> Vec<Arc<MutuallyExclusiveGroup>> {
+        self.command_fields.exclusive_groups.read().unwrap().clone()
+    }
+
+    /// Allow at most one of the parameters in `group` to be given.
+    pub fn add_exclusive_group(&self,
+                               group: Arc<MutuallyExclusiveGroup>)
+                               -> Result<(), FrozenError> {
+        self.node_fields.check_mutable()?;
+        self.command_fields.exclusive_groups.write().unwrap().push(group);
+        Ok(())
+    }
+
+    /// The groups of this command's parameters of which at least one
+    /// must be given.
+    pub fn required_groups(&self) -> Vec<Arc<RequiredGroup>> {
+        self.command_fields.required_groups.read().unwrap().clone()
+    }
+
+    /// Require at least one of the parameters in `group` to be given.
+    pub fn add_required_group(&self, group: Arc<RequiredGroup>) -> Result<(), FrozenError> {
+        self.node_fields.check_mutable()?;
+        self.command_fields.required_groups.write().unwrap().push(group);
+        Ok(())
+    }
+
+    /// Register a named parameter with this command, given the node
+    /// for its name.
+    ///
+    /// The name node becomes a successor of the command, while its
+    /// parameter is registered with the command so that it is
+    /// checked by `verify`.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use commands::parser::nodes::*;
+    ///
+    /// let count = Arc::new(NamedParameterNode::with_options("count",
+    ///                                                       None,
+    ///                                                       false,
+    ///                                                       PRIORITY_PARAMETER,
+    ///                                                       vec![],
+    ///                                                       false,
+    ///                                                       None,
+    ///                                                       false));
+    /// let ping = CommandNode::new("ping", PRIORITY_DEFAULT, false, None, None, vec![]);
+    /// ping.add_parameter_name(ParameterNameNode::new("count",
+    ///                                                false,
+    ///                                                None,
+    ///                                                None,
+    ///                                                Some("Number of pings.".to_string()),
+    ///                                                count)).unwrap();
+    /// ```
+    pub fn add_parameter_name(&self, name: Arc<ParameterNameNode>) -> Result<(), FrozenError> {
+        self.node_fields.check_mutable()?;
+        self.register_parameter_name(name);
+        Ok(())
+    }
+
+    /// Register a named parameter with this command, as with
+    /// `add_parameter_name`, without checking whether this command
+    /// may be modified.
+    fn register_parameter_name(&self, name: Arc<ParameterNameNode>) {
+        self.command_fields.parameters.write().unwrap().push(name.parameter().clone());
+        self.node_fields.push_successor(name);
+    }
 }
 
-/// A flag parameter node.
+/// A keyword within the syntax of a command.
 ///
-/// When implemented, this will only have a value of
-/// true when it is present.
-pub struct FlagParameterNode {
+/// Keywords are fixed words that structure a command line without
+/// being commands themselves, like the `to` in `rename <old> to <new>`.
+/// They have no handler or parameters and are never recorded as the
+/// accepted command, so they are treated as part of the syntax of
+/// the command that they follow.
+///
+/// ```
+/// use commands::parser::nodes::*;
+///
+/// let rename = CommandNode::new("rename", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// rename.add_successor(KeywordNode::new("to", PRIORITY_DEFAULT, false, None)).unwrap();
+/// ```
+pub struct KeywordNode {
     node_fields: NodeFields,
-    repeatable_fields: RepeatableNodeFields,
-    parameter_fields: ParameterNodeFields,
 }
 
-impl Node for FlagParameterNode {
+impl KeywordNode {
+    /// Construct a new `KeywordNode`.
+    pub fn new(name: &str, priority: i32, hidden: bool, help_text: Option<String>) -> Arc<Self> {
+        Arc::new(KeywordNode {
+            node_fields: NodeFields::new(name,
+                                         name.to_string(),
+                                         help_text,
+                                         hidden,
+                                         priority,
+                                         vec![]),
+        })
+    }
+}
+
+impl Node for KeywordNode {
+    #[doc(hidden)]
     fn node_data(&self) -> &NodeFields {
         &self.node_fields
     }
 }
 
-impl RepeatableNode for FlagParameterNode {
-    #[doc(hidden)]
-    fn repeatable_data(&self) -> &RepeatableNodeFields {
-        &self.repeatable_fields
+/// A wrapper node wraps another command.
+///
+/// This is used for the help command so that it can complete
+/// normal commands.
+///
+/// The `successors` will be those of the wrapped node.
+///
+/// A wrapper which wraps the root of its own tree, as the help
+/// command usually does, creates a reference cycle, so the tree
+/// will not be freed.
+///
+/// ```
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("show",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// let help = WrapperNode::new("help", Some("Show help.".to_string()), root.clone());
+/// assert_eq!(help.successors().len(), 1);
+/// ```
+pub struct WrapperNode {
+    node_fields: NodeFields,
+    handler: Option<Handler>,
+    root: Arc<dyn Node>,
+}
+
+impl WrapperNode {
+    /// Construct a new `WrapperNode` which wraps `root`.
+    ///
+    /// The wrapper has the default priority, is not hidden
+    /// and has no handler.
+    pub fn new(name: &str, help_text: Option<String>, root: Arc<dyn Node>) -> Arc<Self> {
+        WrapperNode::with_options(name, PRIORITY_DEFAULT, false, help_text, None, root)
+    }
+
+    /// Construct a new `WrapperNode` which wraps `root`,
+    /// specifying all of its options.
+    pub fn with_options(name: &str,
+                        priority: i32,
+                        hidden: bool,
+                        help_text: Option<String>,
+                        handler: Option<Handler>,
+                        root: Arc<dyn Node>)
+                        -> Arc<Self> {
+        Arc::new(WrapperNode {
+            node_fields: NodeFields::new(name,
+                                         name.to_string(),
+                                         help_text,
+                                         hidden,
+                                         priority,
+                                         vec![]),
+            handler,
+            root,
+        })
+    }
+
+    /// The handler which is executed once this node has been accepted.
+    pub fn handler(&self) -> Option<&Handler> {
+        self.handler.as_ref()
+    }
+
+    /// The node whose successors this node takes on.
+    pub fn root(&self) -> &Arc<dyn Node> {
+        &self.root
     }
 }
 
-impl ParameterNode for FlagParameterNode {
+impl Node for WrapperNode {
     #[doc(hidden)]
-    fn parameter_data(&self) -> &ParameterNodeFields {
-        &self.parameter_fields
+    fn node_data(&self) -> &NodeFields {
+        &self.node_fields
     }
-}
 
-impl FlagParameterNode {
-    /// Construct a new `FlagParameterNode`.
-    pub fn new(name: &str,
-               help_text: Option<String>,
-               hidden: bool,
-               priority: i32,
-               successors: Vec<Rc<Node>>,
-               repeatable: bool,
-               repeat_marker: Option<Rc<Node>>,
-               required: bool)
-               -> Self {
-        let help_symbol = String::from("<") + name +
-                          if repeatable {
-            ">..."
-        } else {
-            ">"
-        };
-        FlagParameterNode {
-            node_fields: NodeFields {
-                name: name.to_string(),
-                help_symbol: help_symbol,
-                help_text: help_text,
-                hidden: hidden,
-                priority: priority,
-                successors: successors,
-            },
-            repeatable_fields: RepeatableNodeFields {
-                repeatable: repeatable,
-                repeat_marker: repeat_marker,
-            },
-            parameter_fields: ParameterNodeFields { required: required },
-        }
+    fn successors(&self) -> Arc<[Arc<dyn Node>]> {
+        self.root.successors()
+    }
+
+    fn as_wrapper(&self) -> Option<&WrapperNode> {
+        Some(self)
     }
 }
 
-/// A named parameter node.
-pub struct NamedParameterNode {
+/// A node which leads into a separate command tree.
+///
+/// This allows a complete command line interface, with its own
+/// `RootNode`, to be embedded beneath a name in another one. Once
+/// the `Parser` accepts this node, it continues from the root of
+/// the other tree, so only the commands of that tree follow it.
+///
+/// ```
+/// use commands::parser::nodes::*;
+///
+/// let route = RootNode::new();
+/// route.add_successor(CommandNode::new("add",
+///                                      PRIORITY_DEFAULT,
+///                                      false,
+///                                      None,
+///                                      None,
+///                                      vec![])).unwrap();
+///
+/// let root = RootNode::new();
+/// root.add_successor(SubparserNode::new("route", route)).unwrap();
+/// ```
+pub struct SubparserNode {
     node_fields: NodeFields,
-    repeatable_fields: RepeatableNodeFields,
-    parameter_fields: ParameterNodeFields,
+    sub_root: Arc<RootNode>,
 }
 
-impl Node for NamedParameterNode {
+impl SubparserNode {
+    /// Construct a new `SubparserNode` leading into the tree
+    /// beneath `sub_root`.
+    pub fn new(name: &str, sub_root: Arc<RootNode>) -> Arc<Self> {
+        Arc::new(SubparserNode {
+            node_fields: NodeFields::new(name,
+                                         name.to_string(),
+                                         None,
+                                         false,
+                                         PRIORITY_DEFAULT,
+                                         vec![]),
+            sub_root,
+        })
+    }
+
+    /// The root of the tree which this node leads into.
+    pub fn sub_root(&self) -> &Arc<RootNode> {
+        &self.sub_root
+    }
+}
+
+impl Node for SubparserNode {
+    #[doc(hidden)]
     fn node_data(&self) -> &NodeFields {
         &self.node_fields
     }
+
+    fn successors(&self) -> Arc<[Arc<dyn Node>]> {
+        self.sub_root.successors()
+    }
+
+    fn as_subparser(&self) -> Option<&SubparserNode> {
+        Some(self)
+    }
 }
 
-impl RepeatableNode for NamedParameterNode {
-    #[doc(hidden)]
-    fn repeatable_data(&self) -> &RepeatableNodeFields {
-        &self.repeatable_fields
+/// A prefix which negates the command that follows it, as `no` does
+/// in `no shutdown`.
+///
+/// Like a `WrapperNode`, it takes on the successors of the node it
+/// wraps, but once the `Parser` has accepted it, only commands which
+/// are `negatable`, or which lead to one, can follow. The handler of
+/// the command is run as usual and finds that it was negated with
+/// `HandlerContext::negated`.
+///
+/// ```
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("shutdown",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// root.add_successor(NegationNode::new("no", root.clone())).unwrap();
+/// ```
+pub struct NegationNode {
+    node_fields: NodeFields,
+    root: Arc<dyn Node>,
+}
+
+impl NegationNode {
+    /// Construct a new `NegationNode` which negates the commands
+    /// following `root`.
+    pub fn new(name: &str, root: Arc<dyn Node>) -> Arc<Self> {
+        Arc::new(NegationNode {
+            node_fields: NodeFields::new(name,
+                                         name.to_string(),
+                                         Some("Negate a command.".to_string()),
+                                         false,
+                                         PRIORITY_DEFAULT,
+                                         vec![]),
+            root,
+        })
+    }
+
+    /// The node whose successors this node takes on.
+    pub fn root(&self) -> &Arc<dyn Node> {
+        &self.root
     }
 }
 
-impl ParameterNode for NamedParameterNode {
+impl Node for NegationNode {
     #[doc(hidden)]
-    fn parameter_data(&self) -> &ParameterNodeFields {
-        &self.parameter_fields
+    fn node_data(&self) -> &NodeFields {
+        &self.node_fields
+    }
+
+    fn successors(&self) -> Arc<[Arc<dyn Node>]> {
+        self.root.successors()
+    }
+
+    fn as_negation(&self) -> Option<&NegationNode> {
+        Some(self)
     }
 }
 
-impl NamedParameterNode {
-    /// Construct a new `NamedParameterNode`.
+/// Can `command` follow a `NegationNode`? It can when it is
+/// `negatable` or when one of its subcommands can, as `ip` can
+/// when `ip route` is negatable.
+pub fn negatable(command: &CommandNode) -> bool {
+    negatable_within(command, &mut HashSet::new())
+}
+
+/// `negatable`, skipping the commands in `seen`, which have been
+/// looked at already, so that cycles in the tree end.
+fn negatable_within(command: &CommandNode, seen: &mut HashSet<*const CommandNode>) -> bool {
+    if !seen.insert(command) {
+        return false;
+    }
+    command.negatable() || subcommands(command).iter().any(|c| negatable_within(c, seen))
+}
+
+/// A command which matches any token, for command lines where
+/// position rather than a keyword says what a token is, as in
+/// `<verb> <resource>`.
+///
+/// The token is recorded as the value of the node's name, so the
+/// handler finds it with `ParameterValues::get_str`, and the node
+/// otherwise acts as the `CommandNode` it holds, with its handler,
+/// parameters and subcommands. When a named node also matches the
+/// token, the `Parser` chooses the named node.
+///
+/// ```
+/// use commands::parser::nodes::*;
+/// use commands::parser::Parser;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("show",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// root.add_successor(PositionalCommandNode::new("verb",
+///                                               PRIORITY_DEFAULT,
+///                                               None,
+///                                               None,
+///                                               vec![])).unwrap();
+///
+/// let mut parser = Parser::new(root);
+/// parser.parse("restart").unwrap();
+/// assert_eq!(parser.values().get_str("verb"), Some("restart"));
+/// ```
+pub struct PositionalCommandNode {
+    command: Arc<CommandNode>,
+}
+
+impl PositionalCommandNode {
+    /// Construct a new `PositionalCommandNode`. The help symbol is
+    /// the name in angle brackets, like that of a parameter.
     pub fn new(name: &str,
-               help_text: Option<String>,
-               hidden: bool,
                priority: i32,
-               successors: Vec<Rc<Node>>,
-               repeatable: bool,
-               repeat_marker: Option<Rc<Node>>,
-               required: bool)
-               -> Self {
-        NamedParameterNode {
-            node_fields: NodeFields {
-                name: name.to_string(),
-                help_symbol: name.to_string(),
-                help_text: help_text,
-                hidden: hidden,
-                priority: priority,
-                successors: successors,
-            },
-            repeatable_fields: RepeatableNodeFields {
-                repeatable: repeatable,
-                repeat_marker: repeat_marker,
-            },
-            parameter_fields: ParameterNodeFields { required: required },
-        }
+               help_text: Option<String>,
+               handler: Option<Handler>,
+               parameters: Vec<Arc<dyn ParameterNode>>)
+               -> Arc<Self> {
+        let mut command = CommandNode::unshared(name,
+                                                priority,
+                                                false,
+                                                help_text,
+                                                handler,
+                                                parameters);
+        command.command_fields.positional = true;
+        *command.node_fields.help_symbol.get_mut().unwrap() = command.help_symbol_for(name).into();
+        Arc::new(PositionalCommandNode { command: Arc::new(command) })
     }
-}
 
-/// A simple parameter node. This is only present in a command
-/// line as a value.
-pub struct SimpleParameterNode {
-    node_fields: NodeFields,
-    repeatable_fields: RepeatableNodeFields,
-    parameter_fields: ParameterNodeFields,
+    /// The command, for setting its options as with a `CommandNode`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the command is shared, as it is once it has been
+    /// listed by `subcommands`.
+    pub fn command_mut(&mut self) -> &mut CommandNode {
+        Arc::get_mut(&mut self.command).expect("the command is shared")
+    }
 }
 
-impl Node for SimpleParameterNode {
+impl Node for PositionalCommandNode {
+    #[doc(hidden)]
     fn node_data(&self) -> &NodeFields {
-        &self.node_fields
+        self.command.node_data()
+    }
+
+    /// Any token names the command.
+    fn matches(&self, _token: &str) -> bool {
+        true
+    }
+
+    /// Any token may name the command, so there are no options to
+    /// offer, only the help symbol as a placeholder.
+    fn complete<'t>(&self, token: Option<Token<'t>>) -> Completion<'t> {
+        Completion::new(self.help_symbol().to_string(),
+                        self.help_text().map(|t| t.to_string()),
+                        token,
+                        false,
+                        vec![],
+                        vec![])
+    }
+
+    fn accept(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+        values.insert(self.name().to_string(), vec![token.to_string()]);
+    }
+
+    fn available(&self) -> bool {
+        self.command.available()
+    }
+
+    fn as_command(&self) -> Option<&CommandNode> {
+        Some(&self.command)
+    }
+
+    fn into_command(self: Arc<Self>) -> Option<Arc<CommandNode>> {
+        Some(self.command.clone())
+    }
+
+    fn as_positional_command(&self) -> Option<&PositionalCommandNode> {
+        Some(self)
     }
 }
 
-impl RepeatableNode for SimpleParameterNode {
+/// A repeatable node is an internal helper for representing
+/// nodes that can be repeated, like some parameters.
+pub trait RepeatableNode: Node {
+    /// Internal data for a repeatable node.
     #[doc(hidden)]
-    fn repeatable_data(&self) -> &RepeatableNodeFields {
-        &self.repeatable_fields
+    fn repeatable_data(&self) -> &RepeatableNodeFields;
+
+    /// Whether or not this node can be repeated. A repeated
+    /// node can be `accept`ed multiple times.
+    fn repeatable(&self) -> bool {
+        self.repeatable_data().repeatable
     }
-}
 
-impl ParameterNode for SimpleParameterNode {
+    /// If present, this node will no longer be `acceptable`.
+    fn repeat_marker(&self) -> &Option<Arc<dyn Node>> {
+        &self.repeatable_data().repeat_marker
+    }
+
+    /// Mutable internal data for a repeatable node.
     #[doc(hidden)]
-    fn parameter_data(&self) -> &ParameterNodeFields {
-        &self.parameter_fields
+    fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields;
+
+    /// The number of times that a repeatable node may be accepted,
+    /// or `None` when there is no limit.
+    ///
+    /// Once the node has been accepted this many times, it is no
+    /// longer `acceptable` and isn't completed. A limit of 0 disables
+    /// the node, even if it isn't repeatable.
+    fn max_repetitions(&self) -> Option<usize> {
+        self.repeatable_data().max_repetitions
     }
-}
 
-impl SimpleParameterNode {
-    /// Construct a new `SimpleParameterNode`.
+    /// Limit the number of times that this node may be accepted.
+    fn set_max_repetitions(&mut self, max_repetitions: Option<usize>) {
+        self.repeatable_data_mut().max_repetitions = max_repetitions;
+    }
+
+    /// The number of times that this node must be given for the
+    /// command line to be valid. This is 1 for required parameters
+    /// and 0 for other nodes unless it has been changed.
+    fn min_repetitions(&self) -> usize {
+        self.repeatable_data().min_repetitions
+    }
+
+    /// Require that this node be given at least `min_repetitions` times.
+    fn set_min_repetitions(&mut self, min_repetitions: usize) {
+        self.repeatable_data_mut().min_repetitions = min_repetitions;
+    }
+
+    /// The number of times that this node has been accepted among
+    /// `nodes`, the nodes accepted for a command line, as given by
+    /// `Parser::accepted_nodes` and `HandlerContext::nodes`.
+    fn current_count(&self, nodes: &[Arc<dyn Node>]) -> usize {
+        let address = self as *const Self as *const u8;
+        nodes.iter().filter(|n| n.address() == address).count()
+    }
+}
+
+/// The data for a repeatable node.
+#[doc(hidden)]
+pub struct RepeatableNodeFields {
+    repeatable: bool,
+    repeat_marker: Option<Arc<dyn Node>>,
+    max_repetitions: Option<usize>,
+    min_repetitions: usize,
+}
+
+/// A node that represented the name portion of a named
+/// parameter.
+pub struct ParameterNameNode {
+    node_fields: NodeFields,
+    repeatable_fields: RepeatableNodeFields,
+    parameter: Arc<dyn ParameterNode>,
+}
+
+impl ParameterNameNode {
+    /// Construct a new `ParameterNameNode` for `parameter`.
+    ///
+    /// The `parameter` becomes the only successor of the name, so
+    /// that the token after the name is taken as its value.
+    ///
+    /// The help symbol of the name includes that of its parameter,
+    /// like `count <count>`.
     pub fn new(name: &str,
+               repeatable: bool,
+               repeat_marker: Option<Arc<dyn Node>>,
+               max_repetitions: Option<usize>,
+               help_text: Option<String>,
+               parameter: Arc<dyn ParameterNode>)
+               -> Arc<Self> {
+        let help_symbol = decorate_help_symbol(&format!("{} {}", name, parameter.help_symbol()),
+                                               parameter.required(),
+                                               false);
+        Arc::new(ParameterNameNode {
+            node_fields: NodeFields::new(name,
+                                         help_symbol,
+                                         help_text,
+                                         parameter.hidden(),
+                                         parameter.priority(),
+                                         vec![parameter.clone()]),
+            repeatable_fields: RepeatableNodeFields {
+                repeatable,
+                repeat_marker,
+                max_repetitions,
+                min_repetitions: parameter.min_repetitions(),
+            },
+            parameter,
+        })
+    }
+
+    /// The parameter which is named by this node.
+    pub fn parameter(&self) -> &Arc<dyn ParameterNode> {
+        &self.parameter
+    }
+
+    /// The parameter which is named by this node, as the `Node` which
+    /// takes the value after the name.
+    pub fn linked_value_node(&self) -> Arc<dyn Node> {
+        self.parameter.clone()
+    }
+}
+
+impl Node for ParameterNameNode {
+    #[doc(hidden)]
+    fn node_data(&self) -> &NodeFields {
+        &self.node_fields
+    }
+
+    fn as_parameter_name(&self) -> Option<&ParameterNameNode> {
+        Some(self)
+    }
+
+    fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+        Some(self)
+    }
+}
+
+impl RepeatableNode for ParameterNameNode {
+    #[doc(hidden)]
+    fn repeatable_data(&self) -> &RepeatableNodeFields {
+        &self.repeatable_fields
+    }
+
+    #[doc(hidden)]
+    fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields {
+        &mut self.repeatable_fields
+    }
+}
+
+/// Parameter nodes.
+pub trait ParameterNode: Node + RepeatableNode {
+    /// Internal data for a parameter node.
+    #[doc(hidden)]
+    fn parameter_data(&self) -> &ParameterNodeFields;
+
+    /// Mutable internal data for a parameter node.
+    #[doc(hidden)]
+    fn parameter_data_mut(&mut self) -> &mut ParameterNodeFields;
+
+    /// A `required` parameter must be supplied for the
+    /// command line being parsed to be valid.
+    fn required(&self) -> bool {
+        self.parameter_data().required
+    }
+
+    /// A positional parameter is given as a bare value rather than
+    /// being introduced by its name. Positional parameters are bound
+    /// in the order in which they were declared on their command.
+    fn positional(&self) -> bool {
+        false
+    }
+
+    /// Whether this parameter is given a value. Flags are not, as
+    /// they are either present or absent.
+    fn takes_value(&self) -> bool {
+        true
+    }
+
+    /// Record `token` as a value of this parameter.
+    ///
+    /// When the parameter `deduplicates`, a value which has already
+    /// been recorded isn't recorded again.
+    #[doc(hidden)]
+    fn record_value(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+        let recorded = values.entry(self.name().to_string()).or_default();
+        if !self.deduplicates() || !recorded.iter().any(|v| v == token) {
+            recorded.push(token.to_string());
+        }
+    }
+
+    /// Whether values given more than once for this repeatable
+    /// parameter are kept only once, in the order in which they
+    /// were first given.
+    fn deduplicates(&self) -> bool {
+        self.parameter_data().deduplicate
+    }
+
+    /// Keep each value given for this parameter only once.
+    fn set_deduplicate(&mut self, deduplicate: bool) {
+        self.parameter_data_mut().deduplicate = deduplicate;
+    }
+
+    /// The names of the other parameters of the same command
+    /// which must also be supplied whenever this one is.
+    fn requires(&self) -> Vec<String> {
+        self.parameter_data().requires.read().unwrap().clone()
+    }
+
+    /// Declare that this parameter may only be supplied together
+    /// with the parameter named `name`.
+    ///
+    /// Unlike most other settings, this may be done after the
+    /// parameter has been added to a command, as with
+    /// `constraints::add_dependency`.
+    ///
+    /// Fails if this parameter has been frozen.
+    fn add_requirement(&self, name: &str) -> Result<(), FrozenError> {
+        self.node_data().check_mutable()?;
+        self.parameter_data().requires.write().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    /// The names of the other parameters of the same command
+    /// which may not be supplied together with this one.
+    fn conflicts_with(&self) -> &Vec<String> {
+        &self.parameter_data().conflicts_with
+    }
+
+    /// Declare that this parameter may not be supplied together
+    /// with the parameter named `name`. Conflicts apply in both
+    /// directions, so only one of the two needs to declare it.
+    fn add_conflict(&mut self, name: &str) {
+        self.parameter_data_mut().conflicts_with.push(name.to_string());
+    }
+}
+
+/// Decorate the help symbol of a parameter: `...` is appended
+/// when it is `repeatable`, and it is enclosed in `[...]` when
+/// it isn't `required`.
+fn decorate_help_symbol(symbol: &str, required: bool, repeatable: bool) -> String {
+    let ellipsis = if repeatable { "..." } else { "" };
+    if required {
+        format!("{}{}", symbol, ellipsis)
+    } else {
+        format!("[{}{}]", symbol, ellipsis)
+    }
+}
+
+/// Data for parameter nodes.
+#[doc(hidden)]
+pub struct ParameterNodeFields {
+    required: bool,
+    requires: RwLock<Vec<String>>,
+    conflicts_with: Vec<String>,
+    deduplicate: bool,
+}
+
+impl ParameterNodeFields {
+    fn new(required: bool) -> Self {
+        ParameterNodeFields {
+            required,
+            requires: RwLock::new(vec![]),
+            conflicts_with: vec![],
+            deduplicate: false,
+        }
+    }
+}
+
+/// A flag parameter node.
+///
+/// A flag is given by its name alone, without a value, and is only
+/// ever `true`: once it has been accepted, `ParameterValues` has it
+/// as `Bool(true)`. Unless it is repeatable, it can't be given again.
+///
+/// A flag only matches its whole name, so that it can't be given by
+/// mistake with a prefix of it, which may be meant as the value of a
+/// parameter. It is still completed from a prefix.
+pub struct FlagParameterNode {
+    node_fields: NodeFields,
+    repeatable_fields: RepeatableNodeFields,
+    parameter_fields: ParameterNodeFields,
+}
+
+impl Node for FlagParameterNode {
+    fn node_data(&self) -> &NodeFields {
+        &self.node_fields
+    }
+
+    /// A flag only matches its whole name.
+    fn matches(&self, token: &str) -> bool {
+        self.case_sensitivity().equals(&self.name(), token)
+    }
+
+    /// A flag is completed from any prefix of its name.
+    fn completes(&self, prefix: &str) -> bool {
+        self.case_sensitivity().starts_with(&self.name(), prefix)
+    }
+
+    /// The token only names the flag, so `true` is recorded
+    /// rather than the token.
+    fn accept(&self, _token: &str, values: &mut HashMap<String, Vec<String>>) {
+        self.record_value(&true.to_string(), values);
+    }
+
+    fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+        Some(self)
+    }
+
+    fn as_parameter(&self) -> Option<&dyn ParameterNode> {
+        Some(self)
+    }
+}
+
+impl RepeatableNode for FlagParameterNode {
+    #[doc(hidden)]
+    fn repeatable_data(&self) -> &RepeatableNodeFields {
+        &self.repeatable_fields
+    }
+
+    #[doc(hidden)]
+    fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields {
+        &mut self.repeatable_fields
+    }
+}
+
+impl ParameterNode for FlagParameterNode {
+    #[doc(hidden)]
+    fn parameter_data(&self) -> &ParameterNodeFields {
+        &self.parameter_fields
+    }
+
+    #[doc(hidden)]
+    fn parameter_data_mut(&mut self) -> &mut ParameterNodeFields {
+        &mut self.parameter_fields
+    }
+
+    fn takes_value(&self) -> bool {
+        false
+    }
+}
+
+impl FlagParameterNode {
+    /// Construct an optional flag which may be given once, with the
+    /// default priority of parameters.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    ///
+    /// let force = FlagParameterNode::new("force", None);
+    /// assert_eq!(&*force.help_symbol(), "[force]");
+    /// assert!(!force.takes_value());
+    /// assert!(force.matches("force"));
+    /// assert!(!force.matches("f"));
+    /// assert!(force.completes("f"));
+    /// ```
+    pub fn new(name: &str, help_text: Option<String>) -> Arc<Self> {
+        Arc::new(FlagParameterNode::with_options(name,
+                                                 help_text,
+                                                 false,
+                                                 PRIORITY_PARAMETER,
+                                                 vec![],
+                                                 false,
+                                                 None,
+                                                 false))
+    }
+
+    /// Construct a `FlagParameterNode` with all of its options.
+    ///
+    /// A flag is typed as its bare name, so that is its help symbol,
+    /// as in `verbose`, or `[verbose]` when it is optional.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_options(name: &str,
                help_text: Option<String>,
                hidden: bool,
                priority: i32,
-               successors: Vec<Rc<Node>>,
+               successors: Vec<Arc<dyn Node>>,
                repeatable: bool,
-               repeat_marker: Option<Rc<Node>>,
+               repeat_marker: Option<Arc<dyn Node>>,
                required: bool)
                -> Self {
-        SimpleParameterNode {
-            node_fields: NodeFields {
-                name: name.to_string(),
-                help_symbol: name.to_string(),
-                help_text: help_text,
-                hidden: hidden,
-                priority: priority,
-                successors: successors,
+        FlagParameterNode {
+            node_fields: NodeFields::new(name,
+                                         decorate_help_symbol(name, required, repeatable),
+                                         help_text,
+                                         hidden,
+                                         priority,
+                                         successors),
+            repeatable_fields: RepeatableNodeFields {
+                repeatable,
+                repeat_marker,
+                max_repetitions: None,
+                min_repetitions: required as usize,
             },
+            parameter_fields: ParameterNodeFields::new(required),
+        }
+    }
+}
+
+/// A named parameter node.
+///
+/// This is the node for the value, which follows the name of the
+/// parameter. The value is checked by the parameter's `value_type`,
+/// when it has one, and otherwise any token is taken.
+pub struct NamedParameterNode {
+    node_fields: NodeFields,
+    repeatable_fields: RepeatableNodeFields,
+    parameter_fields: ParameterNodeFields,
+    value_type: Option<Box<dyn ParameterNode>>,
+}
+
+impl Node for NamedParameterNode {
+    fn node_data(&self) -> &NodeFields {
+        &self.node_fields
+    }
+
+    /// A token may be a value when the `value_type` matches it.
+    fn matches(&self, token: &str) -> bool {
+        match self.value_type {
+            Some(ref value_type) => value_type.matches(token),
+            None => true,
+        }
+    }
+
+    /// The options of the `value_type` are offered, with the help
+    /// symbol of this parameter as the placeholder. Without one, any
+    /// token may be a value, so there are no options to offer.
+    fn complete<'t>(&self, token: Option<Token<'t>>) -> Completion<'t> {
+        match self.value_type {
+            Some(ref value_type) => {
+                let mut completion = value_type.complete(token);
+                completion.help_symbol = self.help_symbol().to_string();
+                completion.help_text = self.help_text().map(|t| t.to_string());
+                completion
+            }
+            None => {
+                Completion::new(self.help_symbol().to_string(),
+                                self.help_text().map(|t| t.to_string()),
+                                token,
+                                false,
+                                vec![],
+                                vec![])
+            }
+        }
+    }
+
+    /// The `value_type` decides what is recorded for the token, as
+    /// a `FuzzyMatchNode` records the choice nearest to it, and it is
+    /// recorded under the name of this parameter.
+    fn accept(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+        match self.value_type {
+            Some(ref value_type) => {
+                let mut accepted = HashMap::new();
+                value_type.accept(token, &mut accepted);
+                for value in accepted.values().flatten() {
+                    self.record_value(value, values);
+                }
+            }
+            None => self.record_value(token, values),
+        }
+    }
+
+    fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+        Some(self)
+    }
+
+    fn as_parameter(&self) -> Option<&dyn ParameterNode> {
+        Some(self)
+    }
+}
+
+impl RepeatableNode for NamedParameterNode {
+    #[doc(hidden)]
+    fn repeatable_data(&self) -> &RepeatableNodeFields {
+        &self.repeatable_fields
+    }
+
+    #[doc(hidden)]
+    fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields {
+        &mut self.repeatable_fields
+    }
+}
+
+impl ParameterNode for NamedParameterNode {
+    #[doc(hidden)]
+    fn parameter_data(&self) -> &ParameterNodeFields {
+        &self.parameter_fields
+    }
+
+    #[doc(hidden)]
+    fn parameter_data_mut(&mut self) -> &mut ParameterNodeFields {
+        &mut self.parameter_fields
+    }
+}
+
+impl NamedParameterNode {
+    /// Construct a named parameter which is given as `key` and then
+    /// its value, which `value_type` must match, with the default
+    /// priority of parameters. It may be given once.
+    ///
+    /// A `CommandNode` it is added to gives it a `ParameterNameNode`
+    /// for the key. The `value_type` is only used to check, complete
+    /// and record the value, so its name and other settings don't
+    /// matter.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    /// use commands::parser::Parser;
+    ///
+    /// let protocol = FuzzyMatchNode::new(SimpleParameterNode::new("protocol",
+    ///                                                             None,
+    ///                                                             false,
+    ///                                                             PRIORITY_PARAMETER,
+    ///                                                             vec![],
+    ///                                                             false,
+    ///                                                             None,
+    ///                                                             true),
+    ///                                    vec!["tcp".to_string(), "udp".to_string()],
+    ///                                    0);
+    /// let ping = CommandNode::new("ping", PRIORITY_DEFAULT, false, None, None, vec![]);
+    /// ping.add_parameter(NamedParameterNode::new("protocol", Box::new(protocol), None, true))
+    ///     .unwrap();
+    /// let root = RootNode::new();
+    /// root.add_successor(ping).unwrap();
+    ///
+    /// let mut parser = Parser::new(root.clone());
+    /// parser.parse("ping protocol udp").unwrap();
+    /// assert_eq!(parser.values().get_str("protocol"), Some("udp"));
+    /// assert!(Parser::new(root).parse("ping protocol icmp").is_err());
+    /// ```
+    pub fn new(key: &str,
+               value_type: Box<dyn ParameterNode>,
+               help_text: Option<String>,
+               required: bool)
+               -> Arc<Self> {
+        let mut parameter = NamedParameterNode::with_options(key,
+                                                             help_text,
+                                                             false,
+                                                             PRIORITY_PARAMETER,
+                                                             vec![],
+                                                             false,
+                                                             None,
+                                                             required);
+        parameter.value_type = Some(value_type);
+        Arc::new(parameter)
+    }
+
+    /// Construct a `NamedParameterNode` with all of its options,
+    /// which takes any token as its value.
+    ///
+    /// This is the node for the value, so its help symbol is a
+    /// placeholder, as in `<count>`. Whether it is optional is shown
+    /// by its `ParameterNameNode`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_options(name: &str,
+               help_text: Option<String>,
+               hidden: bool,
+               priority: i32,
+               successors: Vec<Arc<dyn Node>>,
+               repeatable: bool,
+               repeat_marker: Option<Arc<dyn Node>>,
+               required: bool)
+               -> Self {
+        NamedParameterNode {
+            node_fields: NodeFields::new(name,
+                                         decorate_help_symbol(&format!("<{}>", name),
+                                                              true,
+                                                              repeatable),
+                                         help_text,
+                                         hidden,
+                                         priority,
+                                         successors),
             repeatable_fields: RepeatableNodeFields {
-                repeatable: repeatable,
-                repeat_marker: repeat_marker,
+                repeatable,
+                repeat_marker,
+                max_repetitions: None,
+                min_repetitions: required as usize,
             },
-            parameter_fields: ParameterNodeFields { required: required },
+            parameter_fields: ParameterNodeFields::new(required),
+            value_type: None,
+        }
+    }
+}
+
+/// A simple parameter node. This is only present in a command
+/// line as a value.
+pub struct SimpleParameterNode {
+    node_fields: NodeFields,
+    repeatable_fields: RepeatableNodeFields,
+    parameter_fields: ParameterNodeFields,
+}
+
+impl Node for SimpleParameterNode {
+    fn node_data(&self) -> &NodeFields {
+        &self.node_fields
+    }
+
+    /// Any token may be a value.
+    fn matches(&self, _token: &str) -> bool {
+        true
+    }
+
+    /// Any token may be a value, so there are no options to offer,
+    /// only the help symbol as a placeholder.
+    fn complete<'t>(&self, token: Option<Token<'t>>) -> Completion<'t> {
+        Completion::new(self.help_symbol().to_string(),
+                        self.help_text().map(|t| t.to_string()),
+                        token,
+                        false,
+                        vec![],
+                        vec![])
+    }
+
+    fn accept(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+        self.record_value(token, values);
+    }
+
+    fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+        Some(self)
+    }
+
+    fn as_parameter(&self) -> Option<&dyn ParameterNode> {
+        Some(self)
+    }
+}
+
+impl RepeatableNode for SimpleParameterNode {
+    #[doc(hidden)]
+    fn repeatable_data(&self) -> &RepeatableNodeFields {
+        &self.repeatable_fields
+    }
+
+    #[doc(hidden)]
+    fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields {
+        &mut self.repeatable_fields
+    }
+}
+
+impl ParameterNode for SimpleParameterNode {
+    #[doc(hidden)]
+    fn parameter_data(&self) -> &ParameterNodeFields {
+        &self.parameter_fields
+    }
+
+    #[doc(hidden)]
+    fn parameter_data_mut(&mut self) -> &mut ParameterNodeFields {
+        &mut self.parameter_fields
+    }
+
+    fn positional(&self) -> bool {
+        true
+    }
+}
+
+impl SimpleParameterNode {
+    /// Construct a new `SimpleParameterNode`.
+    ///
+    /// The help symbol is a placeholder for the value, as in
+    /// `<file>`, or `[<file>]` when it is optional.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(name: &str,
+               help_text: Option<String>,
+               hidden: bool,
+               priority: i32,
+               successors: Vec<Arc<dyn Node>>,
+               repeatable: bool,
+               repeat_marker: Option<Arc<dyn Node>>,
+               required: bool)
+               -> Self {
+        SimpleParameterNode {
+            node_fields: NodeFields::new(name,
+                                         decorate_help_symbol(&format!("<{}>", name),
+                                                              required,
+                                                              repeatable),
+                                         help_text,
+                                         hidden,
+                                         priority,
+                                         successors),
+            repeatable_fields: RepeatableNodeFields {
+                repeatable,
+                repeat_marker,
+                max_repetitions: None,
+                min_repetitions: required as usize,
+            },
+            parameter_fields: ParameterNodeFields::new(required),
+        }
+    }
+
+    /// Construct a positional parameter at the default priority of
+    /// parameters, taking any token as its value.
+    ///
+    /// ```
+    /// use commands::parser::nodes::*;
+    ///
+    /// let files = SimpleParameterNode::positional("file", None, true, true);
+    /// assert_eq!(&*files.help_symbol(), "<file>...");
+    /// assert!(files.matches("notes.txt"));
+    /// ```
+    pub fn positional(name: &str,
+                      help_text: Option<String>,
+                      required: bool,
+                      repeatable: bool)
+                      -> Arc<Self> {
+        Arc::new(SimpleParameterNode::new(name,
+                                          help_text,
+                                          false,
+                                          PRIORITY_PARAMETER,
+                                          vec![],
+                                          repeatable,
+                                          None,
+                                          required))
+    }
+}
+
+/// A parameter which accepts one of a list of choices, also when
+/// it is misspelled.
+///
+/// A token matches when it is within `max_distance` edits of one
+/// of the choices, as measured by `levenshtein`, and the choice
+/// which is nearest to it is recorded as the value. This lets a
+/// command line succeed despite a typo, while the handler only
+/// ever sees the correct spelling.
+///
+/// ```
+/// use commands::parser::nodes::*;
+///
+/// let protocol = FuzzyMatchNode::new(SimpleParameterNode::new("protocol",
+///                                                             None,
+///                                                             false,
+///                                                             PRIORITY_PARAMETER,
+///                                                             vec![],
+///                                                             false,
+///                                                             None,
+///                                                             true),
+///                                    vec!["tcp".to_string(), "udp".to_string()],
+///                                    1);
+/// assert_eq!(protocol.nearest("udpp"), Some("udp"));
+/// assert_eq!(protocol.nearest("tpc"), None);
+/// ```
+pub struct FuzzyMatchNode {
+    parameter: SimpleParameterNode,
+    choices: Vec<String>,
+    max_distance: usize,
+}
+
+impl FuzzyMatchNode {
+    /// Construct a new `FuzzyMatchNode` for `parameter`, accepting
+    /// tokens within `max_distance` edits of one of the `choices`.
+    pub fn new(parameter: SimpleParameterNode, choices: Vec<String>, max_distance: usize) -> Self {
+        FuzzyMatchNode {
+            parameter,
+            choices,
+            max_distance,
+        }
+    }
+
+    /// The values which this parameter accepts.
+    pub fn choices(&self) -> &[String] {
+        &self.choices
+    }
+
+    /// The largest number of edits that a token may be from a choice.
+    pub fn max_distance(&self) -> usize {
+        self.max_distance
+    }
+
+    /// The choice which is nearest to `token`, if any is within
+    /// `max_distance`. Ties go to the choice which is listed first.
+    ///
+    /// The distance is measured with regard to case unless the
+    /// node is `CaseSensitivity::Insensitive`.
+    pub fn nearest(&self, token: &str) -> Option<&str> {
+        let fold = |text: &str| match self.case_sensitivity() {
+            CaseSensitivity::Sensitive => text.to_string(),
+            CaseSensitivity::Insensitive => text.to_lowercase(),
+        };
+        let token = fold(token);
+        self.choices
+            .iter()
+            .map(|c| (levenshtein(&fold(c), &token), c))
+            .filter(|&(d, _)| d <= self.max_distance)
+            .min_by_key(|&(d, _)| d)
+            .map(|(_, c)| c.as_str())
+    }
+}
+
+impl Node for FuzzyMatchNode {
+    fn node_data(&self) -> &NodeFields {
+        self.parameter.node_data()
+    }
+
+    /// A token matches when it is near enough to one of the choices.
+    fn matches(&self, token: &str) -> bool {
+        self.nearest(token).is_some()
+    }
+
+    /// The choices are offered as the options.
+    fn complete<'t>(&self, token: Option<Token<'t>>) -> Completion<'t> {
+        Completion::new(self.help_symbol().to_string(),
+                        self.help_text().map(|t| t.to_string()),
+                        token,
+                        true,
+                        self.choices.iter().map(|c| c.as_str()).collect(),
+                        vec![])
+    }
+
+    /// Record the nearest choice rather than the token itself.
+    fn accept(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+        if let Some(choice) = self.nearest(token) {
+            self.record_value(choice, values);
+        }
+    }
+
+    fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+        Some(self)
+    }
+
+    fn as_parameter(&self) -> Option<&dyn ParameterNode> {
+        Some(self)
+    }
+}
+
+impl RepeatableNode for FuzzyMatchNode {
+    #[doc(hidden)]
+    fn repeatable_data(&self) -> &RepeatableNodeFields {
+        self.parameter.repeatable_data()
+    }
+
+    #[doc(hidden)]
+    fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields {
+        self.parameter.repeatable_data_mut()
+    }
+}
+
+impl ParameterNode for FuzzyMatchNode {
+    #[doc(hidden)]
+    fn parameter_data(&self) -> &ParameterNodeFields {
+        self.parameter.parameter_data()
+    }
+
+    #[doc(hidden)]
+    fn parameter_data_mut(&mut self) -> &mut ParameterNodeFields {
+        self.parameter.parameter_data_mut()
+    }
+
+    fn positional(&self) -> bool {
+        true
+    }
+}
+
+/// Begin the `Debug` output for a node with the fields that
+/// all nodes have.
+fn debug_node<'a, 'b>(f: &'a mut fmt::Formatter<'b>,
+                      kind: &str,
+                      node: &dyn Node)
+                      -> fmt::DebugStruct<'a, 'b> {
+    let successors = node.successors().iter().map(|n| n.name()).collect::<Vec<_>>();
+    let mut debug = f.debug_struct(kind);
+    debug.field("name", &node.name())
+         .field("priority", &node.priority())
+         .field("hidden", &node.hidden())
+         .field("successors", &successors);
+    debug
+}
+
+impl fmt::Debug for RootNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_node(f, "RootNode", self).finish()
+    }
+}
+
+impl fmt::Debug for CommandNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters = self.parameters().iter().map(|p| p.name()).collect::<Vec<_>>();
+        debug_node(f, "CommandNode", self)
+            .field("parameters", &parameters)
+            .field("handler", &self.handler().is_some())
+            .finish()
+    }
+}
+
+impl fmt::Debug for PositionalCommandNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters = self.command
+                             .parameters()
+                             .iter()
+                             .map(|p| p.name())
+                             .collect::<Vec<_>>();
+        debug_node(f, "PositionalCommandNode", self)
+            .field("parameters", &parameters)
+            .field("handler", &self.command.handler().is_some())
+            .finish()
+    }
+}
+
+impl fmt::Debug for KeywordNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_node(f, "KeywordNode", self).finish()
+    }
+}
+
+impl fmt::Debug for WrapperNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_node(f, "WrapperNode", self)
+            .field("root", &self.root.name())
+            .field("handler", &self.handler().is_some())
+            .finish()
+    }
+}
+
+impl fmt::Debug for SubparserNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_node(f, "SubparserNode", self).finish()
+    }
+}
+
+impl fmt::Debug for NegationNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_node(f, "NegationNode", self).field("root", &self.root.name()).finish()
+    }
+}
+
+impl fmt::Debug for ParameterNameNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_node(f, "ParameterNameNode", self)
+            .field("repeatable", &self.repeatable())
+            .finish()
+    }
+}
+
+/// Format the `Debug` output for a parameter node.
+fn debug_parameter(f: &mut fmt::Formatter,
+                   kind: &str,
+                   parameter: &dyn ParameterNode)
+                   -> fmt::Result {
+    debug_node(f, kind, parameter)
+        .field("required", &parameter.required())
+        .field("repeatable", &parameter.repeatable())
+        .finish()
+}
+
+impl fmt::Debug for FlagParameterNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_parameter(f, "FlagParameterNode", self)
+    }
+}
+
+impl fmt::Debug for NamedParameterNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_parameter(f, "NamedParameterNode", self)
+    }
+}
+
+impl fmt::Debug for SimpleParameterNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_parameter(f, "SimpleParameterNode", self)
+    }
+}
+
+impl fmt::Debug for FuzzyMatchNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_parameter(f, "FuzzyMatchNode", self)
+    }
+}
+
+/// The successors of `node` which are commands, leaving out
+/// parameters, keywords and their other successors, as given by
+/// `Node::into_command`.
+///
+/// ```
+/// use commands::parser::nodes::*;
+///
+/// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// show.add_successor(CommandNode::new("route",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// show.add_successor(KeywordNode::new("all", PRIORITY_DEFAULT, false, None)).unwrap();
+/// let names = subcommands(&*show).iter().map(|c| c.name().to_string()).collect::<Vec<_>>();
+/// assert_eq!(names, vec!["route"]);
+/// ```
+pub fn subcommands(node: &dyn Node) -> Vec<Arc<CommandNode>> {
+    node.successors().iter().cloned().filter_map(|n| n.into_command()).collect()
+}
+
+/// Find the path from `root` to `target` by following successors.
+///
+/// The path starts with `root` and ends with `target`. It is `None`
+/// when `target` can't be reached from `root`. When `target` can be
+/// reached in several ways, as in shared subtrees, the first path
+/// found is given.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// root.add_successor(show.clone()).unwrap();
+/// let tree: Arc<dyn Node> = root;
+/// let path = ancestors(&tree, &*show).unwrap();
+/// assert_eq!(path.iter().map(|n| n.name().to_string()).collect::<Vec<_>>(),
+///            vec!["__root__", "show"]);
+/// ```
+pub fn ancestors(root: &Arc<dyn Node>, target: &dyn Node) -> Option<Vec<Arc<dyn Node>>> {
+    let mut path = vec![];
+    let mut seen = HashSet::new();
+    if find_ancestors(root, target, &mut seen, &mut path) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn find_ancestors(node: &Arc<dyn Node>,
+                  target: &dyn Node,
+                  seen: &mut HashSet<Arc<dyn Node>>,
+                  path: &mut Vec<Arc<dyn Node>>)
+                  -> bool {
+    if !seen.insert(node.clone()) {
+        return false;
+    }
+    path.push(node.clone());
+    if **node == *target {
+        return true;
+    }
+    for successor in node.successors().iter() {
+        if find_ancestors(successor, target, seen, path) {
+            return true;
+        }
+    }
+    path.pop();
+    false
+}
+
+/// The names of the nodes on a path through a tree, written with
+/// dots between them, as in `show.interface.eth0`.
+///
+/// An empty string is the empty path, which refers to the root.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// show.add_successor(CommandNode::new("route",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// root.add_successor(show).unwrap();
+/// let tree: Arc<dyn Node> = root;
+///
+/// let path = "show.route".parse::<NodePath>().unwrap();
+/// assert_eq!(&*resolve(&tree, &path).unwrap().name(), "route");
+/// ```
+#[derive(Clone,Debug,Default,PartialEq,Eq,Hash)]
+pub struct NodePath(pub Vec<String>);
+
+impl FromStr for NodePath {
+    type Err = NodePathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        if path.is_empty() {
+            return Ok(NodePath(vec![]));
+        }
+        let names = path.split('.').map(|n| n.to_string()).collect::<Vec<_>>();
+        match names.iter().position(|n| n.is_empty()) {
+            Some(index) => {
+                Err(NodePathError::EmptyName {
+                    path: path.to_string(),
+                    index,
+                })
+            }
+            None => Ok(NodePath(names)),
+        }
+    }
+}
+
+impl fmt::Display for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.0.join(".").fmt(f)
+    }
+}
+
+/// Errors that parsing a `NodePath` can raise.
+#[derive(Clone,Debug,PartialEq)]
+pub enum NodePathError {
+    /// One of the names in the path is empty, as in `show..eth0`.
+    EmptyName {
+        /// The path which was given.
+        path: String,
+        /// The index of the empty name.
+        index: usize,
+    },
+}
+
+impl Error for NodePathError {}
+
+impl fmt::Display for NodePathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            NodePathError::EmptyName { ref path, index } => {
+                write!(f, "name {} of the path '{}' is empty", index, path)
+            }
+        }
+    }
+}
+
+/// Find the node reached by following the names of `path` through
+/// the successors of `root`. Names are matched in full, respecting
+/// the case sensitivity of each node.
+pub fn resolve(root: &Arc<dyn Node>, path: &NodePath) -> Option<Arc<dyn Node>> {
+    let mut node = root.clone();
+    for name in &path.0 {
+        node = node.successors()
+                   .iter()
+                   .find(|n| n.case_sensitivity().equals(&n.name(), name))?
+                   .clone();
+    }
+    Some(node)
+}
+
+/// The dotted path from `root` to `command`, like `show.route`, by
+/// which `resolve` finds it, or `None` when it isn't in the tree.
+///
+/// A `CommandNode` doesn't know its parent, so the tree has to be
+/// given to find its path.
+pub fn canonical_path(root: &Arc<dyn Node>, command: &CommandNode) -> Option<String> {
+    let ancestors = ancestors(root, command)?;
+    let names = ancestors[1..].iter().map(|n| n.name().to_string()).collect();
+    Some(NodePath(names).to_string())
+}
+
+/// Render the tree beneath `root` with one node per line, each
+/// indented beneath the node that it follows.
+///
+/// The positional parameters of a command are listed after its
+/// successors and marked as `(positional)`. A node which appears
+/// more than once, as in shared subtrees or beneath a `WrapperNode`,
+/// is only expanded the first time and is marked as `(repeated)`
+/// after that.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("show",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// let tree: Arc<dyn Node> = root;
+/// assert_eq!(dump_tree(&tree), "__root__\n  show\n");
+/// ```
+pub fn dump_tree(root: &Arc<dyn Node>) -> String {
+    let mut dump = String::new();
+    let mut seen = HashSet::new();
+    dump_node(root, 0, &mut seen, &mut dump);
+    dump
+}
+
+fn dump_node(node: &Arc<dyn Node>,
+             depth: usize,
+             seen: &mut HashSet<Arc<dyn Node>>,
+             dump: &mut String) {
+    let indent = "  ".repeat(depth);
+    if !seen.insert(node.clone()) {
+        dump.push_str(&format!("{}{} (repeated)\n", indent, node.name()));
+        return;
+    }
+    dump.push_str(&format!("{}{}\n", indent, node.name()));
+    for successor in node.successors().iter() {
+        dump_node(successor, depth + 1, seen, dump);
+    }
+    if let Some(command) = node.as_command() {
+        for parameter in command.parameters().into_iter().filter(|p| p.positional()) {
+            dump.push_str(&format!("{}  {} (positional)\n", indent, parameter.name()));
+        }
+    }
+}
+
+/// A summary of a command tree, as computed by `compute_stats`.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct NodeStats {
+    /// The number of distinct nodes, including the root.
+    pub nodes: usize,
+    /// The number of `CommandNode`s.
+    pub commands: usize,
+    /// The depth of the deepest node. The root is at depth 0.
+    pub max_depth: usize,
+    /// The number of hidden nodes.
+    pub hidden: usize,
+    /// The number of parameters which are `required`.
+    pub required_parameters: usize,
+}
+
+/// Gather statistics on the tree beneath `root`.
+///
+/// As with `dump_tree`, the positional parameters of a command are
+/// counted one level beneath it, and a node which appears more than
+/// once is only counted the first time that it is reached.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("show",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// let tree: Arc<dyn Node> = root;
+/// let stats = compute_stats(&tree);
+/// assert_eq!(stats.nodes, 2);
+/// assert_eq!(stats.max_depth, 1);
+/// ```
+pub fn compute_stats(root: &Arc<dyn Node>) -> NodeStats {
+    let mut stats = NodeStats::default();
+    let mut seen = HashSet::new();
+    count_node(root, 0, &mut seen, &mut stats);
+    stats
+}
+
+fn count_node(node: &Arc<dyn Node>,
+              depth: usize,
+              seen: &mut HashSet<Arc<dyn Node>>,
+              stats: &mut NodeStats) {
+    if !seen.insert(node.clone()) {
+        return;
+    }
+    stats.nodes += 1;
+    stats.max_depth = stats.max_depth.max(depth);
+    if node.hidden() {
+        stats.hidden += 1;
+    }
+    if node.as_parameter().is_some_and(|p| p.required()) {
+        stats.required_parameters += 1;
+    }
+    for successor in node.successors().iter() {
+        count_node(successor, depth + 1, seen, stats);
+    }
+    if let Some(command) = node.as_command() {
+        stats.commands += 1;
+        for parameter in command.parameters().into_iter().filter(|p| p.positional()) {
+            count_node(&(parameter as Arc<dyn Node>), depth + 1, seen, stats);
+        }
+    }
+}
+
+/// An iterator over the nodes of a tree in breadth-first order, as
+/// made by `bfs`.
+pub struct NodeBfsIterator {
+    queue: VecDeque<(NodePath, Arc<dyn Node>)>,
+    seen: HashSet<Arc<dyn Node>>,
+}
+
+impl NodeBfsIterator {
+    /// Give the `NodePath` of each node along with it, which is the
+    /// path by which the node was reached first.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use commands::parser::nodes::*;
+    ///
+    /// let root = RootNode::new();
+    /// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+    /// show.add_successor(CommandNode::new("route",
+    ///                                     PRIORITY_DEFAULT,
+    ///                                     false,
+    ///                                     None,
+    ///                                     None,
+    ///                                     vec![])).unwrap();
+    /// root.add_successor(show).unwrap();
+    /// let tree: Arc<dyn Node> = root;
+    /// let paths = bfs(tree).with_paths().map(|(p, _)| p.to_string()).collect::<Vec<_>>();
+    /// assert_eq!(paths, vec!["", "show", "show.route"]);
+    /// ```
+    pub fn with_paths(self) -> NodeBfsPaths {
+        NodeBfsPaths(self)
+    }
+
+    fn next_with_path(&mut self) -> Option<(NodePath, Arc<dyn Node>)> {
+        let (path, node) = self.queue.pop_front()?;
+        for successor in node.successors().iter() {
+            if self.seen.insert(successor.clone()) {
+                let mut names = path.0.clone();
+                names.push(successor.name().to_string());
+                self.queue.push_back((NodePath(names), successor.clone()));
+            }
+        }
+        Some((path, node))
+    }
+}
+
+impl Iterator for NodeBfsIterator {
+    type Item = Arc<dyn Node>;
+
+    fn next(&mut self) -> Option<Arc<dyn Node>> {
+        self.next_with_path().map(|(_, node)| node)
+    }
+}
+
+/// An iterator over the nodes of a tree in breadth-first order, along
+/// with their paths, as made by `NodeBfsIterator::with_paths`.
+pub struct NodeBfsPaths(NodeBfsIterator);
+
+impl Iterator for NodeBfsPaths {
+    type Item = (NodePath, Arc<dyn Node>);
+
+    fn next(&mut self) -> Option<(NodePath, Arc<dyn Node>)> {
+        self.0.next_with_path()
+    }
+}
+
+/// Iterate over `root` and the nodes which can be reached from it by
+/// following successors, level by level.
+///
+/// A node which can be reached in several ways, as in shared subtrees
+/// or beneath a `WrapperNode`, is only given the first time.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// show.add_successor(CommandNode::new("route",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// root.add_successor(show).unwrap();
+/// root.add_successor(CommandNode::new("reload",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// let tree: Arc<dyn Node> = root;
+/// let names = bfs(tree).map(|n| n.name().to_string()).collect::<Vec<_>>();
+/// assert_eq!(names, vec!["__root__", "show", "reload", "route"]);
+/// ```
+pub fn bfs(root: Arc<dyn Node>) -> NodeBfsIterator {
+    let mut seen = HashSet::new();
+    seen.insert(root.clone());
+    NodeBfsIterator {
+        queue: VecDeque::from(vec![(NodePath(vec![]), root)]),
+        seen,
+    }
+}
+
+/// An iterator over the nodes of a tree in depth-first pre-order,
+/// as made by `dfs`.
+pub struct NodeDfsIterator {
+    stack: Vec<Arc<dyn Node>>,
+    seen: HashSet<Arc<dyn Node>>,
+}
+
+impl Iterator for NodeDfsIterator {
+    type Item = Arc<dyn Node>;
+
+    fn next(&mut self) -> Option<Arc<dyn Node>> {
+        loop {
+            let node = self.stack.pop()?;
+            // A shared node may have been pushed again before it was
+            // reached the first time.
+            if !self.seen.insert(node.clone()) {
+                continue;
+            }
+            let seen = &self.seen;
+            let successors = node.successors();
+            self.stack.extend(successors.iter().rev().filter(|n| !seen.contains(*n)).cloned());
+            return Some(node);
+        }
+    }
+}
+
+/// Iterate over `root` and the nodes which can be reached from it by
+/// following successors, each node before its successors.
+///
+/// A node which can be reached in several ways is only given the
+/// first time, as with `bfs`.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+///
+/// let root = RootNode::new();
+/// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// show.add_successor(CommandNode::new("route",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// root.add_successor(show).unwrap();
+/// root.add_successor(CommandNode::new("reload",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// let tree: Arc<dyn Node> = root;
+/// let names = dfs(tree).map(|n| n.name().to_string()).collect::<Vec<_>>();
+/// assert_eq!(names, vec!["__root__", "show", "route", "reload"]);
+/// ```
+pub fn dfs(root: Arc<dyn Node>) -> NodeDfsIterator {
+    NodeDfsIterator {
+        stack: vec![root],
+        seen: HashSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr;
+    use super::*;
+    use parser::{ParseError, Parser};
+    use tokenizer::tokenize;
+
+    #[test]
+    fn metadata() {
+        let node = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        assert!(node.metadata().is_empty());
+        assert_eq!(node.get_meta("audit"), None);
+
+        node.set_metadata("audit", "read").unwrap();
+        node.set_metadata("role", "operator").unwrap();
+        assert_eq!(node.get_meta("audit"), Some("read".to_string()));
+        assert_eq!(node.get_meta("role"), Some("operator".to_string()));
+        assert_eq!(node.metadata().len(), 2);
+
+        node.set_metadata("audit", "write").unwrap();
+        assert_eq!(node.get_meta("audit"), Some("write".to_string()));
+        assert_eq!(node.metadata().len(), 2);
+
+        assert_eq!(node.get_meta("missing"), None);
+    }
+
+    fn flag(name: &str) -> Arc<dyn ParameterNode> {
+        Arc::new(FlagParameterNode::with_options(name,
+                                                 None,
+                                                 false,
+                                                 PRIORITY_PARAMETER,
+                                                 vec![],
+                                                 false,
+                                                 None,
+                                                 false))
+    }
+
+    fn simple(name: &str) -> Arc<dyn ParameterNode> {
+        Arc::new(SimpleParameterNode::new(name,
+                                          None,
+                                          false,
+                                          PRIORITY_PARAMETER,
+                                          vec![],
+                                          false,
+                                          None,
+                                          true))
+    }
+
+    fn named(name: &str) -> Arc<dyn ParameterNode> {
+        Arc::new(NamedParameterNode::with_options(name,
+                                                  None,
+                                                  false,
+                                                  PRIORITY_PARAMETER,
+                                                  vec![],
+                                                  false,
+                                                  None,
+                                                  false))
+    }
+
+    fn names<T: ?Sized + Node>(nodes: &[Arc<T>]) -> Vec<String> {
+        nodes.iter().map(|n| n.name().to_string()).collect()
+    }
+
+    #[test]
+    fn node_identity() {
+        let a: Arc<dyn Node> =
+            CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let b: Arc<dyn Node> =
+            CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        assert!(a == a.clone());
+        assert!(a != b);
+
+        let parameter = flag("force");
+        let upcast: Arc<dyn Node> = parameter.clone();
+        let command = CommandNode::new("copy",
+                                       PRIORITY_DEFAULT,
+                                       false,
+                                       None,
+                                       None,
+                                       vec![parameter]);
+        assert!(command.successors()[0] == upcast);
+    }
+
+    #[test]
+    fn nodes_as_map_keys() {
+        let a: Arc<dyn Node> =
+            CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let b: Arc<dyn Node> =
+            CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let mut counts = HashMap::new();
+        *counts.entry(a.clone()).or_insert(0) += 1;
+        *counts.entry(a.clone()).or_insert(0) += 1;
+        *counts.entry(b.clone()).or_insert(0) += 1;
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&a], 2);
+        assert_eq!(counts[&b], 1);
+    }
+
+    #[test]
+    fn accessors_share_storage() {
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(flag("brief")).unwrap();
+        assert!(Arc::ptr_eq(&show.name(), &show.name()));
+        assert!(Arc::ptr_eq(&show.help_symbol(), &show.help_symbol()));
+        let before = show.successors();
+        assert!(Arc::ptr_eq(&before, &show.successors()));
+
+        show.add_successor(flag("detail")).unwrap();
+        assert_eq!(names(&before), vec!["brief"]);
+        assert_eq!(names(&show.successors()), vec!["brief", "detail"]);
+        show.remove_successor("brief").unwrap();
+        assert_eq!(names(&show.successors()), vec!["detail"]);
+    }
+
+    #[test]
+    fn add_parameter_after_construction() {
+        let command = CommandNode::new("copy",
+                                       PRIORITY_DEFAULT,
+                                       false,
+                                       None,
+                                       None,
+                                       vec![simple("src")]);
+        assert_eq!(names(&command.parameters()), vec!["src"]);
+        assert!(command.successors().is_empty());
+
+        command.add_parameter(simple("dst")).unwrap();
+        command.add_parameter(flag("force")).unwrap();
+        assert_eq!(names(&command.parameters()), vec!["src", "dst", "force"]);
+        assert_eq!(names(&command.successors()), vec!["force"]);
+    }
+
+    #[test]
+    fn repetition_counts() {
+        let (verbose, force): (Arc<dyn Node>, Arc<dyn Node>) = (flag("verbose"), flag("force"));
+        let nodes = vec![verbose.clone(), force.clone(), verbose.clone()];
+        assert_eq!(verbose.as_repeatable().unwrap().current_count(&nodes), 2);
+        assert_eq!(force.as_repeatable().unwrap().current_count(&nodes), 1);
+        assert_eq!(force.as_repeatable().unwrap().current_count(&[]), 0);
+    }
+
+    #[test]
+    fn parameter_counts() {
+        let command = CommandNode::new("copy",
+                                       PRIORITY_DEFAULT,
+                                       false,
+                                       None,
+                                       None,
+                                       vec![]);
+        assert_eq!(command.param_count(), 0);
+        assert_eq!(command.required_param_count(), 0);
+
+        command.add_parameter(simple("src")).unwrap();
+        command.add_parameter(flag("force")).unwrap();
+        command.add_parameter(named("mode")).unwrap();
+        assert_eq!(command.param_count(), 3);
+        assert_eq!(command.required_param_count(), 1);
+    }
+
+    #[test]
+    fn named_parameter_gets_name_node() {
+        let count = named("count");
+        let command = CommandNode::new("ping",
+                                       PRIORITY_DEFAULT,
+                                       false,
+                                       None,
+                                       None,
+                                       vec![count.clone()]);
+        assert_eq!(names(&command.parameters()), vec!["count"]);
+
+        let successors = command.successors();
+        assert_eq!(names(&successors), vec!["count"]);
+        let value: Arc<dyn Node> = count;
+        assert!(successors[0] != value.clone());
+        assert!(*successors[0].successors() == [value.clone()]);
+        let name = successors[0].as_parameter_name().unwrap();
+        assert!(name.linked_value_node() == value);
+    }
+
+    #[test]
+    fn help_text_for_every_node_type() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    Some("Show things.".to_string()),
+                                    None,
+                                    vec![flag("brief"), named("count"), simple("name")]);
+        show.add_successor(KeywordNode::new("for", PRIORITY_DEFAULT, false, None)).unwrap();
+        root.add_successor(show).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+
+        fn walk(node: &dyn Node, seen: &mut Vec<Option<Arc<str>>>) {
+            seen.push(node.help_text());
+            if let Some(command) = node.as_command() {
+                for parameter in command.parameters() {
+                    seen.push(parameter.help_text());
+                }
+            }
+            // Don't follow the wrapper back around to the root.
+            if node.as_wrapper().is_none() {
+                for successor in node.successors().iter() {
+                    walk(&**successor, seen);
+                }
+            }
+        }
+        let mut seen = vec![];
+        walk(&*root, &mut seen);
+        // Root, show, its 3 parameters, brief, the count name and
+        // value, for and help.
+        assert_eq!(seen.len(), 10);
+        assert_eq!(seen[1].as_deref(), Some("Show things."));
+        assert_eq!(seen.iter().filter(|t| t.is_some()).count(), 1);
+    }
+
+    #[test]
+    fn debug_output() {
+        let command = CommandNode::new("copy",
+                                       PRIORITY_DEFAULT,
+                                       true,
+                                       None,
+                                       None,
+                                       vec![simple("src"), flag("force")]);
+        assert_eq!(format!("{:?}", command),
+                   "CommandNode { name: \"copy\", priority: 0, hidden: true, successors: \
+                    [\"force\"], parameters: [\"src\", \"force\"], handler: false }");
+
+        let parameter: Arc<dyn Node> = flag("force");
+        assert_eq!(format!("{:?}", parameter),
+                   "FlagParameterNode { name: \"force\", priority: -10, hidden: false, \
+                    successors: [], required: false, repeatable: false }");
+    }
+
+    #[test]
+    fn dump_shared_tree() {
+        let root = RootNode::new();
+        let interface = CommandNode::new("interface", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(interface.clone()).unwrap();
+        let clear = CommandNode::new("clear",
+                                     PRIORITY_DEFAULT,
+                                     false,
+                                     None,
+                                     None,
+                                     vec![named("count"), simple("name")]);
+        clear.add_successor(interface).unwrap();
+        root.add_successor(show).unwrap();
+        root.add_successor(clear).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+
+        let tree: Arc<dyn Node> = root;
+        assert_eq!(dump_tree(&tree),
+                   "__root__
+  show
+    interface
+  clear
+    count
+      count
+    interface (repeated)
+    name (positional)
+  help
+    show (repeated)
+    clear (repeated)
+    help (repeated)
+");
+    }
+
+    #[test]
+    fn stats_of_shared_tree() {
+        let root = RootNode::new();
+        let interface = CommandNode::new("interface", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(interface.clone()).unwrap();
+        show.add_successor(CommandNode::new("debug",
+                                            PRIORITY_DEFAULT,
+                                            true,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+        let clear = CommandNode::new("clear",
+                                     PRIORITY_DEFAULT,
+                                     false,
+                                     None,
+                                     None,
+                                     vec![named("count"), simple("name"), flag("all")]);
+        clear.add_successor(interface).unwrap();
+        root.add_successor(show).unwrap();
+        root.add_successor(clear).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+
+        // The root, show, interface, debug, clear, both nodes for
+        // count, name, all and help. Only name is required, and the
+        // value of count is the deepest node.
+        let tree: Arc<dyn Node> = root;
+        assert_eq!(compute_stats(&tree),
+                   NodeStats {
+                       nodes: 10,
+                       commands: 4,
+                       max_depth: 3,
+                       hidden: 1,
+                       required_parameters: 1,
+                   });
+
+        let tree: Arc<dyn Node> = RootNode::new();
+        assert_eq!(compute_stats(&tree),
+                   NodeStats {
+                       nodes: 1,
+                       ..NodeStats::default()
+                   });
+    }
+
+    fn parameters(required: bool, repeatable: bool) -> Vec<Arc<dyn ParameterNode>> {
+        vec![Arc::new(FlagParameterNode::with_options("verbose",
+                                                      None,
+                                                      false,
+                                                      PRIORITY_PARAMETER,
+                                                      vec![],
+                                                      repeatable,
+                                                      None,
+                                                      required)),
+             Arc::new(NamedParameterNode::with_options("count",
+                                                       None,
+                                                       false,
+                                                       PRIORITY_PARAMETER,
+                                                       vec![],
+                                                       repeatable,
+                                                       None,
+                                                       required)),
+             Arc::new(SimpleParameterNode::new("file",
+                                               None,
+                                               false,
+                                               PRIORITY_PARAMETER,
+                                               vec![],
+                                               repeatable,
+                                               None,
+                                               required))]
+    }
+
+    fn help_symbols(required: bool, repeatable: bool) -> Vec<String> {
+        let command = CommandNode::new("test",
+                                       PRIORITY_DEFAULT,
+                                       false,
+                                       None,
+                                       None,
+                                       parameters(required, repeatable));
+        let mut symbols = command.successors()
+                                 .iter()
+                                 .map(|n| n.help_symbol().to_string())
+                                 .collect::<Vec<_>>();
+        symbols.extend(command.parameters().iter().map(|p| p.help_symbol().to_string()));
+        symbols
+    }
+
+    #[test]
+    fn parameter_help_symbols() {
+        // The successors, verbose and the name of count, followed by
+        // the parameters, verbose, the value of count and file.
+        assert_eq!(help_symbols(true, false),
+                   vec!["verbose", "count <count>", "verbose", "<count>", "<file>"]);
+        assert_eq!(help_symbols(false, false),
+                   vec!["[verbose]", "[count <count>]", "[verbose]", "<count>", "[<file>]"]);
+        assert_eq!(help_symbols(true, true),
+                   vec!["verbose...", "count <count>...", "verbose...", "<count>...", "<file>..."]);
+        assert_eq!(help_symbols(false, true),
+                   vec!["[verbose...]",
+                        "[count <count>...]",
+                        "[verbose...]",
+                        "<count>...",
+                        "[<file>...]"]);
+    }
+
+    fn usage(required: bool, repeatable: bool) -> String {
+        CommandNode::new("test",
+                         PRIORITY_DEFAULT,
+                         false,
+                         None,
+                         None,
+                         parameters(required, repeatable))
+            .usage_string()
+    }
+
+    #[test]
+    fn usage_strings() {
+        assert_eq!(usage(true, false), "test verbose count <count> <file>");
+        assert_eq!(usage(false, false), "test [verbose] [count <count>] [<file>]");
+        assert_eq!(usage(true, true), "test verbose... count <count>... <file>...");
+        assert_eq!(usage(false, true), "test [verbose...] [count <count>...] [<file>...]");
+
+        let interface = CommandNode::new("interface",
+                                         PRIORITY_DEFAULT,
+                                         false,
+                                         None,
+                                         None,
+                                         vec![simple("name"), flag("verbose")]);
+        interface.add_parameter(Arc::new(FlagParameterNode::with_options("debug",
+                                                                         None,
+                                                                         true,
+                                                                         PRIORITY_PARAMETER,
+                                                                         vec![],
+                                                                         false,
+                                                                         None,
+                                                                         false))).unwrap();
+        assert_eq!(interface.usage_string(), "interface <name> [verbose]");
+    }
+
+    /// A node which only matches numbers.
+    struct NumberNode {
+        node_fields: NodeFields,
+    }
+
+    impl fmt::Debug for NumberNode {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            debug_node(f, "NumberNode", self).finish()
+        }
+    }
+
+    impl Node for NumberNode {
+        fn node_data(&self) -> &NodeFields {
+            &self.node_fields
+        }
+
+        fn matches(&self, token: &str) -> bool {
+            token.parse::<u32>().is_ok()
+        }
+
+        fn accept(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+            values.insert(self.name().to_string(), vec![token.to_string()]);
+        }
+    }
+
+    /// A parameter which only accepts numbers as its value.
+    struct NumberParameter {
+        parameter: SimpleParameterNode,
+        positional: bool,
+    }
+
+    impl fmt::Debug for NumberParameter {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            debug_node(f, "NumberParameter", self).finish()
+        }
+    }
+
+    impl Node for NumberParameter {
+        fn node_data(&self) -> &NodeFields {
+            self.parameter.node_data()
+        }
+
+        fn matches(&self, token: &str) -> bool {
+            token.parse::<u32>().is_ok()
+        }
+
+        fn accept(&self, token: &str, values: &mut HashMap<String, Vec<String>>) {
+            self.record_value(token, values);
+        }
+
+        fn as_repeatable(&self) -> Option<&dyn RepeatableNode> {
+            Some(self)
+        }
+
+        fn as_parameter(&self) -> Option<&dyn ParameterNode> {
+            Some(self)
+        }
+    }
+
+    impl RepeatableNode for NumberParameter {
+        fn repeatable_data(&self) -> &RepeatableNodeFields {
+            self.parameter.repeatable_data()
+        }
+
+        fn repeatable_data_mut(&mut self) -> &mut RepeatableNodeFields {
+            self.parameter.repeatable_data_mut()
+        }
+    }
+
+    impl ParameterNode for NumberParameter {
+        fn parameter_data(&self) -> &ParameterNodeFields {
+            self.parameter.parameter_data()
+        }
+
+        fn parameter_data_mut(&mut self) -> &mut ParameterNodeFields {
+            self.parameter.parameter_data_mut()
+        }
+
+        fn positional(&self) -> bool {
+            self.positional
+        }
+    }
+
+    fn number_parameter(name: &str, positional: bool) -> Arc<dyn ParameterNode> {
+        Arc::new(NumberParameter {
+            parameter: SimpleParameterNode::new(name,
+                                                None,
+                                                false,
+                                                PRIORITY_PARAMETER,
+                                                vec![],
+                                                false,
+                                                None,
+                                                true),
+            positional,
+        })
+    }
+
+    fn fuzzy(max_distance: usize) -> FuzzyMatchNode {
+        FuzzyMatchNode::new(SimpleParameterNode::new("interface",
+                                                     None,
+                                                     false,
+                                                     PRIORITY_PARAMETER,
+                                                     vec![],
+                                                     false,
+                                                     None,
+                                                     true),
+                            vec!["ethernet".to_string(), "loopback".to_string()],
+                            max_distance)
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        let node = fuzzy(2);
+        assert_eq!(node.nearest("ethernet"), Some("ethernet"));
+        assert_eq!(node.nearest("etherent"), Some("ethernet"));
+        assert_eq!(node.nearest("lopback"), Some("loopback"));
+        assert_eq!(node.nearest("lpbck"), None);
+        assert!(!node.matches("wifi"));
+        assert!(!fuzzy(0).matches("lopback"));
+        assert!(!node.matches("LOOPBACK"));
+        node.set_case_sensitivity(CaseSensitivity::Insensitive).unwrap();
+        assert_eq!(node.nearest("LOPBACK"), Some("loopback"));
+
+        let mut values = HashMap::new();
+        node.accept("ethrnet", &mut values);
+        assert_eq!(values["interface"], vec!["ethernet"]);
+
+        let show = CommandNode::new("show",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    None,
+                                    Some(Box::new(|_| Ok(None))),
+                                    vec![Arc::new(fuzzy(1))]);
+        let root = RootNode::new();
+        root.add_successor(show).unwrap();
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("show loopbak").is_ok());
+        assert!(parser.verify().is_ok());
+        let mut parser = Parser::new(root);
+        match parser.parse("show lopbak") {
+            Err(ParseError::InvalidParameterValue { parameter, .. }) => {
+                assert_eq!(parameter, "interface")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn invalid_parameter_values() {
+        let ping = CommandNode::new("ping",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    None,
+                                    Some(Box::new(|_| Ok(None))),
+                                    vec![number_parameter("count", false),
+                                         number_parameter("size", true)]);
+        let root = RootNode::new();
+        root.add_successor(ping).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("ping count 3 64").is_ok());
+        assert!(parser.verify().is_ok());
+
+        let mut parser = Parser::new(root.clone());
+        match parser.parse("ping count three") {
+            Err(error @ ParseError::InvalidParameterValue { .. }) => {
+                assert_eq!(error.to_string(),
+                           "'three' is not a valid value for 'count': expected <count>");
+                // The value is pointed at, rather than the name.
+                assert_eq!(error.diagnostic("ping count three").unwrap(),
+                           "ping count three\n           ^~~~~");
+                if let ParseError::InvalidParameterValue { parameter, index, .. } = error {
+                    assert_eq!(parameter, "count");
+                    assert_eq!(index, 2);
+                }
+            }
+            _ => panic!(),
+        }
+
+        let mut parser = Parser::new(root);
+        match parser.parse("ping large") {
+            Err(error @ ParseError::InvalidParameterValue { .. }) => {
+                assert_eq!(error.to_string(),
+                           "'large' is not a valid value for 'size': expected <size>");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn matching() {
+        let root = RootNode::new();
+        assert!(!root.matches(""));
+        assert!(!root.matches("__root__"));
+
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        assert!(show.matches("sh"));
+        assert!(show.matches("show"));
+        assert!(!show.matches("shows"));
+
+        assert!(flag("force").matches("force"));
+        assert!(!flag("force").matches("f"));
+        assert!(flag("force").completes("f"));
+        assert!(!flag("force").completes("x"));
+        assert!(named("count").matches("5"));
+        assert!(simple("file").matches("anything"));
+    }
+
+    #[test]
+    fn case_insensitive_matching() {
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![flag("all")]);
+        let root = RootNode::new();
+        root.add_successor(show.clone()).unwrap();
+        assert_eq!(show.case_sensitivity(), CaseSensitivity::Sensitive);
+        assert!(!show.matches("SH"));
+
+        let root = root.with_case_sensitivity(CaseSensitivity::Insensitive).unwrap();
+        assert_eq!(root.case_sensitivity(), CaseSensitivity::Insensitive);
+        assert!(show.matches("SH"));
+        assert!(show.matches("Show"));
+        assert!(!show.matches("SHOWS"));
+        assert!(show.successors()[0].matches("ALL"));
+
+        // The name is offered with its own case.
+        let token = tokenize("sH").unwrap()[0];
+        let completion = show.complete(Some(token));
+        assert_eq!(completion.options[0].option_string, "show");
+        assert_eq!(completion.token.unwrap().text, "sH");
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("SHOW ALL").is_ok());
+        assert_eq!(parser.nodes.len(), 2);
+
+        root.with_case_sensitivity(CaseSensitivity::Sensitive).unwrap();
+        assert!(!show.matches("SH"));
+    }
+
+    #[test]
+    fn accepting() {
+        let mut values = HashMap::new();
+        CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![])
+            .accept("show", &mut values);
+        assert!(values.is_empty());
+
+        simple("file").accept("a", &mut values);
+        simple("file").accept("b", &mut values);
+        named("count").accept("5", &mut values);
+        assert_eq!(values["file"], vec!["a", "b"]);
+        assert_eq!(values["count"], vec!["5"]);
+    }
+
+    #[test]
+    fn custom_matching_node() {
+        let port = Arc::new(NumberNode {
+            node_fields: NodeFields::new("port",
+                                         "<port>".to_string(),
+                                         None,
+                                         false,
+                                         PRIORITY_PARAMETER,
+                                         vec![]),
+        });
+        let connect = CommandNode::new("connect", PRIORITY_DEFAULT, false, None, None, vec![]);
+        connect.add_successor(port).unwrap();
+        let root = RootNode::new();
+        root.add_successor(connect).unwrap();
+
+        let mut parser = Parser::new(root.clone());
+        assert!(parser.parse("connect 80").is_ok());
+        let mut parser = Parser::new(root);
+        assert!(parser.parse("connect http").is_err());
+    }
+
+    #[test]
+    fn completion() {
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let completion = show.complete(None);
+        assert_eq!(completion.help_symbol, "show");
+        assert!(completion.exhaustive);
+        assert_eq!(completion.options[0].option_string, "show");
+        assert!(completion.options[0].complete);
+
+        let tokens = tokenize("5").unwrap();
+        let completion = named("count").complete(Some(tokens[0]));
+        assert_eq!(completion.help_symbol, "<count>");
+        assert!(!completion.exhaustive);
+        assert_eq!(completion.options.len(), 1);
+        assert_eq!(completion.options[0].option_string, "5");
+        assert!(!completion.options[0].complete);
+    }
+
+    #[test]
+    fn subcommands_of_command() {
+        let show = CommandNode::new("show",
+                                    PRIORITY_DEFAULT,
+                                    false,
+                                    None,
+                                    None,
+                                    vec![flag("verbose"), named("count"), simple("name")]);
+        show.add_successor(CommandNode::new("route",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+        show.add_successor(KeywordNode::new("all", PRIORITY_DEFAULT, false, None)).unwrap();
+        show.add_successor(CommandNode::new("interface",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+        show.add_successor(PositionalCommandNode::new("verb",
+                                                      PRIORITY_DEFAULT,
+                                                      None,
+                                                      None,
+                                                      vec![])).unwrap();
+        let commands = subcommands(&*show);
+        assert_eq!(names(&commands), vec!["route", "interface", "verb"]);
+        assert!(commands.iter().all(|c| c.parameters().is_empty()));
+        // The command of a positional node is shared, not copied.
+        assert!(ptr::eq(&*commands[2], show.successors().last().unwrap().as_command().unwrap()));
+
+        let root = RootNode::new();
+        assert!(subcommands(&*root).is_empty());
+        root.add_successor(show).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+        assert_eq!(names(&subcommands(&*root)), vec!["show"]);
+    }
+
+    fn ancestor_names(root: &Arc<dyn Node>, target: &dyn Node) -> Option<Vec<String>> {
+        ancestors(root, target).map(|path| names(&path))
+    }
+
+    #[test]
+    fn ancestors_of_nodes() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let route = CommandNode::new("route", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(route.clone()).unwrap();
+        root.add_successor(show.clone()).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+        let tree: Arc<dyn Node> = root;
+
+        assert_eq!(ancestor_names(&tree, &*tree), Some(vec!["__root__".to_string()]));
+        assert_eq!(ancestor_names(&tree, &*show),
+                   Some(vec!["__root__".to_string(), "show".to_string()]));
+        assert_eq!(ancestor_names(&tree, &*route),
+                   Some(vec!["__root__".to_string(), "show".to_string(), "route".to_string()]));
+
+        let other = CommandNode::new("route", PRIORITY_DEFAULT, false, None, None, vec![]);
+        assert_eq!(ancestor_names(&tree, &*other), None);
+    }
+
+    fn shared_tree() -> Arc<dyn Node> {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let clear = CommandNode::new("clear", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let counters = KeywordNode::new("counters", PRIORITY_DEFAULT, false, None);
+        counters.add_successor(KeywordNode::new("all", PRIORITY_DEFAULT, false, None)).unwrap();
+        show.add_successor(counters.clone()).unwrap();
+        clear.add_successor(counters).unwrap();
+        root.add_successor(show).unwrap();
+        root.add_successor(clear).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+        root
+    }
+
+    #[test]
+    fn roots() {
+        let tree = shared_tree();
+        assert!(tree.is_root());
+        assert!(!bfs(tree).skip(1).any(|n| n.is_root()));
+
+        // Only a `RootNode` is a root, whatever its name.
+        assert!(RootNode::with_name("git").is_root());
+        assert!(!KeywordNode::new("__root__", PRIORITY_DEFAULT, false, None).is_root());
+    }
+
+    #[test]
+    fn leaves() {
+        let tree = shared_tree();
+        let leaves = bfs(tree.clone()).filter(|n| n.is_leaf()).collect::<Vec<_>>();
+        assert_eq!(names(&leaves), vec!["all"]);
+        assert!(!tree.is_leaf());
+        // A wrapper has the successors of the node it wraps.
+        let help = resolve(&tree, &"help".parse().unwrap()).unwrap();
+        assert!(!help.is_leaf());
+    }
+
+    #[test]
+    fn bfs_visits_shared_nodes_once() {
+        assert_eq!(names(&bfs(shared_tree()).collect::<Vec<_>>()),
+                   vec!["__root__", "show", "clear", "help", "counters", "all"]);
+    }
+
+    #[test]
+    fn dfs_and_bfs_orders() {
+        let tree = shared_tree();
+        let dfs_names = names(&dfs(tree.clone()).collect::<Vec<_>>());
+        assert_eq!(dfs_names, vec!["__root__", "show", "counters", "all", "clear", "help"]);
+        let mut bfs_names = names(&bfs(tree).collect::<Vec<_>>());
+        assert_ne!(dfs_names, bfs_names);
+        // Both visit the same nodes.
+        bfs_names.sort();
+        let mut dfs_names = dfs_names;
+        dfs_names.sort();
+        assert_eq!(dfs_names, bfs_names);
+    }
+
+    #[test]
+    fn node_paths() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(KeywordNode::new("interface", PRIORITY_DEFAULT, false, None)).unwrap();
+        root.add_successor(show.clone()).unwrap();
+        let tree: Arc<dyn Node> = root;
+
+        let path = "show.interface".parse::<NodePath>().unwrap();
+        assert_eq!(path, NodePath(vec!["show".to_string(), "interface".to_string()]));
+        assert_eq!(path.to_string(), "show.interface");
+        assert_eq!(&*resolve(&tree, &path).unwrap().name(), "interface");
+        assert_eq!(resolve(&tree, &"".parse().unwrap()), Some(tree.clone()));
+        assert!(resolve(&tree, &"show.interface.eth0".parse().unwrap()).is_none());
+        // Names must be given in full.
+        assert!(resolve(&tree, &"sh".parse().unwrap()).is_none());
+
+        match "show..eth0".parse::<NodePath>() {
+            Err(error @ NodePathError::EmptyName { index: 1, .. }) => {
+                assert_eq!(error.to_string(), "name 1 of the path 'show..eth0' is empty")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn canonical_paths() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let route = CommandNode::new("route", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(route.clone()).unwrap();
+        root.add_successor(show.clone()).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+        let tree: Arc<dyn Node> = root;
+
+        assert_eq!(canonical_path(&tree, &show).as_deref(), Some("show"));
+        let path = canonical_path(&tree, &route).unwrap();
+        assert_eq!(path, "show.route");
+        assert!(*resolve(&tree, &path.parse().unwrap()).unwrap() == *(route as Arc<dyn Node>));
+
+        let reload = CommandNode::new("reload", PRIORITY_DEFAULT, false, None, None, vec![]);
+        assert_eq!(canonical_path(&tree, &reload), None);
+    }
+
+    #[test]
+    fn mixed_nodes() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let count = named("count");
+        let nodes: Vec<Arc<dyn Node>> =
+            vec![root.clone(),
+                 show.clone(),
+                 KeywordNode::new("to", PRIORITY_DEFAULT, false, None),
+                 WrapperNode::new("help", None, root.clone()),
+                 SubparserNode::new("route", RootNode::new()),
+                 ParameterNameNode::new("count", false, None, None, None, count.clone()),
+                 flag("force"),
+                 count,
+                 simple("file")];
+        let kinds = nodes.iter()
+                         .map(|n| {
+                             (n.as_command().is_some(),
+                              n.as_wrapper().is_some(),
+                              n.as_subparser().is_some(),
+                              n.as_repeatable().is_some(),
+                              n.as_parameter().is_some())
+                         })
+                         .collect::<Vec<_>>();
+        assert_eq!(kinds,
+                   vec![(false, false, false, false, false),
+                        (true, false, false, false, false),
+                        (false, false, false, false, false),
+                        (false, true, false, false, false),
+                        (false, false, true, false, false),
+                        (false, false, false, true, false),
+                        (false, false, false, true, true),
+                        (false, false, false, true, true),
+                        (false, false, false, true, true)]);
+
+        let tokens = tokenize("x").unwrap();
+        for node in &nodes {
+            let _ = node.node_data();
+            let _ = node.help_symbol();
+            let _ = node.help_text();
+            assert!(!node.hidden());
+            assert!(!node.name().is_empty());
+            let _ = node.priority();
+            let _ = node.successors();
+            let _ = node.matches("x");
+            let _ = node.complete(Some(tokens[0]));
+            let mut values = HashMap::new();
+            node.accept("x", &mut values);
+            assert!(node.available());
+            node.set_metadata("kind", "test").unwrap();
+            assert_eq!(node.metadata().len(), 1);
+            assert_eq!(node.get_meta("kind"), Some("test".to_string()));
+            node.set_required_role("admin").unwrap();
+            assert_eq!(node.required_role(), Some("admin".to_string()));
+            let _ = format!("{:?}", node);
         }
+        show.add_successor(nodes[2].clone()).unwrap();
+        assert_eq!(show.successors().len(), 1);
     }
 }
@@ -0,0 +1,330 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Observers
+//!
+//! An `Observer` added to a `Parser` with `Parser::add_observer` is
+//! told about each token that is accepted, each command that is
+//! executed and each error along the way, as for an audit log or
+//! metrics, without wrapping every handler.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use commands::parser::nodes::*;
+//! use commands::parser::observers::Observer;
+//! use commands::parser::values::ParameterValues;
+//! use commands::parser::Parser;
+//!
+//! struct AuditLog(Arc<Mutex<Vec<String>>>);
+//!
+//! impl Observer for AuditLog {
+//!     fn on_execute(&self, command: &str, _values: &ParameterValues) {
+//!         self.0.lock().unwrap().push(command.to_string());
+//!     }
+//! }
+//!
+//! let root = RootNode::new();
+//! root.add_successor(CommandNode::new("reload",
+//!                                     PRIORITY_DEFAULT,
+//!                                     false,
+//!                                     None,
+//!                                     Some(Box::new(|_: &HandlerContext| Ok(None))),
+//!                                     vec![])).unwrap();
+//!
+//! let log = Arc::new(Mutex::new(vec![]));
+//! let mut parser = Parser::new(root);
+//! parser.add_observer(Box::new(AuditLog(log.clone())));
+//! parser.parse("reload").unwrap();
+//! parser.execute().unwrap();
+//! assert_eq!(*log.lock().unwrap(), vec!["reload"]);
+//! ```
+//!
+//! A `CommandStatistics` is an observer which counts how often each
+//! command is executed and how long it takes.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use parser::nodes::Node;
+use parser::values::ParameterValues;
+use parser::{ExecuteError, ParseError, VerifyError};
+use tokenizer::Token;
+
+/// Told about what a `Parser` does, once added to it with
+/// `Parser::add_observer`. Each method does nothing unless it is
+/// implemented.
+///
+/// Observers are only told about lines which are parsed or
+/// executed, and not about those which `Parser::complete_at`
+/// completes or `Parser::check` checks.
+///
+/// An observer which panics is skipped, and the parser carries on
+/// as if it had returned, as the parser doesn't change while its
+/// observers run. When the crate using this one is built with
+/// `panic = "abort"`, the panic can't be caught, and aborts the
+/// process.
+///
+/// Observers are `Send` and `Sync`, so that one which is shared,
+/// like the clones of a `CommandStatistics`, can be told about the
+/// parsers of several threads.
+pub trait Observer: Send + Sync {
+    /// `node` has been accepted for `token`, whose text is bound to
+    /// the node when it is a parameter.
+    ///
+    /// When backtracking accepts other nodes for tokens which had
+    /// been accepted, those nodes are observed again.
+    fn on_accept(&self, _node: &dyn Node, _token: &Token) {}
+
+    /// The handler of `command`, the path of the command's name,
+    /// as in `show interfaces`, is about to be run with `values`.
+    ///
+    /// When a `WrapperNode` was accepted first, its handler is the
+    /// one which runs, and `command` is the wrapper's name, as in
+    /// `help` for `help show interfaces`.
+    fn on_execute(&self, _command: &str, _values: &ParameterValues) {}
+
+    /// The handler of `command` has returned `result` after
+    /// `elapsed`, the wall time that it took.
+    fn on_result(&self,
+                 _command: &str,
+                 _result: &Result<Option<String>, ExecuteError>,
+                 _elapsed: Duration) {
+    }
+
+    /// A token couldn't be accepted, or a line couldn't be tokenized.
+    fn on_parse_error(&self, _error: &ParseError) {}
+
+    /// The accepted command failed `Parser::verify`. As `verify` may
+    /// be called after each token to report problems as the user
+    /// types, this may be called for a line that is still being
+    /// given.
+    fn on_verify_error(&self, _errors: &[VerifyError]) {}
+}
+
+/// How often a command has been executed, and how long it took, as
+/// counted by `CommandStatistics`.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct CommandStats {
+    /// The number of times that the handler was run.
+    pub executions: u64,
+    /// The number of those times that it failed.
+    pub failures: u64,
+    /// The wall time that the handler took over all of its runs.
+    pub total: Duration,
+    /// The wall time that the handler took when it was last run.
+    pub last: Duration,
+}
+
+/// An `Observer` which counts how often each command is executed
+/// and how long its handler takes, keyed by the path of the
+/// command's name, as in `show interfaces`.
+///
+/// As for `Observer::on_execute`, a command run through a wrapper
+/// is counted under the wrapper's name, so `help show interfaces`
+/// is counted as `help`.
+///
+/// Clones share their statistics, so one can be added to each
+/// `Parser` made for a tree, while another is kept to report on
+/// all of them. Nothing is counted for parsers that it hasn't been
+/// added to.
+///
+/// The counters of each command are atomic, so parsers on several
+/// threads only wait on each other the first time that a command is
+/// counted, to add it. The counters are read one by one, so the
+/// statistics of a command which is being counted may only include
+/// part of its last run.
+///
+/// ```
+/// use commands::parser::nodes::*;
+/// use commands::parser::observers::CommandStatistics;
+/// use commands::parser::Parser;
+///
+/// let root = RootNode::new();
+/// root.add_successor(CommandNode::new("reload",
+///                                     PRIORITY_DEFAULT,
+///                                     false,
+///                                     None,
+///                                     Some(Box::new(|_: &HandlerContext| Ok(None))),
+///                                     vec![])).unwrap();
+///
+/// let statistics = CommandStatistics::new();
+/// for _ in 0..2 {
+///     let mut parser = Parser::new(root.clone());
+///     parser.add_observer(Box::new(statistics.clone()));
+///     parser.parse("reload").unwrap();
+///     parser.execute().unwrap();
+/// }
+/// assert_eq!(statistics.get("reload").unwrap().executions, 2);
+/// ```
+#[derive(Clone,Debug,Default)]
+pub struct CommandStatistics {
+    commands: Arc<RwLock<BTreeMap<String, Arc<Counters>>>>,
+}
+
+/// The counters behind the `CommandStats` of a command, with the
+/// times in nanoseconds.
+#[derive(Debug,Default)]
+struct Counters {
+    executions: AtomicU64,
+    failures: AtomicU64,
+    total: AtomicU64,
+    last: AtomicU64,
+}
+
+impl Counters {
+    fn stats(&self) -> CommandStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        CommandStats {
+            executions: load(&self.executions),
+            failures: load(&self.failures),
+            total: Duration::from_nanos(load(&self.total)),
+            last: Duration::from_nanos(load(&self.last)),
+        }
+    }
+}
+
+impl CommandStatistics {
+    /// Construct a `CommandStatistics` which hasn't counted anything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The statistics of the command at `path`, if it has been
+    /// executed since they were last `reset`.
+    pub fn get(&self, path: &str) -> Option<CommandStats> {
+        self.commands.read().unwrap().get(path).map(|c| c.stats())
+    }
+
+    /// The statistics of each command which has been executed since
+    /// they were last `reset`, ordered by path.
+    pub fn report(&self) -> Vec<(String, CommandStats)> {
+        let commands = self.commands.read().unwrap();
+        commands.iter().map(|(path, counters)| (path.clone(), counters.stats())).collect()
+    }
+
+    /// Forget everything which has been counted.
+    pub fn reset(&self) {
+        self.commands.write().unwrap().clear();
+    }
+
+    /// The counters of `command`, which are added when it hasn't
+    /// been counted yet.
+    fn counters(&self, command: &str) -> Arc<Counters> {
+        if let Some(counters) = self.commands.read().unwrap().get(command) {
+            return counters.clone();
+        }
+        self.commands.write().unwrap().entry(command.to_string()).or_default().clone()
+    }
+}
+
+impl Observer for CommandStatistics {
+    fn on_result(&self,
+                 command: &str,
+                 result: &Result<Option<String>, ExecuteError>,
+                 elapsed: Duration) {
+        let counters = self.counters(command);
+        let nanos = elapsed.as_nanos() as u64;
+        counters.executions.fetch_add(1, Ordering::Relaxed);
+        counters.failures.fetch_add(result.is_err() as u64, Ordering::Relaxed);
+        counters.total.fetch_add(nanos, Ordering::Relaxed);
+        counters.last.store(nanos, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+    use super::*;
+    use parser::nodes::*;
+    use parser::Parser;
+
+    fn tree() -> Arc<RootNode> {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        show.add_successor(CommandNode::new("route",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(|_: &HandlerContext| Ok(None))),
+                                            vec![])).unwrap();
+        root.add_successor(show).unwrap();
+        root.add_successor(CommandNode::new("reload",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            Some(Box::new(|_: &HandlerContext| {
+                                                Err(HandlerError::new("not now"))
+                                            })),
+                                            vec![])).unwrap();
+        root.add_successor(WrapperNode::with_options("help",
+                                                     PRIORITY_DEFAULT,
+                                                     false,
+                                                     None,
+                                                     Some(Box::new(|_: &HandlerContext| Ok(None))),
+                                                     root.clone())).unwrap();
+        root
+    }
+
+    fn run(statistics: &CommandStatistics, line: &str) {
+        let mut parser = Parser::new(tree());
+        parser.add_observer(Box::new(statistics.clone()));
+        parser.parse(line).unwrap();
+        let _ = parser.execute();
+    }
+
+    #[test]
+    fn statistics() {
+        let statistics = CommandStatistics::new();
+        run(&statistics, "show route");
+        run(&statistics, "show route");
+        run(&statistics, "reload");
+        let paths = statistics.report().into_iter().map(|(p, _)| p).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["reload", "show route"]);
+
+        let route = statistics.get("show route").unwrap();
+        assert_eq!(route.executions, 2);
+        assert_eq!(route.failures, 0);
+        assert!(route.total >= route.last);
+        assert_eq!(statistics.get("reload").unwrap().failures, 1);
+        assert!(statistics.get("show").is_none());
+
+        statistics.reset();
+        assert!(statistics.report().is_empty());
+        assert_eq!(statistics.get("show route"), None);
+        run(&statistics, "reload");
+        assert_eq!(statistics.get("reload").unwrap().executions, 1);
+    }
+
+    #[test]
+    fn wrapped_commands_are_counted_as_the_wrapper() {
+        let statistics = CommandStatistics::new();
+        run(&statistics, "help show route");
+        run(&statistics, "help");
+        assert_eq!(statistics.get("help").unwrap().executions, 2);
+        assert!(statistics.get("show route").is_none());
+    }
+
+    #[test]
+    fn statistics_are_shared_between_threads() {
+        let statistics = CommandStatistics::new();
+        let threads = (0..4)
+            .map(|_| {
+                let statistics = statistics.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        run(&statistics, "show route");
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(statistics.get("show route").unwrap().executions, 40);
+    }
+}
@@ -0,0 +1,200 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Patches
+//!
+//! Incremental updates to a command tree which hasn't been frozen,
+//! as when a plugin adds its commands to those of an application,
+//! without building the whole tree again.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use commands::parser::nodes::*;
+//! use commands::parser::patch::{apply, NodePatch};
+//!
+//! let root = RootNode::new();
+//! root.add_successor(CommandNode::new("show",
+//!                                     PRIORITY_DEFAULT,
+//!                                     false,
+//!                                     None,
+//!                                     None,
+//!                                     vec![])).unwrap();
+//! let tree: Arc<dyn Node> = root;
+//!
+//! let route = CommandNode::new("route", PRIORITY_DEFAULT, false, None, None, vec![]);
+//! apply(&tree,
+//!       &NodePatch::AddSuccessor {
+//!           parent_path: "show".parse().unwrap(),
+//!           node: route,
+//!       })
+//!     .unwrap();
+//! assert!(resolve(&tree, &"show.route".parse().unwrap()).is_some());
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use parser::nodes::{resolve, Node, NodePath};
+
+/// An update to a command tree, applied with `apply`.
+pub enum NodePatch {
+    /// Add `node` as a successor of the node at `parent_path`.
+    AddSuccessor {
+        /// The path of the node to add a successor to.
+        parent_path: NodePath,
+        /// The node to add.
+        node: Arc<dyn Node>,
+    },
+    /// Remove the successor called `name` from the node at
+    /// `parent_path`.
+    RemoveSuccessor {
+        /// The path of the node to remove a successor from.
+        parent_path: NodePath,
+        /// The name of the successor.
+        name: String,
+    },
+    /// Replace the help text of the node at `path`.
+    SetHelp {
+        /// The path of the node.
+        path: NodePath,
+        /// The new help text.
+        help: String,
+    },
+}
+
+/// Errors that applying a `NodePatch` can raise.
+#[derive(Clone,Debug,PartialEq)]
+pub enum PatchError {
+    /// There is no node at the path.
+    NotFound(NodePath),
+    /// The node at the path has no successor with the name.
+    NoSuchSuccessor {
+        /// The path of the node.
+        parent_path: NodePath,
+        /// The name of the missing successor.
+        name: String,
+    },
+    /// The node at the path has been frozen.
+    Frozen(NodePath),
+}
+
+impl Error for PatchError {}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PatchError::NotFound(ref path) => write!(f, "no node at '{}'", path),
+            PatchError::NoSuchSuccessor { ref parent_path, ref name } => {
+                write!(f, "node '{}' has no successor '{}'", parent_path, name)
+            }
+            PatchError::Frozen(ref path) => {
+                write!(f, "node '{}' is frozen and can't be modified", path)
+            }
+        }
+    }
+}
+
+/// Apply `patch` to the tree beneath `root`.
+///
+/// Nothing is changed when an error is returned.
+pub fn apply(root: &Arc<dyn Node>, patch: &NodePatch) -> Result<(), PatchError> {
+    match *patch {
+        NodePatch::AddSuccessor { ref parent_path, ref node } => {
+            let frozen = |_| PatchError::Frozen(parent_path.clone());
+            find(root, parent_path)?.add_successor(node.clone()).map_err(frozen)?;
+        }
+        NodePatch::RemoveSuccessor { ref parent_path, ref name } => {
+            let parent = find(root, parent_path)?;
+            let frozen = |_| PatchError::Frozen(parent_path.clone());
+            let removed = parent.remove_successor(name).map_err(frozen)?;
+            if removed.is_none() {
+                return Err(PatchError::NoSuchSuccessor {
+                    parent_path: parent_path.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+        NodePatch::SetHelp { ref path, ref help } => {
+            let node = find(root, path)?;
+            node.set_help_text(Some(help.clone())).map_err(|_| PatchError::Frozen(path.clone()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Find the node at `path`.
+fn find(root: &Arc<dyn Node>, path: &NodePath) -> Result<Arc<dyn Node>, PatchError> {
+    resolve(root, path).ok_or_else(|| PatchError::NotFound(path.clone()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::fixtures::{path, show_tree};
+    use parser::nodes::*;
+
+    fn tree() -> Arc<dyn Node> {
+        show_tree(&[("route", PRIORITY_DEFAULT)])
+    }
+
+    #[test]
+    fn add_successor() {
+        let tree = tree();
+        let patch = NodePatch::AddSuccessor {
+            parent_path: path("show"),
+            node: CommandNode::new("version", PRIORITY_DEFAULT, false, None, None, vec![]),
+        };
+        assert!(apply(&tree, &patch).is_ok());
+        assert!(resolve(&tree, &path("show.version")).is_some());
+
+        let patch = NodePatch::AddSuccessor {
+            parent_path: path("reload"),
+            node: CommandNode::new("now", PRIORITY_DEFAULT, false, None, None, vec![]),
+        };
+        assert_eq!(apply(&tree, &patch), Err(PatchError::NotFound(path("reload"))));
+    }
+
+    #[test]
+    fn remove_successor() {
+        let tree = tree();
+        let patch = NodePatch::RemoveSuccessor {
+            parent_path: path("show"),
+            name: "route".to_string(),
+        };
+        assert!(apply(&tree, &patch).is_ok());
+        assert!(resolve(&tree, &path("show.route")).is_none());
+        match apply(&tree, &patch) {
+            Err(error @ PatchError::NoSuchSuccessor { .. }) => {
+                assert_eq!(error.to_string(), "node 'show' has no successor 'route'")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn set_help() {
+        let tree = tree();
+        let patch = NodePatch::SetHelp {
+            path: path("show.route"),
+            help: "Show the routing table.".to_string(),
+        };
+        assert!(apply(&tree, &patch).is_ok());
+        let route = resolve(&tree, &path("show.route")).unwrap();
+        assert_eq!(route.help_text().as_deref(), Some("Show the routing table."));
+    }
+
+    #[test]
+    fn frozen_trees_are_not_patched() {
+        let tree = tree();
+        tree.freeze();
+        let patch = NodePatch::SetHelp {
+            path: path("show"),
+            help: "Show things.".to_string(),
+        };
+        assert_eq!(apply(&tree, &patch), Err(PatchError::Frozen(path("show"))));
+        assert_eq!(resolve(&tree, &path("show")).unwrap().help_text(), None);
+    }
+}
@@ -0,0 +1,107 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Permissions
+//!
+//! Nodes may be restricted to users with a particular role
+//! using `Node::set_required_role`. When a `Parser` has been
+//! given a `PermissionChecker`, nodes which the user is not
+//! permitted to use are not completed and can't be parsed.
+//!
+//! ```
+//! use commands::parser::nodes::*;
+//! use commands::parser::permissions::RoleChecker;
+//! use commands::parser::Parser;
+//!
+//! let root = RootNode::new();
+//! let reload = CommandNode::new("reload", PRIORITY_DEFAULT, false, None, None, vec![]);
+//! reload.set_required_role("admin").unwrap();
+//! root.add_successor(reload).unwrap();
+//!
+//! let mut parser = Parser::new(root);
+//! parser.set_permission_checker(Box::new(RoleChecker), "operator");
+//! assert!(parser.complete(None).is_empty());
+//! ```
+
+use parser::nodes::Node;
+
+/// Decide whether a user may use a node.
+pub trait PermissionChecker {
+    /// Is a user with the role `user_role` permitted to use `node`?
+    fn is_permitted(&self, node: &dyn Node, user_role: &str) -> bool;
+}
+
+/// A `PermissionChecker` which requires the user's role to be
+/// exactly the role required by the node.
+///
+/// Nodes which do not require a role are permitted for everyone.
+pub struct RoleChecker;
+
+impl PermissionChecker for RoleChecker {
+    fn is_permitted(&self, node: &dyn Node, user_role: &str) -> bool {
+        match node.required_role() {
+            Some(role) => role == user_role,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use super::*;
+    use parser::nodes::*;
+    use parser::{ParseError, Parser};
+
+    fn tree() -> Arc<RootNode> {
+        let root = RootNode::new();
+        let reload = CommandNode::new("reload", PRIORITY_DEFAULT, false, None, None, vec![]);
+        reload.set_required_role("admin").unwrap();
+        root.add_successor(reload).unwrap();
+        root.add_successor(CommandNode::new("show",
+                                            PRIORITY_DEFAULT,
+                                            false,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+        root
+    }
+
+    #[test]
+    fn permitted() {
+        let mut parser = Parser::new(tree());
+        parser.set_permission_checker(Box::new(RoleChecker), "admin");
+        assert_eq!(parser.complete(None).len(), 2);
+        assert!(parser.parse("reload").is_ok());
+    }
+
+    #[test]
+    fn forbidden() {
+        let mut parser = Parser::new(tree());
+        parser.set_permission_checker(Box::new(RoleChecker), "operator");
+        let completions = parser.complete(None);
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].help_symbol, "show");
+        match parser.parse("reload") {
+            Err(ParseError::PermissionDenied { token, .. }) => assert_eq!(token.text, "reload"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn no_role_required() {
+        let mut parser = Parser::new(tree());
+        parser.set_permission_checker(Box::new(RoleChecker), "readonly");
+        assert!(parser.parse("show").is_ok());
+    }
+
+    #[test]
+    fn no_checker() {
+        let mut parser = Parser::new(tree());
+        assert_eq!(parser.complete(None).len(), 2);
+        assert!(parser.parse("reload").is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Node Registry
+//!
+//! A `NodeRegistry` finds nodes by the dotted path of their names,
+//! like `show.interface`, for modules which don't hold the root of
+//! the tree. It only holds weak references, so it doesn't keep a
+//! tree alive once the application has dropped it.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use commands::parser::nodes::*;
+//! use commands::parser::registry::NodeRegistry;
+//!
+//! let root = RootNode::new();
+//! root.add_successor(CommandNode::new("show",
+//!                                     PRIORITY_DEFAULT,
+//!                                     false,
+//!                                     None,
+//!                                     None,
+//!                                     vec![])).unwrap();
+//! let tree: Arc<dyn Node> = root;
+//!
+//! let mut registry = NodeRegistry::new();
+//! registry.register(&tree);
+//! assert_eq!(&*registry.get("show").unwrap().name(), "show");
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use parser::nodes::{bfs, Node};
+
+/// Nodes found by their path, as registered with `register`.
+#[derive(Default)]
+pub struct NodeRegistry {
+    nodes: HashMap<String, Weak<dyn Node>>,
+}
+
+impl NodeRegistry {
+    /// Construct an empty `NodeRegistry`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register every node which can be reached from `root` by
+    /// following successors, keyed by its `NodePath`. The root
+    /// itself is registered as the empty path.
+    ///
+    /// A node which can be reached in several ways, as in shared
+    /// subtrees or beneath a `WrapperNode`, is only registered under
+    /// the path by which `bfs` reaches it first. Nodes which are
+    /// already registered under a path are replaced.
+    pub fn register(&mut self, root: &Arc<dyn Node>) {
+        for (path, node) in bfs(root.clone()).with_paths() {
+            self.nodes.insert(path.to_string(), Arc::downgrade(&node));
+        }
+    }
+
+    /// The node registered under `path`, unless it has been dropped.
+    pub fn get(&self, path: &str) -> Option<Arc<dyn Node>> {
+        self.nodes.get(path).and_then(|n| n.upgrade())
+    }
+
+    /// Forget the nodes which have been dropped.
+    pub fn prune(&mut self) {
+        self.nodes.retain(|_, n| n.strong_count() > 0);
+    }
+
+    /// The number of paths which are registered, including those of
+    /// nodes which have been dropped but not yet `prune`d.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Are no paths registered?
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::fixtures::show_tree;
+    use parser::nodes::*;
+
+    fn tree() -> Arc<dyn Node> {
+        show_tree(&[("interface", PRIORITY_DEFAULT)])
+    }
+
+    #[test]
+    fn lookup() {
+        let tree = tree();
+        let mut registry = NodeRegistry::new();
+        registry.register(&tree);
+        assert_eq!(registry.len(), 3);
+        assert_eq!(&*registry.get("").unwrap().name(), "__root__");
+        assert_eq!(&*registry.get("show.interface").unwrap().name(), "interface");
+        assert!(registry.get("show.route").is_none());
+    }
+
+    #[test]
+    fn dropped_trees_expire() {
+        let mut registry = NodeRegistry::new();
+        registry.register(&tree());
+        assert!(registry.get("show").is_none());
+        assert_eq!(registry.len(), 3);
+        registry.prune();
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn wrapped_trees_are_registered_once() {
+        let tree = tree();
+        tree.add_successor(WrapperNode::new("help", None, tree.clone())).unwrap();
+        let mut registry = NodeRegistry::new();
+        registry.register(&tree);
+        assert_eq!(registry.len(), 4);
+        assert!(registry.get("help").is_some());
+        assert!(registry.get("help.show").is_none());
+    }
+}
@@ -0,0 +1,164 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Parameter Values
+//!
+//! The values given for the parameters of a command, as found
+//! by `Parser::values` and passed to handlers when a command is
+//! executed.
+//!
+//! Flags which were given are `Bool(true)` and are absent when
+//! they weren't. Repeatable parameters collect their values in
+//! a `List`, in the order given, which `get_all` returns. Other
+//! parameters hold the `Str` that was typed, which `get_as` parses
+//! into any type implementing `FromStr`.
+//!
+//! ```
+//! use commands::parser::nodes::*;
+//! use commands::parser::Parser;
+//!
+//! let count = NamedParameterNode::with_options("count",
+//!                                              None,
+//!                                              false,
+//!                                              PRIORITY_PARAMETER,
+//!                                              vec![],
+//!                                              false,
+//!                                              None,
+//!                                              false);
+//! let root = RootNode::new();
+//! root.add_successor(CommandNode::new("ping",
+//!                                     PRIORITY_DEFAULT,
+//!                                     false,
+//!                                     None,
+//!                                     None,
+//!                                     vec![std::sync::Arc::new(count)])).unwrap();
+//!
+//! let mut parser = Parser::new(root);
+//! parser.parse("ping count 5").unwrap();
+//! let values = parser.values();
+//! assert_eq!(values.get_str("count"), Some("5"));
+//! assert_eq!(values.get_as::<u32>("count"), Some(5));
+//! ```
+
+use std::collections::HashMap;
+use std::slice;
+use std::str::FromStr;
+
+/// The value given for a parameter.
+#[derive(Clone,Debug,PartialEq)]
+pub enum ParamValue {
+    /// A flag which was given.
+    Bool(bool),
+    /// The value of a parameter which can be given once.
+    Str(String),
+    /// The values of a repeatable parameter, in the order given.
+    List(Vec<ParamValue>),
+}
+
+/// The values given for the parameters of a command, by name.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct ParameterValues {
+    values: HashMap<String, ParamValue>,
+}
+
+impl ParameterValues {
+    /// Construct an empty `ParameterValues`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the value of the parameter `name`, replacing any
+    /// earlier value.
+    pub fn insert(&mut self, name: &str, value: ParamValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// The value of the parameter `name`, if it was given.
+    pub fn get(&self, name: &str) -> Option<&ParamValue> {
+        self.values.get(name)
+    }
+
+    /// Was the parameter `name` given?
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// The value of the parameter `name` as it was typed.
+    ///
+    /// This is `None` for flags and repeatable parameters, whose
+    /// values aren't a single string.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ParamValue::Str(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of the parameter `name` parsed as a `T`.
+    ///
+    /// Flags parse from `"true"`, so `get_as::<bool>` works for them
+    /// too. This is `None` when the parameter wasn't given, when it
+    /// is repeatable, or when its value doesn't parse as a `T`.
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Option<T> {
+        match self.get(name) {
+            Some(ParamValue::Bool(value)) => value.to_string().parse().ok(),
+            Some(ParamValue::Str(value)) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// All of the values given for the parameter `name`, in the
+    /// order in which they were given.
+    ///
+    /// This is the `List` of a repeatable parameter, the single
+    /// value of any other parameter, and empty when the parameter
+    /// wasn't given.
+    pub fn get_all(&self, name: &str) -> &[ParamValue] {
+        match self.get(name) {
+            Some(ParamValue::List(values)) => values,
+            Some(value) => slice::from_ref(value),
+            None => &[],
+        }
+    }
+
+    /// The number of parameters which were given.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Were no parameters given?
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+    use super::*;
+
+    #[test]
+    fn typed_retrieval() {
+        let mut values = ParameterValues::new();
+        values.insert("verbose", ParamValue::Bool(true));
+        values.insert("address", ParamValue::Str("10.0.0.1".to_string()));
+        values.insert("count", ParamValue::Str("five".to_string()));
+        let tags = vec![ParamValue::Str("a".to_string()), ParamValue::Str("b".to_string())];
+        values.insert("tag", ParamValue::List(tags.clone()));
+
+        assert_eq!(values.get_as::<bool>("verbose"), Some(true));
+        assert_eq!(values.get_str("verbose"), None);
+        assert_eq!(values.get_as::<IpAddr>("address"), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(values.get_as::<u32>("count"), None);
+        assert_eq!(values.get_str("count"), Some("five"));
+        assert_eq!(values.get_all("tag"), &tags[..]);
+        assert_eq!(values.get_all("verbose"), &[ParamValue::Bool(true)]);
+        assert!(values.get_all("quiet").is_empty());
+        assert_eq!(values.get_as::<String>("tag"), None);
+        assert!(!values.contains("quiet"));
+        assert_eq!(values.len(), 4);
+    }
+}
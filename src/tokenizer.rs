@@ -41,8 +41,10 @@
 //! }
 //! ```
 
+use std::cmp;
 use std::fmt;
 use std::error::Error;
+use std::ops::Range;
 
 /// A position within a body of text.
 ///
@@ -69,9 +71,9 @@ impl SourceOffset {
     /// Construct a `SourceOffset`.
     pub fn new(char: usize, line: usize, column: usize) -> SourceOffset {
         SourceOffset {
-            char: char,
-            line: line,
-            column: column,
+            char,
+            line,
+            column,
         }
     }
 }
@@ -89,8 +91,8 @@ impl SourceLocation {
     /// Construct a `SourceLocation`.
     pub fn new(start: SourceOffset, end: SourceOffset) -> SourceLocation {
         SourceLocation {
-            start: start,
-            end: end,
+            start,
+            end,
         }
     }
 }
@@ -111,25 +113,23 @@ pub enum TokenizerError {
     UnclosedDoubleQuoteAtEndOfInput,
 }
 
-impl Error for TokenizerError {
-    fn description(&self) -> &str {
+impl Error for TokenizerError {}
+
+impl fmt::Display for TokenizerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match *self {
-            TokenizerError::CharacterNotAllowedHere(_) => "Character not allowed here",
-            TokenizerError::SpecialNotYetImplemented(_) => "Special not yet implemented",
-            TokenizerError::EscapingBackslashAtEndOfInput => "Escaping backlash at end of input",
+            TokenizerError::CharacterNotAllowedHere(_) => "Character not allowed here".fmt(f),
+            TokenizerError::SpecialNotYetImplemented(_) => "Special not yet implemented".fmt(f),
+            TokenizerError::EscapingBackslashAtEndOfInput => {
+                "Escaping backlash at end of input".fmt(f)
+            }
             TokenizerError::UnclosedDoubleQuoteAtEndOfInput => {
-                "Unclosed double quote at end of input"
+                "Unclosed double quote at end of input".fmt(f)
             }
         }
     }
 }
 
-impl fmt::Display for TokenizerError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        self.description().fmt(f)
-    }
-}
-
 /// The role that a token plays: `Whitespace` or `Word`.
 #[derive(Clone,Copy,Debug,PartialEq)]
 pub enum TokenType {
@@ -159,13 +159,30 @@ pub struct Token<'t> {
 impl<'t> Token<'t> {
     /// Construct a `Token`. The lifetime parameter `'t` refers to the lifetime of the
     /// text being tokenized.
-    pub fn new(text: &'t str, token_type: TokenType, location: SourceLocation) -> Token {
+    pub fn new(text: &'t str, token_type: TokenType, location: SourceLocation) -> Token<'t> {
         Token {
-            text: text,
-            token_type: token_type,
-            location: location,
+            text,
+            token_type,
+            location,
         }
     }
+
+    /// The bytes of `line`, the body of text which the token was
+    /// taken from, that the token covers. Its `location` is in
+    /// characters, which differ from bytes beyond ASCII.
+    ///
+    /// ```
+    /// use commands::tokenizer::tokenize;
+    ///
+    /// let line = "set név \"a b\"";
+    /// let tokens = tokenize(line).unwrap();
+    /// assert_eq!(&line[tokens[2].span(line)], "név");
+    /// assert_eq!(&line[tokens[4].span(line)], "\"a b\"");
+    /// ```
+    pub fn span(&self, line: &str) -> Range<usize> {
+        let start = line.char_indices().nth(self.location.start.char).map_or(line.len(), |c| c.0);
+        start..cmp::min(start + self.text.len(), line.len())
+    }
 }
 
 #[derive(Clone,Copy,PartialEq)]
@@ -185,17 +202,29 @@ struct Tokenizer<'t> {
     token_type: TokenType,
     token_start: usize,
     token_end: usize,
+    /// The byte of the character being tokenized, and of the one
+    /// after it, as tokens are sliced by bytes but located by
+    /// characters.
+    byte: usize,
+    next_byte: usize,
+    /// The bytes of the token, with the end after its last character.
+    token_start_byte: usize,
+    token_end_byte: usize,
     tokens: Vec<Token<'t>>,
 }
 
 impl<'t> Tokenizer<'t> {
-    fn new(text: &'t str) -> Tokenizer {
+    fn new(text: &'t str) -> Tokenizer<'t> {
         Tokenizer {
-            text: text,
+            text,
             state: State::Initial,
             token_type: TokenType::Invalid,
             token_start: 0,
             token_end: 0,
+            byte: 0,
+            next_byte: 0,
+            token_start_byte: 0,
+            token_end_byte: 0,
             tokens: vec![],
         }
     }
@@ -205,10 +234,12 @@ impl<'t> Tokenizer<'t> {
         self.token_type = TokenType::Invalid;
         self.token_start = 0;
         self.token_end = 0;
+        self.token_start_byte = 0;
+        self.token_end_byte = 0;
     }
 
     fn reduce(&mut self) {
-        let token_text = &self.text[self.token_start..self.token_end + 1];
+        let token_text = &self.text[self.token_start_byte..self.token_end_byte];
         let loc = SourceLocation::new(SourceOffset::new(self.token_start, 0, self.token_start),
                                       SourceOffset::new(self.token_end, 0, self.token_end));
         self.tokens.push(Token::new(token_text, self.token_type, loc));
@@ -218,6 +249,7 @@ impl<'t> Tokenizer<'t> {
     fn shift(&mut self, offset: usize, next_state: State) {
         self.recognize(offset, next_state);
         self.token_end = offset;
+        self.token_end_byte = self.next_byte;
         self.state = next_state;
     }
 
@@ -229,6 +261,7 @@ impl<'t> Tokenizer<'t> {
                 TokenType::Word
             };
             self.token_start = offset;
+            self.token_start_byte = self.byte;
         }
     }
 
@@ -253,7 +286,9 @@ impl<'t> Tokenizer<'t> {
     }
 
     fn tokenize(&mut self) -> Result<(), TokenizerError> {
-        for (offset, c) in self.text.chars().enumerate() {
+        for (offset, (byte, c)) in self.text.char_indices().enumerate() {
+            self.byte = byte;
+            self.next_byte = byte + c.len_utf8();
             match self.state {
                 State::Initial => self.initial(offset, c),
                 State::Whitespace => {
@@ -330,7 +365,7 @@ impl<'t> Tokenizer<'t> {
 }
 
 /// Tokenize a body of text.
-pub fn tokenize(text: &str) -> Result<Vec<Token>, TokenizerError> {
+pub fn tokenize(text: &str) -> Result<Vec<Token<'_>>, TokenizerError> {
     let mut tokenizer = Tokenizer::new(text);
     match tokenizer.tokenize() {
         Ok(_) => Ok(tokenizer.tokens),
@@ -342,70 +377,66 @@ pub fn tokenize(text: &str) -> Result<Vec<Token>, TokenizerError> {
 mod test {
     use super::*;
 
-    fn mk_token(text: &str, token_type: TokenType, start: usize, end: usize) -> Token {
+    fn mk_token(text: &str, token_type: TokenType, start: usize, end: usize) -> Token<'_> {
         Token::new(text,
                    token_type,
                    SourceLocation::new(SourceOffset::new(start, 0, start),
                                        SourceOffset::new(end, 0, end)))
     }
 
+    #[test]
+    fn multibyte_locations() {
+        let tokens = tokenize("né ok").unwrap();
+        assert_eq!(tokens[2].text, "ok");
+        assert_eq!(tokens[0].text, "né");
+        assert_eq!(tokens[2].location.start.char, 3);
+        assert_eq!(tokens[2].span("né ok"), 4..6);
+    }
+
     #[test]
     fn empty_test() {
-        match tokenize("") {
-            Ok(ts) => assert_eq!(ts.len(), 0),
-            _ => {}
+        if let Ok(ts) = tokenize("") {
+            assert_eq!(ts.len(), 0);
         };
     }
 
     #[test]
     fn single_word() {
-        match tokenize("a") {
-            Ok(ts) => {
-                assert_eq!(ts.len(), 1);
-                assert_eq!(ts[0], mk_token("a", TokenType::Word, 0, 0));
-            }
-            _ => {}
+        if let Ok(ts) = tokenize("a") {
+            assert_eq!(ts.len(), 1);
+            assert_eq!(ts[0], mk_token("a", TokenType::Word, 0, 0));
         };
     }
 
     #[test]
     fn multiple_words() {
-        match tokenize(" aa bb  ccc ") {
-            Ok(ts) => {
-                assert_eq!(ts.len(), 7);
-                assert_eq!(ts[0], mk_token(" ", TokenType::Whitespace, 0, 0));
-                assert_eq!(ts[1], mk_token("aa", TokenType::Word, 1, 2));
-                assert_eq!(ts[2], mk_token(" ", TokenType::Whitespace, 3, 3));
-                assert_eq!(ts[3], mk_token("bb", TokenType::Word, 4, 5));
-                assert_eq!(ts[4], mk_token("  ", TokenType::Whitespace, 6, 7));
-                assert_eq!(ts[5], mk_token("ccc", TokenType::Word, 8, 10));
-                assert_eq!(ts[6], mk_token(" ", TokenType::Whitespace, 11, 11));
-            }
-            _ => {}
+        if let Ok(ts) = tokenize(" aa bb  ccc ") {
+            assert_eq!(ts.len(), 7);
+            assert_eq!(ts[0], mk_token(" ", TokenType::Whitespace, 0, 0));
+            assert_eq!(ts[1], mk_token("aa", TokenType::Word, 1, 2));
+            assert_eq!(ts[2], mk_token(" ", TokenType::Whitespace, 3, 3));
+            assert_eq!(ts[3], mk_token("bb", TokenType::Word, 4, 5));
+            assert_eq!(ts[4], mk_token("  ", TokenType::Whitespace, 6, 7));
+            assert_eq!(ts[5], mk_token("ccc", TokenType::Word, 8, 10));
+            assert_eq!(ts[6], mk_token(" ", TokenType::Whitespace, 11, 11));
         };
     }
 
     #[test]
     fn quoted_text() {
-        match tokenize("a \"b c\"") {
-            Ok(ts) => {
-                assert_eq!(ts.len(), 3);
-                assert_eq!(ts[0], mk_token("a", TokenType::Word, 0, 0));
-                assert_eq!(ts[1], mk_token(" ", TokenType::Whitespace, 1, 1));
-                assert_eq!(ts[2], mk_token("\"b c\"", TokenType::Word, 2, 6));
-            }
-            _ => {}
+        if let Ok(ts) = tokenize("a \"b c\"") {
+            assert_eq!(ts.len(), 3);
+            assert_eq!(ts[0], mk_token("a", TokenType::Word, 0, 0));
+            assert_eq!(ts[1], mk_token(" ", TokenType::Whitespace, 1, 1));
+            assert_eq!(ts[2], mk_token("\"b c\"", TokenType::Word, 2, 6));
         };
     }
 
     #[test]
     fn escaped_whitespace_in_word() {
-        match tokenize("a\\ b") {
-            Ok(ts) => {
-                assert_eq!(ts.len(), 1);
-                assert_eq!(ts[0], mk_token("a\\ b", TokenType::Word, 0, 3));
-            }
-            _ => {}
+        if let Ok(ts) = tokenize("a\\ b") {
+            assert_eq!(ts.len(), 1);
+            assert_eq!(ts[0], mk_token("a\\ b", TokenType::Word, 0, 3));
         };
     }
 
@@ -427,27 +458,24 @@ mod test {
     #[test]
     #[should_panic]
     fn escaping_backslash_at_end_of_input() {
-        match tokenize("ab \\") {
-            Err(TokenizerError::EscapingBackslashAtEndOfInput) => panic!(),
-            _ => {}
+        if let Err(TokenizerError::EscapingBackslashAtEndOfInput) = tokenize("ab \\") {
+            panic!()
         }
     }
 
     #[test]
     #[should_panic]
     fn unclosed_double_quote_at_end_of_input() {
-        match tokenize("ab \"") {
-            Err(TokenizerError::UnclosedDoubleQuoteAtEndOfInput) => panic!(),
-            _ => {}
+        if let Err(TokenizerError::UnclosedDoubleQuoteAtEndOfInput) = tokenize("ab \"") {
+            panic!()
         }
     }
 
     #[test]
     #[should_panic]
     fn escaped_double_quote_at_end_of_input() {
-        match tokenize("ab \"\\") {
-            Err(TokenizerError::EscapingBackslashAtEndOfInput) => panic!(),
-            _ => {}
+        if let Err(TokenizerError::EscapingBackslashAtEndOfInput) = tokenize("ab \"\\") {
+            panic!()
         }
     }
 }
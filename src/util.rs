@@ -7,6 +7,9 @@
 //! Utilities
 
 use std::cmp;
+use std::ops::Range;
+use std::sync::Arc;
+use parser::nodes::{dfs, Node};
 
 /// Longest Common Prefix
 ///
@@ -38,9 +41,161 @@ pub fn longest_common_prefix(strings: Vec<&str>) -> &str {
     &strings[0][..len]
 }
 
+/// Levenshtein distance
+///
+/// Calculate the number of single character insertions, deletions
+/// and substitutions needed to turn `a` into `b`.
+///
+/// ```
+/// use commands::util::levenshtein;
+///
+/// assert_eq!(levenshtein("kitten", "sitting"), 3);
+/// ```
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    // The distances from the first i characters of `a` to each
+    // prefix of `b`, for the previous and current values of i.
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1);
+        }
+        previous.copy_from_slice(&current);
+    }
+    previous[b.len()]
+}
+
+/// The most suggestions which `suggestions` gives.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Suggestions for a mistyped `token`.
+///
+/// Find the `candidates` which are close to `token`, as measured by
+/// `levenshtein`, for messages like "did you mean 'show'?". Tokens
+/// of 1 or 2 characters may be 1 edit away, and longer ones 2. At
+/// most `MAX_SUGGESTIONS` are given, the closest first, and those
+/// which are as close in the order of `candidates`.
+///
+/// ```
+/// use commands::util::suggestions;
+///
+/// assert_eq!(suggestions("shwo", vec!["show", "reload", "shop"]), vec!["show", "shop"]);
+/// assert!(suggestions("xyzzy", vec!["show"]).is_empty());
+/// ```
+pub fn suggestions<'a, I>(token: &str, candidates: I) -> Vec<&'a str>
+    where I: IntoIterator<Item = &'a str>
+{
+    let max_distance = cmp::min(token.chars().count().div_ceil(2), 2);
+    let mut close = vec![];
+    for candidate in candidates {
+        let distance = levenshtein(token, candidate);
+        if distance <= max_distance && !close.iter().any(|&(_, c)| c == candidate) {
+            close.push((distance, candidate));
+        }
+    }
+    close.sort_by_key(|&(distance, _)| distance);
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, c)| c).collect()
+}
+
+/// The fewest characters of `text` which hold the characters of
+/// `pattern` in order, ignoring case, as `ifc` is found within the
+/// first 8 characters of `interface-config`. `None` is returned
+/// when `pattern` isn't a subsequence of `text`. Smaller spans are
+/// tighter matches, and the span is at least the length of
+/// `pattern`.
+///
+/// ```
+/// use commands::util::subsequence_span;
+///
+/// assert_eq!(subsequence_span("ifc", "interface-config"), Some(8));
+/// assert_eq!(subsequence_span("SH", "show"), Some(2));
+/// assert_eq!(subsequence_span("xyz", "show"), None);
+/// ```
+pub fn subsequence_span(pattern: &str, text: &str) -> Option<usize> {
+    let lower = |s: &str| s.chars().flat_map(char::to_lowercase).collect::<Vec<_>>();
+    let (pattern, text) = (lower(pattern), lower(text));
+    let first = match pattern.first() {
+        Some(&c) => c,
+        None => return Some(0),
+    };
+    // The tightest span starts at one of the occurrences of the
+    // first character, and takes each character after it as early
+    // as possible.
+    (0..text.len())
+        .filter(|&start| text[start] == first)
+        .filter_map(|start| {
+            let mut end = start;
+            for &c in &pattern[1..] {
+                end += 1 + text.get(end + 1..)?.iter().position(|&t| t == c)?;
+            }
+            Some(end - start + 1)
+        })
+        .min()
+}
+
+/// Point at the bytes `span` of `line`, as for an error about one
+/// of its tokens: the line, and then a line with `^` under the first
+/// character of the span and `~` under the rest of it. An empty span
+/// is pointed at with a `^` alone. A span which runs past the end of
+/// the line, or into a character, is cut back to fit.
+///
+/// ```
+/// use commands::util::caret_diagnostic;
+///
+/// assert_eq!(caret_diagnostic("ping count three", 11..16),
+///            "ping count three\n           ^~~~~");
+/// assert_eq!(caret_diagnostic("ping", 4..4), "ping\n    ^");
+/// ```
+pub fn caret_diagnostic(line: &str, span: Range<usize>) -> String {
+    let end = char_boundary(line, span.end);
+    let start = char_boundary(line, cmp::min(span.start, end));
+    let indent = line[..start].chars().count();
+    let width = cmp::max(line[start..end].chars().count(), 1);
+    format!("{}\n{}^{}", line, " ".repeat(indent), "~".repeat(width - 1))
+}
+
+/// The last character boundary of `text` at or before `index`.
+fn char_boundary(text: &str, index: usize) -> usize {
+    (0..=cmp::min(index, text.len())).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0)
+}
+
+/// All commands of a tree
+///
+/// List the commands which can be reached from `root`, at any depth,
+/// in the order of `dfs`, as for an index of the help for each. Hidden
+/// commands are included. Each command is listed once, however it is
+/// reached.
+///
+/// ```
+/// use std::sync::Arc;
+/// use commands::parser::nodes::*;
+/// use commands::util::flatten_commands;
+///
+/// let root = RootNode::new();
+/// let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+/// show.add_successor(KeywordNode::new("all", PRIORITY_DEFAULT, false, None)).unwrap();
+/// show.add_successor(CommandNode::new("route",
+///                                     PRIORITY_DEFAULT,
+///                                     true,
+///                                     None,
+///                                     None,
+///                                     vec![])).unwrap();
+/// root.add_successor(show).unwrap();
+/// let tree: Arc<dyn Node> = root;
+/// let names = flatten_commands(&tree).iter().map(|c| c.name().to_string()).collect::<Vec<_>>();
+/// assert_eq!(names, vec!["show", "route"]);
+/// ```
+pub fn flatten_commands(root: &Arc<dyn Node>) -> Vec<Arc<dyn Node>> {
+    dfs(root.clone()).filter(|n| n.as_command().is_some()).collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use parser::nodes::*;
 
     #[test]
     fn empty_lcp() {
@@ -63,8 +218,83 @@ mod test {
         assert_eq!(longest_common_prefix(vec!["aba", "abb", "abc"]), "ab");
     }
 
+    #[test]
+    fn tightest_subsequence() {
+        assert_eq!(subsequence_span("ab", "axxbab"), Some(2));
+        assert_eq!(subsequence_span("", "show"), Some(0));
+        assert_eq!(subsequence_span("ss", "show"), None);
+    }
+
     #[test]
     fn valid_is_shortest_lcp() {
         assert_eq!(longest_common_prefix(vec!["aba", "ab", "abc"]), "ab");
     }
+
+    #[test]
+    fn levenshtein_exact() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("show", "show"), 0);
+    }
+
+    #[test]
+    fn levenshtein_distance_1() {
+        assert_eq!(levenshtein("show", "shw"), 1);
+        assert_eq!(levenshtein("show", "shows"), 1);
+        assert_eq!(levenshtein("show", "shou"), 1);
+    }
+
+    #[test]
+    fn levenshtein_distance_2() {
+        assert_eq!(levenshtein("show", "sohw"), 2);
+        assert_eq!(levenshtein("route", "rote"), 1);
+        assert_eq!(levenshtein("route", "rout3s"), 2);
+    }
+
+    #[test]
+    fn levenshtein_beyond() {
+        assert_eq!(levenshtein("show", ""), 4);
+        assert_eq!(levenshtein("", "ab"), 2);
+        assert_eq!(levenshtein("show", "reload"), 5);
+        assert_eq!(levenshtein("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn flatten_nested_commands() {
+        let root = RootNode::new();
+        let show = CommandNode::new("show", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let interface = CommandNode::new("interface", PRIORITY_DEFAULT, false, None, None, vec![]);
+        let brief = CommandNode::new("brief", PRIORITY_DEFAULT, false, None, None, vec![]);
+        interface.add_successor(brief).unwrap();
+        show.add_successor(interface).unwrap();
+        root.add_successor(show).unwrap();
+        root.add_successor(CommandNode::new("debug",
+                                            PRIORITY_DEFAULT,
+                                            true,
+                                            None,
+                                            None,
+                                            vec![])).unwrap();
+        root.add_successor(WrapperNode::new("help", None, root.clone())).unwrap();
+        let tree: Arc<dyn Node> = root;
+
+        let commands = flatten_commands(&tree);
+        let names = commands.iter().map(|c| c.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["show", "interface", "brief", "debug"]);
+        assert!(flatten_commands(&(RootNode::new() as Arc<dyn Node>)).is_empty());
+    }
+
+    #[test]
+    fn carets_fit_the_line() {
+        assert_eq!(caret_diagnostic("ping", 2..10), "ping\n  ^~");
+        assert_eq!(caret_diagnostic("ping", 6..8), "ping\n    ^");
+        // "ö" is 2 bytes long, and byte 3 is within it.
+        assert_eq!(caret_diagnostic("shöw", 3..5), "shöw\n  ^~");
+    }
+
+    #[test]
+    fn suggestions_are_bounded() {
+        let candidates = vec!["show", "shop", "shot", "shoe", "set"];
+        assert_eq!(suggestions("sho", candidates.clone()), vec!["show", "shop", "shot"]);
+        assert_eq!(suggestions("st", candidates.clone()), vec!["set"]);
+        assert!(suggestions("", candidates).is_empty());
+    }
 }